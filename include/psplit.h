@@ -0,0 +1,47 @@
+/*
+ * Embedding interface of psplit, linking libpsplit.so built by
+ * `cargo build --release`.
+ *
+ * Every splitter runs on a thread of its own and is stopped by a SIGTERM
+ * directed at that thread. Processes embedding it should leave SIGTERM,
+ * SIGINT, SIGHUP and SIGUSR1 blocked in the threads they start or handle
+ * them themselves.
+ */
+#ifndef PSPLIT_H
+#define PSPLIT_H
+
+#include <stddef.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct PsplitHandle psplit_t;
+
+/*
+ * Load the configuration file at config_path and split its pipes on a new
+ * thread. Returns NULL when the path or the configuration is invalid or the
+ * thread cannot be started.
+ */
+psplit_t *psplit_start(const char *config_path);
+
+/*
+ * Stop the splitter, wait for its workers to drain and release it. Returns
+ * the exit status of the psplit binary: 0 clean, 3 runtime error and
+ * 4 partial failure, or -1 when splitter is NULL.
+ */
+int psplit_stop(psplit_t *splitter);
+
+/*
+ * Write the status report of the splitter, one line per pipe, to buf of
+ * len bytes, NUL terminated and truncated when too long. Returns the length
+ * of the whole report as snprintf does, or -1 when splitter is NULL.
+ */
+ssize_t psplit_stats(const psplit_t *splitter, char *buf, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PSPLIT_H */
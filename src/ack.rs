@@ -0,0 +1,176 @@
+//! Acknowledged delivery of records to an output.
+//!
+//! Every record is prefixed with a sequence number and a space. The consumer
+//! acknowledges by writing the sequence number of the last record it has
+//! processed, one per line, to the companion ack FIFO; an ack covers every
+//! record up to and including that number. Records that stay unacknowledged
+//! for longer than the ack timeout are written again with the same sequence
+//! number, so consumers can discard duplicates.
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::{FifoOptions, Writer};
+
+/// Maximum number of records waiting for an acknowledgement
+pub(crate) const ACK_WINDOW: usize = 256;
+
+/// Tracks the records sent to an output until they are acknowledged
+pub(crate) struct AckTracker {
+    /// Path of the ack FIFO
+    path: PathBuf,
+    /// Mode and label of the ack FIFO
+    fifo: FifoOptions,
+    /// Read end of the ack FIFO, once opened
+    file: Option<File>,
+    /// Bytes read from the ack FIFO not yet forming a complete line
+    partial: Vec<u8>,
+    /// Retransmit records unacknowledged for this long
+    timeout: Duration,
+    /// Sequence number of the next record
+    next_seq: u64,
+    /// Sent records as `(sequence, framed record, last sent)`
+    unacked: VecDeque<(u64, Vec<u8>, Instant)>,
+    /// Index in `unacked` of the next record to retransmit
+    resend_from: Option<usize>,
+}
+
+impl AckTracker {
+    /// Tracker reading acknowledgements from the FIFO at `path`
+    pub fn new(path: PathBuf, fifo: FifoOptions, timeout: Duration) -> AckTracker {
+        AckTracker {
+            path,
+            fifo,
+            file: None,
+            partial: Vec::new(),
+            timeout,
+            next_seq: 1,
+            unacked: VecDeque::new(),
+            resend_from: None,
+        }
+    }
+
+    /// Number the record, remember it until acknowledged and return the
+    /// bytes to write
+    pub fn track(&mut self, record: Vec<u8>) -> Vec<u8> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let mut framed = format!("{seq} ").into_bytes();
+        framed.extend_from_slice(&record);
+        self.unacked
+            .push_back((seq, framed.clone(), Instant::now()));
+        framed
+    }
+
+    /// Whether no more records can be sent before some are acknowledged
+    pub fn is_full(&self) -> bool {
+        self.unacked.len() >= ACK_WINDOW
+    }
+
+    /// Resend every unacknowledged record, the consumer changed
+    pub fn rewind(&mut self) {
+        if !self.unacked.is_empty() {
+            self.resend_from = Some(0);
+        }
+    }
+
+    /// Next record to write again, if any has timed out
+    pub fn next_retransmit(&mut self) -> Option<Vec<u8>> {
+        if self.resend_from.is_none() {
+            let timed_out = self
+                .unacked
+                .front()
+                .is_some_and(|(_, _, sent)| sent.elapsed() >= self.timeout);
+            if timed_out {
+                self.resend_from = Some(0);
+            }
+        }
+
+        let index = self.resend_from?;
+        match self.unacked.get_mut(index) {
+            Some((_, framed, sent)) => {
+                *sent = Instant::now();
+                self.resend_from = Some(index + 1);
+                Some(framed.clone())
+            }
+            None => {
+                self.resend_from = None;
+                None
+            }
+        }
+    }
+
+    /// Read pending acknowledgements and forget the acknowledged records
+    pub fn receive(&mut self) -> io::Result<()> {
+        if self.file.is_none() {
+            Writer::create_fifo(&self.path, &self.fifo)?;
+            self.file = Some(
+                OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(&self.path)?,
+            );
+        }
+
+        let mut buffer = [0u8; 512];
+        loop {
+            let read = match self.file.as_mut().unwrap().read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+            self.partial.extend_from_slice(&buffer[..read]);
+        }
+
+        while let Some(end) = self.partial.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            if let Some(seq) = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|l| l.trim().parse::<u64>().ok())
+            {
+                self.acknowledge(seq);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget every record up to and including `seq`
+    fn acknowledge(&mut self, seq: u64) {
+        let mut acked = 0;
+        while self.unacked.front().is_some_and(|(s, _, _)| *s <= seq) {
+            self.unacked.pop_front();
+            acked += 1;
+        }
+        self.resend_from = self.resend_from.map(|index| index.saturating_sub(acked));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn acknowledge_and_retransmit() {
+        let mut tracker = AckTracker::new(
+            PathBuf::from("/nonexistent"),
+            FifoOptions::default(),
+            Duration::ZERO,
+        );
+
+        assert_eq!(tracker.track(b"a\n".to_vec()), b"1 a\n");
+        assert_eq!(tracker.track(b"b\n".to_vec()), b"2 b\n");
+        assert_eq!(tracker.track(b"c\n".to_vec()), b"3 c\n");
+
+        tracker.acknowledge(1);
+        assert_eq!(tracker.next_retransmit(), Some(b"2 b\n".to_vec()));
+        tracker.acknowledge(2);
+        assert_eq!(tracker.next_retransmit(), Some(b"3 c\n".to_vec()));
+        tracker.acknowledge(3);
+        assert_eq!(tracker.next_retransmit(), None);
+    }
+}
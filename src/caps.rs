@@ -0,0 +1,225 @@
+//! Linux capabilities of a splitter started with elevated privileges, to
+//! create FIFOs in protected directories or label them. Once initialised it
+//! drops every capability but those of `[DEFAULT] keep_capabilities`, from
+//! its effective, permitted and inheritable sets as well as its bounding
+//! and ambient sets, so neither a worker nor a hook it runs can regain them.
+//! Capabilities belong to threads: they are dropped before the workers are
+//! started, which inherit them.
+use std::fs;
+use std::io;
+
+/// `_LINUX_CAPABILITY_VERSION_3`, 64 bit sets split in two
+const VERSION: u32 = 0x2008_0522;
+
+/// Names of the capabilities, by number, as in `capabilities(7)` without
+/// their `CAP_` prefix
+const NAMES: &[&str] = &[
+    "chown",
+    "dac_override",
+    "dac_read_search",
+    "fowner",
+    "fsetid",
+    "kill",
+    "setgid",
+    "setuid",
+    "setpcap",
+    "linux_immutable",
+    "net_bind_service",
+    "net_broadcast",
+    "net_admin",
+    "net_raw",
+    "ipc_lock",
+    "ipc_owner",
+    "sys_module",
+    "sys_rawio",
+    "sys_chroot",
+    "sys_ptrace",
+    "sys_pacct",
+    "sys_admin",
+    "sys_boot",
+    "sys_nice",
+    "sys_resource",
+    "sys_time",
+    "sys_tty_config",
+    "mknod",
+    "lease",
+    "audit_write",
+    "audit_control",
+    "setfcap",
+    "mac_override",
+    "mac_admin",
+    "syslog",
+    "wake_alarm",
+    "block_suspend",
+    "audit_read",
+    "perfmon",
+    "bpf",
+    "checkpoint_restore",
+];
+
+#[repr(C)]
+struct Header {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Data {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// Set of capabilities, by number
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct CapSet(pub u64);
+
+impl CapSet {
+    /// Capabilities of the comma separated `names`, `all` keeping them all
+    pub fn parse(names: &str) -> Result<CapSet, String> {
+        let mut set = 0;
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let name = name.to_lowercase();
+            if name == "all" {
+                return Ok(CapSet(u64::MAX));
+            }
+            let name = name.strip_prefix("cap_").unwrap_or(&name);
+            match NAMES.iter().position(|known| *known == name) {
+                Some(number) => set |= 1 << number,
+                None => return Err(name.to_owned()),
+            }
+        }
+        Ok(CapSet(set))
+    }
+
+    /// Whether capability `number` is in the set
+    fn contains(&self, number: usize) -> bool {
+        number >= 64 || self.0 & (1 << number) != 0
+    }
+}
+
+impl std::fmt::Display for CapSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = NAMES
+            .iter()
+            .enumerate()
+            .filter(|(number, _)| self.contains(*number))
+            .map(|(_, name)| *name)
+            .collect();
+        match names.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", names.join(",")),
+        }
+    }
+}
+
+/// Effective, permitted and inheritable sets of the calling thread
+fn get() -> io::Result<[Data; 2]> {
+    let mut header = Header {
+        version: VERSION,
+        pid: 0,
+    };
+    let mut data = [Data::default(); 2];
+    match unsafe { libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) } {
+        0 => Ok(data),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Replace the sets of the calling thread
+fn set(data: &[Data; 2]) -> io::Result<()> {
+    let mut header = Header {
+        version: VERSION,
+        pid: 0,
+    };
+    match unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Permitted capabilities of the calling thread
+pub(crate) fn permitted() -> io::Result<CapSet> {
+    let data = get()?;
+    Ok(CapSet(
+        data[0].permitted as u64 | (data[1].permitted as u64) << 32,
+    ))
+}
+
+/// Highest capability known to the kernel
+fn last_cap() -> usize {
+    fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|last| last.trim().parse().ok())
+        .unwrap_or(NAMES.len() - 1)
+}
+
+/// Drop every capability of the calling thread but those of `keep`,
+/// returning the ones left, `None` when it had none to drop
+pub(crate) fn drop_except(keep: CapSet) -> io::Result<Option<CapSet>> {
+    let held = permitted()?;
+    if held.0 == 0 || keep.0 == u64::MAX {
+        return Ok(None);
+    }
+    // Dropping from the bounding set takes CAP_SETPCAP, still held
+    for number in (0..=last_cap()).filter(|number| !keep.contains(*number)) {
+        if unsafe { libc::prctl(libc::PR_CAPBSET_DROP, number, 0, 0, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    let ambient = libc::PR_CAP_AMBIENT_CLEAR_ALL as libc::c_ulong;
+    if unsafe { libc::prctl(libc::PR_CAP_AMBIENT, ambient, 0, 0, 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let kept = held.0 & keep.0;
+    let data = [kept as u32, (kept >> 32) as u32].map(|half| Data {
+        effective: half,
+        permitted: half,
+        inheritable: 0,
+    });
+    set(&data)?;
+    Ok(Some(CapSet(kept)))
+}
+
+/// Forbid the process and its children from gaining privileges, through
+/// set-user-ID binaries or file capabilities, `--no-new-privs`
+pub(crate) fn no_new_privs() -> io::Result<()> {
+    match unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_capabilities() {
+        assert_eq!(CapSet::parse(""), Ok(CapSet(0)));
+        assert_eq!(CapSet::parse("chown, CAP_FOWNER"), Ok(CapSet(0b1001)));
+        assert_eq!(CapSet::parse("all"), Ok(CapSet(u64::MAX)));
+        assert_eq!(CapSet::parse("chown,teleport"), Err("teleport".into()));
+        assert_eq!(CapSet(0b1001).to_string(), "chown,fowner");
+        assert_eq!(CapSet(0).to_string(), "none");
+    }
+
+    #[test]
+    fn drop_in_thread() {
+        // Capabilities belong to threads, the test process keeps its own
+        std::thread::spawn(|| {
+            let held = permitted().unwrap();
+            let kept = drop_except(CapSet(1 << 3)).unwrap();
+            match held.0 {
+                0 => assert_eq!(kept, None),
+                _ => {
+                    assert_eq!(kept, Some(CapSet(held.0 & 1 << 3)));
+                    assert_eq!(permitted().unwrap(), CapSet(held.0 & 1 << 3));
+                }
+            }
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+//! Console output of the splitter.
+//!
+//! Every message goes through [`print`]. When standard output is a terminal,
+//! messages are coloured by kind and the pipe following the `->`, `<-` or
+//! `<>` marker is aligned; when it is piped, lines are printed unchanged.
+//! Once a log file is set, messages are written there instead. The binary
+//! running in the foreground logs to standard error, leaving standard
+//! output to the commands printing results.
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::logfile::LogFile;
+use crate::secrets;
+use crate::SplitIn;
+
+/// Log file messages are written to instead of standard output
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Messages are printed to standard error rather than standard output
+static STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Width the text before a pipe marker is padded to on a terminal
+const MARKER_COLUMN: usize = 20;
+
+/// Markers separating a message from the pipe it is about
+const MARKERS: [&str; 3] = [" -> ", " <- ", " <> "];
+
+/// Whether the stream messages are printed to is a terminal
+pub(crate) fn is_tty() -> bool {
+    static TTY: OnceLock<(bool, bool)> = OnceLock::new();
+    let (stdout, stderr) = *TTY.get_or_init(|| unsafe {
+        (
+            libc::isatty(libc::STDOUT_FILENO) == 1,
+            libc::isatty(libc::STDERR_FILENO) == 1,
+        )
+    });
+    match STDERR.load(Ordering::Relaxed) {
+        true => stderr,
+        false => stdout,
+    }
+}
+
+/// Print `text` on the stream messages go to
+fn emit(text: &str) {
+    match STDERR.load(Ordering::Relaxed) {
+        true => eprintln!("{text}"),
+        false => println!("{text}"),
+    }
+}
+
+/// ANSI colour of a message, by kind
+fn color(line: &str) -> Option<&'static str> {
+    if line.starts_with("Warning") {
+        Some("33")
+    } else if line.contains(" Error ")
+        || line.starts_with("Evicting")
+        || line.starts_with("Incident")
+    {
+        Some("31")
+    } else if line.starts_with("Stopping") {
+        Some("2")
+    } else if [
+        "Reading",
+        "Writing",
+        "Reattached",
+        "Replaying",
+        "Queue drained",
+    ]
+    .iter()
+    .any(|p| line.starts_with(p))
+    {
+        Some("32")
+    } else {
+        None
+    }
+}
+
+/// Pad the text before the first pipe marker to [`MARKER_COLUMN`]
+fn align(line: &str) -> String {
+    let marker = MARKERS
+        .iter()
+        .filter_map(|m| line.find(m))
+        .min()
+        .filter(|at| *at < MARKER_COLUMN);
+    match marker {
+        Some(at) => format!("{:<MARKER_COLUMN$}{}", &line[..at], &line[at..]),
+        None => line.to_owned(),
+    }
+}
+
+/// Render a message for a terminal
+fn decorate(line: &str) -> String {
+    let line = align(line);
+    match color(&line) {
+        Some(code) => format!("\x1b[{code}m{line}\x1b[0m"),
+        None => line,
+    }
+}
+
+/// Write every further message to `log`
+pub(crate) fn set_log_file(log: LogFile) {
+    *LOG_FILE.lock().unwrap() = Some(log);
+}
+
+/// Print every further message to standard error
+pub(crate) fn use_stderr() {
+    STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Point the standard streams at the log file, see
+/// [`LogFile::redirect_stdio`]
+pub(crate) fn redirect_stdio() -> io::Result<()> {
+    match LOG_FILE.lock().unwrap().as_mut() {
+        Some(log) => log.redirect_stdio(),
+        None => Err(io::Error::other("no log file to redirect to")),
+    }
+}
+
+/// Print a message
+pub(crate) fn print(line: &str) {
+    let line = &secrets::redact(line);
+    if let Some(log) = LOG_FILE.lock().unwrap().as_mut() {
+        match log.write_line(line) {
+            Ok(_) => return,
+            Err(e) => emit(&format!("Log file Error {:?}", e)),
+        }
+    }
+    if is_tty() {
+        emit(&decorate(line));
+    } else {
+        emit(line);
+    }
+}
+
+/// Print the configured pipes as a table, on a terminal only
+pub(crate) fn topology(entries: &[Arc<SplitIn>]) {
+    if !is_tty() {
+        return;
+    }
+    let width = entries
+        .iter()
+        .flat_map(|i| std::iter::once(i.name()).chain(i.outputs.iter().map(|o| o.name())))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+
+    for input in entries {
+        emit(&format!(
+            "\x1b[1mIN \x1b[0m {:<width$}  {}",
+            input.name(),
+            input.configuration
+        ));
+        for output in input.outputs.iter() {
+            emit(&format!(
+                "  \x1b[1mOUT\x1b[0m {:<width$}  {}",
+                output.name(),
+                output.configuration
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decorate_messages() {
+        assert_eq!(
+            decorate("Stopping read <> IN(pipe: a)"),
+            "\x1b[2mStopping read        <> IN(pipe: a)\x1b[0m"
+        );
+        assert_eq!(decorate("Warning: late"), "\x1b[33mWarning: late\x1b[0m");
+        assert_eq!(decorate("plain"), "plain");
+    }
+}
@@ -0,0 +1,95 @@
+//! Encryption of the records of an output with ChaCha20-Poly1305, for
+//! sensitive data written to FIFOs other users can open.
+//!
+//! Every record is sealed under a fresh random nonce, as the nonce followed
+//! by the ciphertext and its tag.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::ChaCha20Poly1305;
+
+/// Length of a key
+const KEY_LEN: usize = 32;
+/// Length of the nonce preceding every sealed record
+#[cfg(test)]
+const NONCE_LEN: usize = 12;
+
+/// Symmetric key, kept out of debug output
+#[derive(Clone, PartialEq)]
+pub(crate) struct Key([u8; KEY_LEN]);
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key(..)")
+    }
+}
+
+impl Key {
+    /// Key stored in the file at `path`, as 32 raw bytes or 64 hexadecimal
+    /// digits
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Key> {
+        let contents = fs::read(path)?;
+        let text = std::str::from_utf8(&contents).map(str::trim);
+        let key = match text {
+            Ok(hex) if hex.len() == 2 * KEY_LEN => (0..KEY_LEN)
+                .map(|i| u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>(),
+            _ => Some(contents),
+        };
+        match key.and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok()) {
+            Some(key) => Ok(Key(key)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "key must be 32 bytes or 64 hexadecimal digits",
+            )),
+        }
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&self.0.into())
+    }
+
+    /// `data` encrypted under a new nonce, preceded by that nonce
+    pub fn seal(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self.cipher().encrypt(&nonce, data).ok()?;
+        Some([nonce.as_slice(), &sealed].concat())
+    }
+
+    /// Contents of the sealed record `sealed`, `None` when it was not sealed
+    /// with this key or was altered
+    #[cfg(test)]
+    pub fn open(&self, sealed: &[u8]) -> Option<Vec<u8>> {
+        let nonce = chacha20poly1305::Nonce::from_slice(sealed.get(..NONCE_LEN)?);
+        self.cipher().decrypt(nonce, &sealed[NONCE_LEN..]).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn seal_and_open() {
+        let path = temp_dir().join("p_split_key");
+        fs::write(&path, format!("{}\n", "2a".repeat(KEY_LEN))).unwrap();
+        let key = Key::load(&path).expect("hex key");
+        assert_eq!(key, Key([0x2a; KEY_LEN]));
+
+        let sealed = key.seal(b"fuel=12\n").expect("seal");
+        assert_eq!(sealed.len(), NONCE_LEN + 8 + 16);
+        assert_ne!(key.seal(b"fuel=12\n").unwrap(), sealed);
+        assert_eq!(key.open(&sealed).as_deref(), Some(&b"fuel=12\n"[..]));
+
+        let mut altered = sealed.clone();
+        altered[NONCE_LEN] ^= 1;
+        assert_eq!(key.open(&altered), None);
+
+        fs::write(&path, b"short").unwrap();
+        assert!(Key::load(&path).is_err());
+    }
+}
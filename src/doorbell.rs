@@ -0,0 +1,97 @@
+//! Doorbell of an output, `notify=<name>`: a companion FIFO a byte is
+//! written to whenever records were written to the output, so a consumer
+//! can sleep on the doorbell alone and then read the data pipe in batches.
+//! Rings are not queued beyond the capacity of the FIFO, a consumer drains
+//! the doorbell and then reads every record available.
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+use crate::{FifoOptions, Writer};
+
+/// Write end of the doorbell FIFO of an output
+pub(crate) struct Doorbell {
+    /// Path of the doorbell FIFO
+    path: PathBuf,
+    /// Mode and label of the doorbell FIFO
+    fifo: FifoOptions,
+    /// Write end of the doorbell FIFO, once a consumer listens
+    file: Option<File>,
+}
+
+impl Doorbell {
+    /// Doorbell ringing the FIFO at `path`
+    pub fn new(path: PathBuf, fifo: FifoOptions) -> Doorbell {
+        Doorbell {
+            path,
+            fifo,
+            file: None,
+        }
+    }
+
+    /// Tell the consumer records are available, nothing to do while no
+    /// consumer listens or rings it has yet to drain fill the FIFO
+    pub fn ring(&mut self) -> io::Result<()> {
+        if self.file.is_none() {
+            Writer::create_fifo(&self.path, &self.fifo)?;
+            let opened = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path);
+            match opened {
+                Ok(file) => self.file = Some(file),
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+        match self.file.as_mut().unwrap().write(&[1]) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                // The consumer went away, reopen for the next one
+                self.file = None;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::io::Read;
+
+    #[test]
+    fn ring_listener() {
+        let path = temp_dir().join(format!("p_split_doorbell_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut doorbell = Doorbell::new(path.clone(), FifoOptions::default());
+        // Nobody listens yet
+        doorbell.ring().unwrap();
+
+        let mut listener = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+        doorbell.ring().unwrap();
+        doorbell.ring().unwrap();
+        let mut rings = [0u8; 8];
+        assert_eq!(listener.read(&mut rings).unwrap(), 2);
+
+        // A new listener after the first went away
+        drop(listener);
+        doorbell.ring().unwrap();
+        let mut listener = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+        doorbell.ring().unwrap();
+        assert_eq!(listener.read(&mut rings).unwrap(), 1);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,232 @@
+//! Events of a running splitter, for an embedder supervising it without
+//! scraping the log. [`SplitterHandle::subscribe`] returns a channel of
+//! them, [`SplitterHandle::on_event`] registers a callback.
+//! [`SplitterHandle::on_drop`] registers a callback given every dropped
+//! record with its payload, to salvage it elsewhere.
+//!
+//! Events are sent from the worker threads as they happen: a callback must
+//! return quickly and must not subscribe itself, and a channel holds up to
+//! [`CAPACITY`] events not yet received, later ones being lost rather than
+//! holding up the workers.
+//!
+//! [`SplitterHandle::subscribe`]: crate::SplitterHandle::subscribe
+//! [`SplitterHandle::on_event`]: crate::SplitterHandle::on_event
+//! [`SplitterHandle::on_drop`]: crate::SplitterHandle::on_drop
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+
+/// Events a channel holds until they are received
+pub const CAPACITY: usize = 1024;
+
+/// Why a record was not delivered to an output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropReason {
+    /// The queue of the output was full
+    QueueFull,
+    /// The record was the oldest of a full overflow buffer
+    Overflow,
+    /// The record waited in the queue for longer than `ttl`
+    Expired,
+    /// The output was evicted when the record was read
+    Evicted,
+}
+
+/// Why the worker of a pipe was started again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartReason {
+    /// The worker panicked and the restart policy allowed it again
+    Panic,
+    /// The worker made no progress for the watchdog timeout
+    Wedged,
+}
+
+/// Event of a running splitter, pipes being named by their label or path
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// The record `seq` was dropped for `output`
+    RecordDropped {
+        output: String,
+        seq: u64,
+        reason: DropReason,
+    },
+    /// A consumer opened `output`
+    ConsumerAttached { output: String },
+    /// A new worker took `pipe` over
+    PipeRestarted { pipe: String, reason: RestartReason },
+    /// The configuration `config` was loaded again and applied
+    ReloadApplied { config: PathBuf },
+}
+
+/// Record dropped for an output
+#[derive(Debug)]
+pub struct DroppedRecord<'a> {
+    /// Label of the output, its path when unlabelled
+    pub output: &'a str,
+    /// Path of the output FIFO, or template of a path changing over time
+    pub pipe: &'a str,
+    /// Sequence number within the input
+    pub seq: u64,
+    /// Bytes of the record, as delivered to the output
+    pub data: &'a [u8],
+    /// Why the record was dropped
+    pub reason: DropReason,
+}
+
+/// Callback given the records dropped
+type Salvage = Box<dyn Fn(&DroppedRecord) + Send + Sync>;
+
+enum Observer {
+    Channel(SyncSender<Event>),
+    Callback(Box<dyn Fn(&Event) + Send + Sync>),
+}
+
+/// Observers of the events of a splitter, shared by its pipes
+#[derive(Default)]
+pub(crate) struct Observers {
+    /// Channels and callbacks the events are sent to
+    list: Mutex<Vec<Observer>>,
+    /// Whether `list` has any, so events are only built when observed
+    observed: AtomicBool,
+    /// Callbacks given the records dropped
+    salvage: Mutex<Vec<Salvage>>,
+    /// Whether `salvage` has any
+    salvaged: AtomicBool,
+}
+
+impl Observers {
+    fn add(&self, observer: Observer) {
+        self.list.lock().unwrap().push(observer);
+        self.observed.store(true, Ordering::Release);
+    }
+
+    /// Channel receiving the events from now on
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::sync_channel(CAPACITY);
+        self.add(Observer::Channel(sender));
+        receiver
+    }
+
+    /// Call `callback` on every event from now on
+    pub fn on_event<F: Fn(&Event) + Send + Sync + 'static>(&self, callback: F) {
+        self.add(Observer::Callback(Box::new(callback)));
+    }
+
+    /// Call `callback` on every record dropped from now on
+    pub fn on_drop<F: Fn(&DroppedRecord) + Send + Sync + 'static>(&self, callback: F) {
+        self.salvage.lock().unwrap().push(Box::new(callback));
+        self.salvaged.store(true, Ordering::Release);
+    }
+
+    /// Hand the dropped `record` to the callbacks and send its event
+    pub fn dropped(&self, record: &DroppedRecord) {
+        if self.salvaged.load(Ordering::Acquire) {
+            for callback in self.salvage.lock().unwrap().iter() {
+                callback(record);
+            }
+        }
+        self.emit(|| Event::RecordDropped {
+            output: record.output.to_owned(),
+            seq: record.seq,
+            reason: record.reason,
+        });
+    }
+
+    /// Send the event built by `event` to the observers, if any
+    pub fn emit<F: FnOnce() -> Event>(&self, event: F) {
+        if !self.observed.load(Ordering::Acquire) {
+            return;
+        }
+        let event = event();
+        let mut list = self.list.lock().unwrap();
+        // Channels whose receiver is gone are forgotten
+        list.retain(|observer| match observer {
+            Observer::Channel(sender) => !matches!(
+                sender.try_send(event.clone()),
+                Err(TrySendError::Disconnected(_))
+            ),
+            Observer::Callback(callback) => {
+                callback(&event);
+                true
+            }
+        });
+        self.observed.store(!list.is_empty(), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn emit_to_observers() {
+        let observers = Observers::default();
+        observers.emit(|| unreachable!("no observer"));
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&received);
+        observers.on_event(move |event| seen.lock().unwrap().push(event.clone()));
+        let channel = observers.subscribe();
+        let attached = Event::ConsumerAttached {
+            output: "fuel".into(),
+        };
+        observers.emit(|| attached.clone());
+        assert_eq!(channel.try_recv(), Ok(attached.clone()));
+        assert_eq!(*received.lock().unwrap(), vec![attached]);
+
+        // A full channel loses events, a dropped one is forgotten
+        for seq in 0..CAPACITY as u64 + 1 {
+            observers.emit(|| Event::RecordDropped {
+                output: "fuel".into(),
+                seq,
+                reason: DropReason::QueueFull,
+            });
+        }
+        assert_eq!(channel.try_iter().count(), CAPACITY);
+        drop(channel);
+        observers.emit(|| Event::ReloadApplied {
+            config: "/etc/psplit.ini".into(),
+        });
+        assert_eq!(observers.list.lock().unwrap().len(), 1);
+        assert_eq!(received.lock().unwrap().len(), CAPACITY + 3);
+    }
+
+    #[test]
+    fn salvage_dropped_records() {
+        let observers = Observers::default();
+        let record = DroppedRecord {
+            output: "fuel",
+            pipe: "/tmp/cvAnalogsMapperExtFuelApp",
+            seq: 7,
+            data: b"fuel=12\n",
+            reason: DropReason::Expired,
+        };
+        observers.dropped(&record);
+
+        let salvaged = Arc::new(Mutex::new(Vec::new()));
+        let store = Arc::clone(&salvaged);
+        observers.on_drop(move |dropped| {
+            store
+                .lock()
+                .unwrap()
+                .push((dropped.pipe.to_owned(), dropped.data.to_vec()))
+        });
+        let channel = observers.subscribe();
+        observers.dropped(&record);
+        assert_eq!(
+            *salvaged.lock().unwrap(),
+            [(record.pipe.to_owned(), b"fuel=12\n".to_vec())]
+        );
+        assert_eq!(
+            channel.try_recv(),
+            Ok(Event::RecordDropped {
+                output: "fuel".into(),
+                seq: 7,
+                reason: DropReason::Expired
+            })
+        );
+    }
+}
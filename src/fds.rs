@@ -0,0 +1,100 @@
+//! Budget of file descriptors, as large topologies hold hundreds of pipes.
+//! The descriptors in use are counted against `RLIMIT_NOFILE`, read again at
+//! every check so a limit raised at runtime with `prlimit` is taken into
+//! account, with a warning once `[DEFAULT] fd_warning` percent of it is in
+//! use. Pipes failing to open for lack of descriptors wait for some to be
+//! freed rather than failing.
+use std::fs;
+use std::io;
+
+/// Descriptors open in the process
+pub(crate) fn open_count() -> io::Result<usize> {
+    // The directory being listed holds one of them
+    Ok(fs::read_dir("/proc/self/fd")?.count().saturating_sub(1))
+}
+
+/// Soft limit of the descriptors of the process
+pub(crate) fn limit() -> io::Result<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    match unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } {
+        0 => Ok(limit.rlim_cur),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Whether `error` tells the process or the system is out of descriptors
+pub(crate) fn is_exhausted(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+}
+
+/// Descriptors in use against their limit
+pub(crate) struct FdBudget {
+    /// Percent of the limit in use warned about
+    percent: u64,
+    /// Whether the usage is above `percent`, until it falls below half of it
+    above: bool,
+}
+
+impl FdBudget {
+    /// Budget warning once `percent` of the limit is in use
+    pub fn new(percent: u64) -> FdBudget {
+        FdBudget {
+            percent,
+            above: false,
+        }
+    }
+
+    /// Report the usage crossing the warning level, and again once it
+    /// fell below half of it
+    pub fn check(&mut self) -> io::Result<()> {
+        let limit = limit()?;
+        let used = match open_count() {
+            Ok(used) => used as u64,
+            // Counting takes a descriptor too
+            Err(e) if is_exhausted(&e) => limit,
+            Err(e) => return Err(e),
+        };
+        let level = used * 100 / limit.max(1);
+        if !self.above && level >= self.percent {
+            self.above = true;
+            log!(
+                "Warning: file descriptors nearing their limit ({}/{}) <> RLIMIT_NOFILE",
+                used,
+                limit
+            );
+        } else if self.above && level * 2 < self.percent {
+            self.above = false;
+            log!(
+                "File descriptors back below their warning level ({}/{})",
+                used,
+                limit
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_descriptors() {
+        assert!(limit().unwrap() > 0);
+        let file = fs::File::open("/proc/self/status").unwrap();
+        assert!(open_count().unwrap() >= 4);
+        drop(file);
+
+        assert!(is_exhausted(&io::Error::from_raw_os_error(libc::EMFILE)));
+        assert!(!is_exhausted(&io::Error::from_raw_os_error(libc::ENXIO)));
+        let mut budget = FdBudget::new(100);
+        budget.check().unwrap();
+        assert!(!budget.above);
+        let mut budget = FdBudget::new(0);
+        budget.check().unwrap();
+        assert!(budget.above);
+    }
+}
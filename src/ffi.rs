@@ -0,0 +1,152 @@
+//! C interface of the `cdylib` build, for a supervisor embedding the
+//! splitter in its own process rather than managing a child process. See
+//! `include/psplit.h`:
+//!
+//! ```c
+//! psplit_t *splitter = psplit_start("/etc/psplit.ini");
+//! char stats[4096];
+//! psplit_stats(splitter, stats, sizeof stats);
+//! int status = psplit_stop(splitter);
+//! ```
+//!
+//! Every splitter runs on a thread of its own, stopped by a `SIGTERM`
+//! directed at that thread, which blocks the signals it handles so they
+//! never reach the default handlers through it.
+use std::ffi::{c_char, c_int, CStr, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+use crate::{status, Splitter, SplitterHandle};
+
+/// Splitter started by [`psplit_start`], opaque to C
+pub struct PsplitHandle(SplitterHandle);
+
+/// Load the configuration file at `config_path` and split its pipes on a
+/// new thread, returning `NULL` when the configuration is invalid or the
+/// thread cannot be started
+///
+/// # Safety
+///
+/// `config_path` must be `NULL` or a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn psplit_start(config_path: *const c_char) -> *mut PsplitHandle {
+    if config_path.is_null() {
+        return ptr::null_mut();
+    }
+    // Paths are bytes, whatever their encoding
+    let config_path = Path::new(OsStr::from_bytes(CStr::from_ptr(config_path).to_bytes()));
+    let splitter = match Splitter::load(config_path, None) {
+        Ok(splitter) => splitter,
+        Err(e) => {
+            log!("Configuration -> {} Error {}", config_path.display(), e);
+            return ptr::null_mut();
+        }
+    };
+    match splitter.spawn() {
+        Ok(handle) if handle.is_running() => Box::into_raw(Box::new(PsplitHandle(handle))),
+        Ok(handle) => {
+            handle.wait();
+            ptr::null_mut()
+        }
+        Err(e) => {
+            log!("Splitter -> {} Error {:?}", config_path.display(), e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Stop the splitter `splitter` and wait for its workers to drain, then
+/// release it. Returns the exit status of the `psplit` binary: 0 when every
+/// pipe ran without errors, `-1` when `splitter` is `NULL`.
+///
+/// # Safety
+///
+/// `splitter` must be `NULL` or returned by [`psplit_start`], and not used
+/// after this call
+#[no_mangle]
+pub unsafe extern "C" fn psplit_stop(splitter: *mut PsplitHandle) -> c_int {
+    if splitter.is_null() {
+        return -1;
+    }
+    Box::from_raw(splitter).0.stop().code() as c_int
+}
+
+/// Write the status report of `splitter`, one line per pipe as in the
+/// status file, to `buf` of `len` bytes, NUL terminated and truncated when
+/// too long. Returns the length of the whole report, as `snprintf` does, or
+/// `-1` when `splitter` is `NULL`.
+///
+/// # Safety
+///
+/// `splitter` must be `NULL` or returned by [`psplit_start`] and not yet
+/// stopped, `buf` must be valid for writes of `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn psplit_stats(
+    splitter: *const PsplitHandle,
+    buf: *mut c_char,
+    len: usize,
+) -> isize {
+    let Some(splitter) = splitter.as_ref() else {
+        return -1;
+    };
+    let topology = &splitter.0.topology;
+    let report = status::report(&topology.settings, &topology.inputs);
+    if !buf.is_null() && len > 0 {
+        let copied = report.len().min(len - 1);
+        ptr::copy_nonoverlapping(report.as_ptr() as *const c_char, buf, copied);
+        *buf.add(copied) = 0;
+    }
+    report.len() as isize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::ffi::CString;
+    use std::fs;
+
+    #[test]
+    fn start_and_stop() {
+        let root = temp_dir().join(format!("p_split_ffi_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let config = temp_dir().join("p_split_ffi_config");
+        let content = format!(
+            "
+[DEFAULT]
+root={}
+drain_timeout=0.1
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt
+",
+            root.display()
+        );
+        fs::write(&config, content).unwrap();
+        crate::Writer::create(root.join("cvAnalogsMapperExt"), None).unwrap();
+
+        unsafe {
+            assert!(psplit_start(ptr::null()).is_null());
+            let missing = CString::new("/nonexistent/psplit.ini").unwrap();
+            assert!(psplit_start(missing.as_ptr()).is_null());
+
+            let path = CString::new(config.to_str().unwrap()).unwrap();
+            let splitter = psplit_start(path.as_ptr());
+            assert!(!splitter.is_null());
+            let mut buf = [0 as c_char; 16];
+            let len = psplit_stats(splitter, buf.as_mut_ptr(), buf.len());
+            let truncated = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            assert!(truncated.starts_with("PROCESS(pid: "));
+            assert_eq!(truncated.len(), buf.len() - 1);
+            let mut buf = vec![0 as c_char; len as usize + 1];
+            psplit_stats(splitter, buf.as_mut_ptr(), buf.len());
+            let report = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            assert!(report.contains("OUT(pipe: "));
+            assert_eq!(psplit_stop(splitter), 0);
+            assert_eq!(psplit_stop(ptr::null_mut()), -1);
+        }
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,159 @@
+//! Rendering of the parsed topology as a graph, inputs feeding their outputs
+//! through their transform chains, so complex routing setups can be reviewed
+//! visually.
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::{Config, SplitIn, SplitOut};
+
+/// Language the topology graph is written in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+/// Lines of the label of a pipe: its name, mode and custom endpoint
+fn label_lines(name: &str, configuration: &Config, endpoint: Option<String>) -> Vec<String> {
+    let mut lines = vec![name.to_owned()];
+    if let Some(mode) = configuration.mode {
+        lines.push(mode.code().to_owned());
+    }
+    lines.extend(endpoint);
+    lines
+}
+
+fn input_label(input: &SplitIn) -> Vec<String> {
+    let source = input.source.as_ref().map(|s| format!("source {s}"));
+    label_lines(input.name(), &input.configuration, source)
+}
+
+fn output_label(output: &SplitOut) -> Vec<String> {
+    let sink = output.sink.as_ref().map(|s| format!("sink {s}"));
+    let mut lines = label_lines(output.name(), &output.configuration, sink);
+    if let Some(group) = &output.group {
+        lines.push(format!("group {group}"));
+    }
+    lines
+}
+
+/// `text` as a quoted DOT string, lines separated by `\n`
+fn dot_string(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+/// `text` as a quoted Mermaid string
+fn mermaid_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
+
+/// Graph of `inputs` and their outputs in `format`
+pub(crate) fn render(inputs: &[Arc<SplitIn>], format: GraphFormat) -> String {
+    let mut graph = String::new();
+    match format {
+        GraphFormat::Dot => graph.push_str("digraph psplit {\n    rankdir=LR;\n"),
+        GraphFormat::Mermaid => graph.push_str("flowchart LR\n"),
+    }
+    for (i, input) in inputs.iter().enumerate() {
+        let id = format!("in{i}");
+        node(
+            &mut graph,
+            format,
+            &id,
+            &input_label(input),
+            input.configuration.enabled,
+        );
+        for (o, output) in input.outputs.iter().enumerate() {
+            let out_id = format!("{id}_out{o}");
+            let enabled = input.configuration.enabled && output.configuration.enabled;
+            node(&mut graph, format, &out_id, &output_label(output), enabled);
+            let transforms = output.transforms.names().join(", ");
+            edge(&mut graph, format, &id, &out_id, &transforms, enabled);
+        }
+    }
+    if format == GraphFormat::Dot {
+        graph.push_str("}\n");
+    }
+    graph
+}
+
+/// Append the node `id`, drawn dashed when disabled
+fn node(graph: &mut String, format: GraphFormat, id: &str, label: &[String], enabled: bool) {
+    match format {
+        GraphFormat::Dot => {
+            let style = if enabled { "" } else { ", style=dashed" };
+            let label = dot_string(&label.join("\n"));
+            let _ = writeln!(graph, "    {id} [shape=box, label={label}{style}];");
+        }
+        GraphFormat::Mermaid => {
+            let label = mermaid_string(&label.join("<br/>"));
+            let _ = writeln!(graph, "    {id}[{label}]");
+            if !enabled {
+                let _ = writeln!(graph, "    style {id} stroke-dasharray: 5 5");
+            }
+        }
+    }
+}
+
+/// Append the edge `from` -> `to` labelled with its transforms, dashed when
+/// disabled
+fn edge(graph: &mut String, format: GraphFormat, from: &str, to: &str, label: &str, enabled: bool) {
+    match format {
+        GraphFormat::Dot => {
+            let style = if enabled { "" } else { ", style=dashed" };
+            let label = dot_string(label);
+            let _ = writeln!(graph, "    {from} -> {to} [label={label}{style}];");
+        }
+        GraphFormat::Mermaid => {
+            let arrow = if enabled { "-->" } else { "-.->" };
+            let _ = match label.is_empty() {
+                true => writeln!(graph, "    {from} {arrow} {to}"),
+                false => writeln!(graph, "    {from} {arrow}|{}| {to}", mermaid_string(label)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn render_topology() {
+        let file_name = temp_dir().join("p_split_graph_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,label=\"fuel \"\"main\"\"\",newline=yes
+cvAnalogsMapperExtLogApp=0,wb
+";
+        fs::write(&file_name, file_content).expect("write");
+        let topology = Parser::load_from_file(&file_name).expect("Should load configuration ");
+
+        let dot = render(&topology.inputs, GraphFormat::Dot);
+        assert!(dot.starts_with("digraph psplit {\n"));
+        assert!(dot.contains("    in0 [shape=box, label=\"/tmp/cvAnalogsMapperExt\\nrt\"];\n"));
+        assert!(dot.contains("label=\"fuel \\\"main\\\"\\nwt\"];\n"));
+        assert!(dot.contains("    in0 -> in0_out0 [label=\"newline\"];\n"));
+        assert!(dot.contains("    in0 -> in0_out1 [label=\"\", style=dashed];\n"));
+
+        let mermaid = render(&topology.inputs, GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("    in0_out0[\"fuel #quot;main#quot;<br/>wt\"]\n"));
+        assert!(mermaid.contains("    in0 -->|\"newline\"| in0_out0\n"));
+        assert!(mermaid.contains("    in0 -.-> in0_out1\n"));
+        assert!(mermaid.contains("    style in0_out1 stroke-dasharray: 5 5\n"));
+    }
+}
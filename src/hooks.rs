@@ -0,0 +1,163 @@
+//! Commands run on events of a pipe, such as a consumer attaching to an
+//! output, `on_attach=<command>`, or the worker of a pipe starting, stopping
+//! and failing, `exec_on_start=`, `exec_on_stop=` and `exec_on_error=`. A
+//! hook runs through `sh -c` with the event and the pipe in its environment,
+//! `PSPLIT_EVENT`, `PSPLIT_PIPE` and `PSPLIT_NAME`. It does not hold the
+//! worker up: it runs on its own and only a failure is logged. The error
+//! hook runs at most once every [`ERROR_INTERVAL`], with the number of errors
+//! since its last run in `PSPLIT_ERRORS`.
+use std::io;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::options::PipeOptions;
+use crate::secrets;
+
+/// Minimum interval between two runs of the error hook of a pipe
+pub(crate) const ERROR_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Start `command` for `event` of the pipe at `pipe` named `name`, with the
+/// additional variables `env`
+fn spawn(
+    command: &str,
+    event: &str,
+    pipe: &str,
+    name: &str,
+    env: &[(&str, &str)],
+) -> io::Result<Child> {
+    Command::new("/bin/sh")
+        .arg("-c")
+        .arg(command)
+        .env("PSPLIT_EVENT", event)
+        .env("PSPLIT_PIPE", pipe)
+        .env("PSPLIT_NAME", name)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .spawn()
+}
+
+/// Run `command` for `event` of the pipe at `pipe` named `name`, with the
+/// additional variables `env`, logging its failure
+pub(crate) fn run(command: &str, event: &str, pipe: &str, name: &str, env: &[(&str, &str)]) {
+    let mut child = match spawn(command, event, pipe, name, env) {
+        Ok(child) => child,
+        Err(e) => {
+            log!("Hook -> {} Error {:?}", command, e);
+            return;
+        }
+    };
+    let event = event.to_owned();
+    let pipe = pipe.to_owned();
+    // The hook is waited for so it does not linger as a zombie
+    thread::spawn(move || match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => log!("Warning: {} hook failed, {} <> {}", event, status, pipe),
+        Err(e) => log!("Hook -> {} Error {:?}", pipe, e),
+    });
+}
+
+/// Errors of a pipe since its error hook last ran
+#[derive(Debug, Default)]
+struct Errors {
+    /// When the error hook last ran
+    last_run: Option<Instant>,
+    /// Errors not reported by the hook yet
+    count: u64,
+}
+
+/// Hooks of the worker of a pipe
+#[derive(Debug, Default)]
+pub(crate) struct Lifecycle {
+    /// Command run when the worker starts, `exec_on_start=`
+    pub on_start: Option<String>,
+    /// Command run when the worker stops, `exec_on_stop=`
+    pub on_stop: Option<String>,
+    /// Command run on errors of the pipe, `exec_on_error=`
+    pub on_error: Option<String>,
+    /// Errors since the error hook last ran
+    errors: Mutex<Errors>,
+}
+
+impl Lifecycle {
+    /// Hooks set in the options of a pipe
+    pub fn parse(options: &PipeOptions) -> Lifecycle {
+        let command = |key: &str| options.get(key).map(str::to_owned);
+        Lifecycle {
+            on_start: command("exec_on_start"),
+            on_stop: command("exec_on_stop"),
+            on_error: command("exec_on_error"),
+            errors: Mutex::default(),
+        }
+    }
+
+    /// The worker of the pipe at `pipe` named `name` started
+    pub fn started(&self, pipe: &str, name: &str) {
+        if let Some(command) = &self.on_start {
+            run(command, "start", pipe, name, &[]);
+        }
+    }
+
+    /// The worker of the pipe stopped, after a failure unless `ok`
+    pub fn stopped(&self, pipe: &str, name: &str, ok: bool) {
+        if let Some(command) = &self.on_stop {
+            let status = if ok { "ok" } else { "failed" };
+            run(command, "stop", pipe, name, &[("PSPLIT_STATUS", status)]);
+        }
+    }
+
+    /// The pipe hit `error`, run the error hook unless it ran within
+    /// [`ERROR_INTERVAL`], so a pipe failing on every record does not start
+    /// a process for each
+    pub fn failed(&self, pipe: &str, name: &str, error: &str) {
+        let Some(command) = &self.on_error else {
+            return;
+        };
+        let count = {
+            let mut errors = self.errors.lock().unwrap();
+            errors.count += 1;
+            if errors
+                .last_run
+                .is_some_and(|t| t.elapsed() < ERROR_INTERVAL)
+            {
+                return;
+            }
+            errors.last_run = Some(Instant::now());
+            std::mem::take(&mut errors.count)
+        };
+        let error = secrets::redact(error);
+        let env = [
+            ("PSPLIT_ERROR", error.as_str()),
+            ("PSPLIT_ERRORS", &count.to_string()),
+        ];
+        run(command, "error", pipe, name, &env);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn run_with_context() {
+        let path = temp_dir().join(format!("p_split_hook_{}", std::process::id()));
+        let command = format!(
+            "echo \"$PSPLIT_EVENT $PSPLIT_NAME $PSPLIT_REASON\" > {}",
+            path.display()
+        );
+        let mut child = spawn(
+            &command,
+            "detach",
+            "/tmp/fuel",
+            "fuel",
+            &[("PSPLIT_REASON", "consumer")],
+        )
+        .expect("spawn");
+        assert!(child.wait().unwrap().success());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "detach fuel consumer\n");
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,154 @@
+//! Read-only view of a splitter, for a daemon embedding or supervising it
+//! without parsing the configuration itself. [`SplitterHandle::topology`]
+//! returns the inputs and outputs as configured, with the live state and
+//! counters of their pipes at the time of the call, and the resources held
+//! by the process.
+//!
+//! [`SplitterHandle::topology`]: crate::SplitterHandle::topology
+use std::sync::Arc;
+
+use crate::process::{self, ProcessView};
+use crate::{status, Config, OperationMode, Settings, SplitIn, SplitOut};
+
+/// How a pipe is read or written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PipeMode {
+    /// Lines of text read, `rt`
+    ReadText,
+    /// Bytes read, `rb`
+    ReadBytes,
+    /// Lines of text written, `wt`
+    WriteText,
+    /// Bytes written, `wb`
+    WriteBytes,
+}
+
+/// What a pipe is doing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PipeState {
+    /// Not enabled in the configuration, or an input without enabled outputs
+    Disabled,
+    /// Taking part in splitting
+    Running,
+    /// An output whose queue is full or whose FIFO is not being read
+    Stalled,
+    /// An output disabled by its eviction policy until its consumer is back
+    Evicted,
+}
+
+/// Inputs of a splitter
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopologyView {
+    /// Name telling several splitters on a host apart, `[DEFAULT] instance`
+    pub instance: Option<String>,
+    /// Directory holding the pipes
+    pub root: String,
+    /// Resources of the process, `None` when `/proc` cannot be read
+    pub process: Option<ProcessView>,
+    /// Inputs, in the order of the configuration
+    pub inputs: Vec<InputView>,
+}
+
+/// Input pipe and its outputs
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputView {
+    /// Label of the input, its path when unlabelled
+    pub name: String,
+    /// Path of the FIFO
+    pub pipe: String,
+    /// Mode, `None` when unspecified
+    pub mode: Option<PipeMode>,
+    /// What the pipe is doing
+    pub state: PipeState,
+    /// Records read
+    pub records: u64,
+    /// Bytes read
+    pub bytes: u64,
+    /// Errors opening or reading the pipe
+    pub errors: u64,
+    /// Outputs, in the order of the configuration
+    pub outputs: Vec<OutputView>,
+}
+
+/// Output pipe
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputView {
+    /// Label of the output, its path when unlabelled
+    pub name: String,
+    /// Path of the FIFO, or template of a path changing over time
+    pub pipe: String,
+    /// Mode, `None` when unspecified
+    pub mode: Option<PipeMode>,
+    /// What the pipe is doing
+    pub state: PipeState,
+    /// Records written
+    pub records: u64,
+    /// Bytes written
+    pub bytes: u64,
+    /// Records dropped
+    pub drops: u64,
+    /// Records queued or being written
+    pub queued: u64,
+    /// Records the queue holds, see [`crate::SplitterHandle::set_queue`]
+    pub capacity: usize,
+    /// Errors opening or writing the pipe
+    pub errors: u64,
+}
+
+/// Mode of a pipe configured as `configuration`
+fn mode(configuration: &Config) -> Option<PipeMode> {
+    configuration.mode.map(|mode| match mode {
+        OperationMode::StringRead => PipeMode::ReadText,
+        OperationMode::BytesRead => PipeMode::ReadBytes,
+        OperationMode::StringWrite => PipeMode::WriteText,
+        OperationMode::BytesWrite => PipeMode::WriteBytes,
+    })
+}
+
+fn output(output: &SplitOut) -> OutputView {
+    let status = &output.status;
+    let state = match () {
+        _ if !output.configuration.enabled => PipeState::Disabled,
+        _ if status.is_evicted() => PipeState::Evicted,
+        _ if status.is_stalled() => PipeState::Stalled,
+        _ => PipeState::Running,
+    };
+    OutputView {
+        name: output.name().to_owned(),
+        pipe: output.pipe.clone(),
+        mode: mode(&output.configuration),
+        state,
+        records: status.records(),
+        bytes: status.bytes(),
+        drops: status.drops(),
+        queued: status.queue_len(),
+        capacity: output.channel.capacity(),
+        errors: status.errors(),
+    }
+}
+
+fn input(input: &SplitIn) -> InputView {
+    InputView {
+        name: input.name().to_owned(),
+        pipe: input.pipe.clone(),
+        mode: mode(&input.configuration),
+        state: match input.runs() {
+            true => PipeState::Running,
+            false => PipeState::Disabled,
+        },
+        records: input.status.records(),
+        bytes: input.status.bytes(),
+        errors: input.status.errors(),
+        outputs: input.outputs.iter().map(|o| output(o)).collect(),
+    }
+}
+
+/// View of the inputs `entries` of a splitter set up with `settings`
+pub(crate) fn view(settings: &Settings, entries: &[Arc<SplitIn>]) -> TopologyView {
+    TopologyView {
+        instance: settings.instance.clone(),
+        root: settings.root.clone(),
+        process: process::usage(status::queued_bytes(entries)).ok(),
+        inputs: entries.iter().map(|i| input(i)).collect(),
+    }
+}
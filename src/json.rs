@@ -0,0 +1,257 @@
+//! Minimal JSON support for the record transforms: it validates documents
+//! and locates the members of an object without building a value tree, so
+//! members are copied through byte for byte, and writes strings.
+
+/// Nesting depth beyond which a document is refused
+const MAX_DEPTH: usize = 128;
+
+/// Cursor over a JSON document
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(data: &'a [u8]) -> Scanner<'a> {
+        Scanner { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `byte` if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        let next = self.peek() == Some(byte);
+        if next {
+            self.pos += 1;
+        }
+        next
+    }
+
+    /// Whether only whitespace is left
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos == self.data.len()
+    }
+
+    /// Skip a value, `None` if it is malformed
+    fn value(&mut self, depth: usize) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.object(depth, |_, _| {}),
+            b'[' => self.array(depth),
+            b'"' => self.string().map(|_| ()),
+            b't' => self.literal(b"true"),
+            b'f' => self.literal(b"false"),
+            b'n' => self.literal(b"null"),
+            _ => self.number(),
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Option<()> {
+        let end = self.pos + literal.len();
+        (self.data.get(self.pos..end)? == literal).then(|| self.pos = end)
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Option<()> {
+        self.eat(b'-');
+        match self.peek()? {
+            b'0' => self.pos += 1,
+            b'1'..=b'9' => {
+                self.digits();
+            }
+            _ => return None,
+        }
+        if self.eat(b'.') && self.digits() == 0 {
+            return None;
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            if !self.eat(b'+') {
+                self.eat(b'-');
+            }
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    /// Skip a string, returning its contents between the quotes as written
+    fn string(&mut self) -> Option<&'a [u8]> {
+        if !self.eat(b'"') {
+            return None;
+        }
+        let start = self.pos;
+        loop {
+            match self.peek()? {
+                b'"' => break,
+                b'\\' => {
+                    self.pos += 1;
+                    match self.peek()? {
+                        b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => self.pos += 1,
+                        b'u' => {
+                            let hex = self.data.get(self.pos + 1..self.pos + 5)?;
+                            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                                return None;
+                            }
+                            self.pos += 5;
+                        }
+                        _ => return None,
+                    }
+                }
+                0x00..=0x1f => return None,
+                _ => self.pos += 1,
+            }
+        }
+        let contents = &self.data[start..self.pos];
+        self.pos += 1;
+        std::str::from_utf8(contents).ok()?;
+        Some(contents)
+    }
+
+    fn array(&mut self, depth: usize) -> Option<()> {
+        self.eat(b'[');
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Some(());
+        }
+        loop {
+            self.value(depth + 1)?;
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Some(());
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    /// Skip an object, calling `member` with the key, as written, and the
+    /// value of every member
+    fn object<F>(&mut self, depth: usize, mut member: F) -> Option<()>
+    where
+        F: FnMut(&'a [u8], &'a [u8]),
+    {
+        self.eat(b'{');
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Some(());
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return None;
+            }
+            self.skip_whitespace();
+            let start = self.pos;
+            self.value(depth + 1)?;
+            member(key, &self.data[start..self.pos]);
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Some(());
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+}
+
+/// Whether `data` is a single JSON document
+pub(crate) fn is_valid(data: &[u8]) -> bool {
+    let mut scanner = Scanner::new(data);
+    scanner.value(0).is_some() && scanner.at_end()
+}
+
+/// Whether `data` is a JSON number
+pub(crate) fn is_number(data: &[u8]) -> bool {
+    let mut scanner = Scanner::new(data);
+    scanner.number().is_some() && scanner.pos == data.len()
+}
+
+/// Append `text` to `out` as a JSON string
+pub(crate) fn write_string(out: &mut Vec<u8>, text: &[u8]) {
+    out.push(b'"');
+    for byte in text {
+        match byte {
+            b'"' => out.extend_from_slice(b"\\\""),
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            0x00..=0x1f => out.extend_from_slice(format!("\\u{byte:04x}").as_bytes()),
+            _ => out.push(*byte),
+        }
+    }
+    out.push(b'"');
+}
+
+/// Members of the object `data`, as `(key, value)` with the key as written
+/// between its quotes and the value's JSON text. `None` when `data` is not
+/// a single JSON object.
+pub(crate) fn object_members(data: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let mut scanner = Scanner::new(data);
+    scanner.skip_whitespace();
+    if scanner.peek()? != b'{' {
+        return None;
+    }
+    let mut members = Vec::new();
+    scanner.object(0, |key, value| members.push((key, value)))?;
+    scanner.at_end().then_some(members)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scan_object_members() {
+        let members =
+            object_members(br#" {"ts": 17, "pos": {"lat": 1.5e3}, "tags": ["a\"b", null]}"#)
+                .expect("object");
+        assert_eq!(
+            members,
+            vec![
+                (&b"ts"[..], &b"17"[..]),
+                (b"pos", br#"{"lat": 1.5e3}"#),
+                (b"tags", br#"["a\"b", null]"#),
+            ]
+        );
+        assert!(object_members(b"[1, 2]").is_none());
+        assert!(object_members(br#"{"ts": 01}"#).is_none());
+        assert!(object_members(br#"{"ts": 1} x"#).is_none());
+        assert!(object_members(br#"{"ts": 1,}"#).is_none());
+
+        assert!(is_valid(br#"[true, false, null, -0.5, "\u00e9"]"#));
+        assert!(!is_valid(b"tru"));
+        assert!(!is_valid(b"{} {}"));
+        assert!(!is_valid(b""));
+
+        assert!(is_number(b"-12.5e3"));
+        assert!(!is_number(b"12:00"));
+        let mut out = Vec::new();
+        write_string(&mut out, b"a\"b\\\n\x01");
+        assert_eq!(out, br#""a\"b\\\n\u0001""#);
+    }
+}
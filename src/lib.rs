@@ -2,25 +2,204 @@ use ini::{Error as IniError, Ini};
 use libc::{c_int, mkfifo, mode_t, EACCES, EEXIST, ENOENT};
 use mio::unix::pipe;
 use mio::{Events, Interest, Poll, Token};
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::fmt;
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader};
+use std::io::{self, BufReader, Read};
 use std::os::fd::AsRawFd;
-use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::os::unix::io::FromRawFd;
 use std::os::unix::io::IntoRawFd;
-use std::path::Path;
-use std::sync::{mpsc, Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::SystemTime;
 use std::{thread, time};
 
+/// Print a message through the [`console`]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::console::print(&format!($($arg)*))
+    };
+}
+
+mod ack;
+mod base64;
+mod caps;
+mod console;
+mod crypt;
+mod doorbell;
+mod events;
+mod fds;
+mod ffi;
+mod graph;
+mod hooks;
+mod inspect;
+mod json;
+mod limits;
+mod lock;
+mod logfile;
+mod migrate;
+mod mq;
+mod naming;
+mod opener;
+mod options;
+mod overflow;
+mod plugin;
+mod pool;
+mod process;
+#[cfg(feature = "python")]
+mod python;
+mod queue;
+mod registry;
+mod restart;
+mod rotation;
+mod secrets;
+mod security;
+mod selftest;
+mod shared;
+mod shm;
+mod signals;
+mod sink;
+mod soak;
+mod source;
+mod state;
+mod status;
+mod tap;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(not(feature = "testing"))]
+#[allow(dead_code)]
+mod testing;
+mod throttle;
+mod timer;
+mod transcript;
+mod transform;
+mod varint;
+mod wal;
+mod watchdog;
+
+use ack::AckTracker;
+use caps::CapSet;
+use doorbell::Doorbell;
+use events::Observers;
+use fds::FdBudget;
+use hooks::Lifecycle;
+use limits::Limits;
+use naming::TimedPath;
+use opener::BlockingOpen;
+use options::PipeOptions;
+use overflow::Overflow;
+use pool::VectoredReader;
+use queue::{Popped, PushError, RecordQueue};
+use restart::RestartPolicy;
+use rotation::{ChunkSize, Rotation};
+use security::{NonFifoPolicy, RootPolicy};
+use shared::WriteTurn;
+use signals::Signals;
+use sink::SinkTarget;
+use source::SourceTarget;
+use state::ResumeState;
+use status::{InputStatus, OutputStatus};
+use throttle::Throttle;
+use timer::Timer;
+use transcript::Transcript;
+use transform::Chain;
+use wal::Wal;
+use watchdog::Liveness;
+
+pub use events::{DropReason, DroppedRecord, Event, RestartReason};
+pub use graph::GraphFormat;
+pub use inspect::{InputView, OutputView, PipeMode, PipeState, TopologyView};
+pub use logfile::LogRotation;
+pub use process::ProcessView;
+pub use sink::{register_sink, Sink, SinkFactory};
+pub use soak::SoakOptions;
+pub use source::{register_source, Source, SourceFactory};
+pub use transform::{register_transform, Transform, TransformFactory, TransformOptions};
+
 const PIPE_RECV: Token = Token(0);
 const PIPE_SEND: Token = Token(1);
 const TIME_OUT: time::Duration = time::Duration::from_millis(100);
 const SIG_RUN: u8 = 0;
 const SIG_EXIT: u8 = 1;
 const SIG_CLOSE: u8 = 2;
+const STATUS_INTERVAL: time::Duration = time::Duration::from_secs(1);
+const REPROBE_INTERVAL: time::Duration = time::Duration::from_secs(5);
+const ACK_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+const TURN_WAIT: time::Duration = time::Duration::from_millis(1);
+const WAL_CHECKPOINT_INTERVAL: time::Duration = time::Duration::from_secs(1);
+const DRAIN_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+const QUEUE_SIZE: usize = 1;
+const MAX_PACKET: usize = 1 << 16;
+const READ_BUFFER: usize = 8 << 10;
+const CHUNK_SIZE: usize = 1 << 16;
+const FD_WARNING: u64 = 80;
+
+/// Options understood on an input pipe
+const INPUT_OPTIONS: &[&str] = &[
+    "wal",
+    "read_buffer",
+    "read_chunk",
+    "label",
+    "framing",
+    "chunk",
+    "base64",
+    "source",
+    "rotate",
+    "rotate_bytes",
+    "exec_on_start",
+    "exec_on_stop",
+    "exec_on_error",
+    "transcript",
+    "transcript_size",
+    "transcript_window",
+];
+/// Options understood on an output pipe
+const OUTPUT_OPTIONS: &[&str] = &[
+    "evict_after",
+    "reprobe",
+    "ack",
+    "notify",
+    "ack_timeout",
+    "ttl",
+    "queue",
+    "overflow",
+    "buffer_until_reader",
+    "on_attach",
+    "on_detach",
+    "exec_on_start",
+    "exec_on_stop",
+    "exec_on_error",
+    "high_water",
+    "group",
+    "label",
+    "coalesce",
+    "oversize",
+    "open",
+    "open_policy",
+    "standby",
+    "transcript",
+    "transcript_size",
+    "transcript_window",
+    "newline",
+    "strip_ansi",
+    "columns",
+    "select",
+    "require_json",
+    "csv_json",
+    "base64",
+    "encrypt",
+    "prefix",
+    "transforms",
+    "filter",
+    "transform",
+    "encode",
+    "sink",
+];
 
 #[derive(Debug)]
 /// Parse Error
@@ -59,33 +238,78 @@ enum OperationMode {
 
 impl OperationMode {
     fn code(&self) -> &str {
-        let code = match self {
+        match self {
             OperationMode::BytesRead => "rb",
             OperationMode::StringRead => "rt",
             OperationMode::StringWrite => "wt",
             OperationMode::BytesWrite => "wb",
-        };
-        code
+        }
     }
 }
 
+/// How records are delimited on an input
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Framing {
+    /// Newline terminated lines
+    Line,
+    /// Packets of a pipe in packet mode, each read returns one record
+    Packet,
+    /// Messages preceded by their length as a varint, as protobuf writes them
+    Varint,
+    /// Fixed size chunks of a byte stream, the last one of a stream shorter
+    Chunk(usize),
+}
+
+/// What a writer does with a record larger than `PIPE_BUF`, which may
+/// interleave with the writes of other producers on the same FIFO
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Oversize {
+    /// Write it whole and warn about the first one
+    Warn,
+    /// Write it in atomic parts prefixed with `<part>/<parts> `
+    Split,
+    /// Drop it
+    Reject,
+}
+
+/// How a writer opens its output FIFO
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OpenMode {
+    /// Open it non-blocking, again every `retry` interval until a consumer
+    /// has the other end open
+    Poll,
+    /// Open it blocking on a thread of its own, which a consumer wakes
+    Wait,
+}
+
+/// When a writer holds its output FIFO open
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OpenPolicy {
+    /// While the input has data, the consumer sees end of file once the
+    /// input is idle
+    OnDemand,
+    /// From startup until exit, so the blocking reads of the consumer never
+    /// see end of file between bursts
+    Persistent,
+}
+
 #[derive(Clone, Copy)]
 struct Config {
-    ///
+    /// Whether the pipe takes part in splitting
     pub enabled: bool,
-    ///
+    /// Read/write mode, `None` when unspecified
     pub mode: Option<OperationMode>,
 }
 
 impl Config {
-    ///
+    /// Enabled configuration for an input pipe
     pub fn default_read() -> Config {
         Config {
             enabled: true,
             mode: Some(OperationMode::StringRead),
         }
     }
-    ///
+    /// Enabled configuration for an output pipe
     pub fn default_write() -> Config {
         Config {
             enabled: true,
@@ -95,22 +319,279 @@ impl Config {
 }
 
 struct SplitOut {
-    ///
+    /// Absolute path of the output FIFO
     pub pipe: String,
-    ///
+    /// Write configuration
     pub configuration: Config,
+    /// Disable the output once it has been stalled for this long
+    pub evict_after: Option<time::Duration>,
+    /// Interval between attempts to reach the consumer of an evicted output
+    pub reprobe: time::Duration,
+    /// FIFO the consumer acknowledges records on, enables acknowledged delivery
+    pub ack: Option<String>,
+    /// Retransmit records unacknowledged for this long
+    pub ack_timeout: time::Duration,
+    /// FIFO a byte is written to whenever records were written
+    pub notify: Option<String>,
+    /// FIFO written to while no consumer has the output open
+    pub standby: Option<String>,
+    /// Drop records that waited in the queue for longer than this
+    pub ttl: Option<time::Duration>,
+    /// Keep every record until the first consumer opens the pipe
+    pub buffer_until_reader: bool,
+    /// Command run when a consumer opened the pipe
+    pub on_attach: Option<String>,
+    /// Command run when the pipe was closed, by the consumer or on an idle
+    /// input, eviction or exit
+    pub on_detach: Option<String>,
+    /// Commands run when the writer starts, stops or fails
+    pub lifecycle: Lifecycle,
+    /// Path depending on the time, `pipe` showing its template
+    pub timed: Option<TimedPath>,
+    /// Records queued for the writer
+    pub channel: RecordQueue,
+    /// Queue occupancy, in percent, above which an alert is emitted
+    pub high_water: Option<usize>,
+    /// Delivery group, records are queued on all outputs of the group or none
+    pub group: Option<String>,
+    /// Name shown in logs and status instead of the path
+    pub label: Option<String>,
+    /// Merge small queued records into writes of up to `PIPE_BUF` bytes
+    pub coalesce: bool,
+    /// Handling of records larger than `PIPE_BUF`
+    pub oversize: Oversize,
+    /// How the FIFO is opened, `open=` option
+    pub open: OpenMode,
+    /// When the FIFO is held open, `open_policy=` option
+    pub open_policy: OpenPolicy,
+    /// Write every record as one packet, the input is in packet mode
+    pub packet: bool,
+    /// Rewrites applied to records before they are queued
+    pub transforms: Chain,
+    /// Custom sink written to instead of the FIFO
+    pub sink: Option<SinkTarget>,
+    /// Turn to write records longer than `PIPE_BUF`, when other inputs list
+    /// the same FIFO
+    pub turn: Option<Arc<WriteTurn>>,
+    /// Mode and label of created FIFOs
+    pub fifo: FifoOptions,
+    /// Intervals and delays of the writer
+    pub timing: Timing,
+    /// What to do when the writer panics
+    pub restart: RestartPolicy,
+    /// Progress of the writer thread, watched by the watchdog
+    pub worker: Liveness,
+    /// Observers of the events of the splitter, shared with every pipe
+    pub events: Arc<Observers>,
+    /// Recent records routed to the output
+    pub transcript: Option<Transcript>,
+    /// Runtime counters
+    pub status: OutputStatus,
 }
 
 struct SplitIn {
-    ///
+    /// Read configuration
     pub configuration: Config,
-    ///
+    /// Outputs fed from this input
     pub outputs: Vec<Arc<SplitOut>>,
-    ///
+    /// Absolute path of the input FIFO
     pub pipe: String,
+    /// Base path of the write-ahead log, records are logged when set
+    pub wal: Option<String>,
+    /// Capacity of the buffer records are read through
+    pub read_buffer: usize,
+    /// Bytes taken by a read bypassing the buffer: the largest packet, or
+    /// the pooled buffers filled by a vectored read
+    pub read_chunk: usize,
+    /// How records are delimited
+    pub framing: Framing,
+    /// Records are base64 lines, decoded before being dispatched
+    pub base64: bool,
+    /// Custom source read from instead of the FIFO
+    pub source: Option<SourceTarget>,
+    /// Name shown in logs and status instead of the path
+    pub label: Option<String>,
+    /// Commands run when the reader starts, stops or fails
+    pub lifecycle: Lifecycle,
+    /// Chunks of records each output gets in turn, instead of every record
+    pub rotate: Option<ChunkSize>,
+    /// Ceiling on the bytes read by all inputs, shared with them
+    pub throttle: Option<Arc<Throttle>>,
+    /// Intervals and delays of the reader
+    pub timing: Timing,
+    /// What to do when the reader panics
+    pub restart: RestartPolicy,
+    /// Progress of the reader thread, watched by the watchdog
+    pub worker: Liveness,
+    /// Observers of the events of the splitter, shared with every pipe
+    pub events: Arc<Observers>,
+    /// Recent records read
+    pub transcript: Option<Transcript>,
+    /// Runtime counters
+    pub status: InputStatus,
+}
+
+/// Process wide settings from the `DEFAULT` section
+struct Settings {
+    /// Directory holding the pipes
+    pub root: String,
+    /// Name telling several splitters on a host apart
+    pub instance: Option<String>,
+    /// File the status report is periodically written to
+    pub status_file: Option<String>,
+    /// File the sequence numbers and output positions are saved to
+    pub state_file: Option<String>,
+    /// Create the root directory when missing
+    pub create_root: bool,
+    /// What to do when the root directory is unsafe
+    pub root_permissions: RootPolicy,
+    /// Mode and label of created FIFOs
+    pub fifo: FifoOptions,
+    /// Appended to every pipe name, isolating instances from each other
+    pub pipe_suffix: String,
+    /// Intervals and delays of the worker loops
+    pub timing: Timing,
+    /// What to do when a worker thread panics
+    pub restart: RestartPolicy,
+    /// Replace workers making no progress for this long
+    pub watchdog: Option<time::Duration>,
+    /// Interval between throughput lines in the log
+    pub throughput: Option<time::Duration>,
+    /// Ceiling on the bytes read by all inputs together
+    pub throttle: Option<Arc<Throttle>>,
+    /// Resource limits applied to the process at startup
+    pub limits: Limits,
+    /// Percent of the file descriptor limit in use warned about
+    pub fd_warning: u64,
+    /// Capabilities kept once initialised, the others are dropped
+    pub keep_capabilities: CapSet,
+    /// Observers of the events of the splitter
+    pub events: Arc<Observers>,
+    /// Warnings about the configuration are errors
+    pub strict: bool,
+}
+
+/// Intervals and delays of the worker loops, each tunable on its own
+#[derive(Clone, Copy, Debug)]
+struct Timing {
+    /// Longest wait for a pipe or a queued record before checking for signals
+    pub poll: time::Duration,
+    /// Delay before retrying an operation that could not proceed
+    pub retry: time::Duration,
+    /// Interval of the supervising loop
+    pub supervise: time::Duration,
+    /// Interval between status file updates
+    pub status: time::Duration,
+    /// Interval between write-ahead log checkpoints
+    pub checkpoint: time::Duration,
+    /// Longest time writers keep flushing queued records on shutdown
+    pub drain: time::Duration,
+}
+
+/// Drain timeout set by the caller, overriding `[DEFAULT] drain_timeout`
+static DRAIN_OVERRIDE: Mutex<Option<time::Duration>> = Mutex::new(None);
+
+/// Strict parsing requested by the caller, whatever `[DEFAULT] strict`
+static STRICT_OVERRIDE: AtomicBool = AtomicBool::new(false);
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            poll: TIME_OUT,
+            retry: TIME_OUT,
+            supervise: TIME_OUT,
+            status: STATUS_INTERVAL,
+            checkpoint: WAL_CHECKPOINT_INTERVAL,
+            drain: DRAIN_TIMEOUT,
+        }
+    }
+}
+
+/// How FIFOs created by the splitter are set up
+#[derive(Clone, Debug, Default)]
+struct FifoOptions {
+    /// Permission bits, overriding the umask
+    pub mode: Option<u32>,
+    /// Security label, as `(extended attribute, value)`
+    pub label: Option<(String, String)>,
+    /// What to do with a file other than a FIFO at the path
+    pub non_fifo: NonFifoPolicy,
+}
+
+/// Parsed configuration file
+struct Topology {
+    /// Process wide settings
+    pub settings: Settings,
+    /// Input pipes and their outputs
+    pub inputs: Vec<Arc<SplitIn>>,
+}
+
+impl SplitOut {
+    /// Create the output FIFO at `path` if missing, see [`Writer::create_fifo`]
+    pub fn create_fifo<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        Writer::create_fifo(path, &self.fifo)
+    }
+    /// Label of the output, its path when unlabelled
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.pipe)
+    }
+    /// Account for an error of the output and run its error hook
+    pub fn failed(&self, error: &dyn fmt::Display) {
+        self.status.failed();
+        self.lifecycle
+            .failed(&self.pipe, self.name(), &error.to_string());
+    }
+    /// Account for `record` dropped for `reason`
+    pub fn dropped(&self, record: &Record, reason: DropReason) {
+        match reason {
+            DropReason::QueueFull => self.status.dropped(record.seq),
+            DropReason::Overflow => self.status.overflowed(record.seq),
+            DropReason::Expired => self.status.expired(record.seq),
+            DropReason::Evicted => self.status.skipped(record.seq),
+        }
+        self.events.dropped(&DroppedRecord {
+            output: self.name(),
+            pipe: &self.pipe,
+            seq: record.seq,
+            data: &record.data,
+            reason,
+        });
+    }
+    /// A new writer took the output over after `reason`
+    pub fn restarted(&self, reason: RestartReason) {
+        self.events.emit(|| Event::PipeRestarted {
+            pipe: self.name().to_owned(),
+            reason,
+        });
+    }
+    /// Copy of `record` as delivered to this output, `None` when a filter
+    /// drops it
+    pub fn transform(&self, record: &Record) -> Option<Record> {
+        Some(Record {
+            data: self.transforms.apply(&record.data)?,
+            ..*record
+        })
+    }
 }
 
 impl SplitIn {
+    /// Label of the input, its path when unlabelled
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.pipe)
+    }
+    /// Account for an error of the input and run its error hook
+    pub fn failed(&self, error: &dyn fmt::Display) {
+        self.status.failed();
+        self.lifecycle
+            .failed(&self.pipe, self.name(), &error.to_string());
+    }
+    /// A new reader took the input over after `reason`
+    pub fn restarted(&self, reason: RestartReason) {
+        self.events.emit(|| Event::PipeRestarted {
+            pipe: self.name().to_owned(),
+            reason,
+        });
+    }
     /// Count of enabled outputs
     pub fn enabled_outputs(&self) -> usize {
         self.outputs
@@ -118,6 +599,10 @@ impl SplitIn {
             .filter(|x| x.configuration.enabled)
             .count()
     }
+    /// Whether a reader runs for the input
+    pub fn runs(&self) -> bool {
+        self.configuration.enabled && self.enabled_outputs() > 0
+    }
 }
 
 impl fmt::Display for Config {
@@ -135,7 +620,8 @@ impl fmt::Display for SplitOut {
         write!(
             f,
             "OUT(pipe: {}, configuration: {})",
-            self.pipe, self.configuration,
+            self.name(),
+            self.configuration,
         )
     }
 }
@@ -145,7 +631,7 @@ impl fmt::Display for SplitIn {
         write!(
             f,
             "IN(pipe: {}, configuration: {}, outputs: [count: {}, enabled: {}])",
-            self.pipe,
+            self.name(),
             self.configuration,
             self.outputs.len(),
             self.enabled_outputs()
@@ -156,35 +642,584 @@ impl fmt::Display for SplitIn {
 struct Parser;
 
 impl Parser {
-    ///
-    fn get_read_config(config: &str) -> Result<Config, ParseError> {
+    /// Read configuration of an input, defaults when empty
+    fn get_read_config(config: &str) -> Result<(Config, PipeOptions), ParseError> {
         if config.is_empty() {
-            return Ok(Config::default_read());
+            return Ok((Config::default_read(), PipeOptions::default()));
         }
         Self::get_split_configuration(config)
     }
-    ///
-    fn get_write_config(config: &str) -> Result<Config, ParseError> {
+    /// Write configuration of an output, defaults when empty
+    fn get_write_config(config: &str) -> Result<(Config, PipeOptions), ParseError> {
         if config.is_empty() {
-            return Ok(Config::default_write());
+            return Ok((Config::default_write(), PipeOptions::default()));
         }
         Self::get_split_configuration(config)
     }
-    ///
-    fn get_root_directory(conf: &Ini) -> &str {
-        let root = conf.get_from_or(Some("DEFAULT"), "root", "/tmp/cvnpipes");
-        root
+    /// Whether warnings about the configuration are errors, `--strict` or
+    /// `[DEFAULT] strict`
+    fn is_strict(conf: &Ini) -> Result<bool, ParseError> {
+        Ok(STRICT_OVERRIDE.load(Ordering::Relaxed) || Self::get_flag(conf, "strict", false)?)
+    }
+    /// Log the warning `message`, an error in strict mode
+    fn warn(strict: bool, message: String) -> Result<(), ParseError> {
+        if strict {
+            return Err(ParseError::Configuration(message));
+        }
+        log!("Warning: {message}");
+        Ok(())
+    }
+    /// Warn about options of `pipe` that are not in `known`
+    fn check_options(
+        pipe: &str,
+        options: &PipeOptions,
+        known: &[&str],
+        settings: &Settings,
+    ) -> Result<(), ParseError> {
+        let listed = Chain::listed(options);
+        for key in options.unknown(known).filter(|key| !listed.contains(key)) {
+            Self::warn(settings.strict, format!("unknown option '{key}' <> {pipe}"))?;
+        }
+        Ok(())
+    }
+    /// Warn about sections that are neither settings, inputs of a topology
+    /// nor outputs of an input, and so are never read
+    fn check_sections(conf: &Ini, settings: &Settings) -> Result<(), ParseError> {
+        let inputs: HashSet<&str> = Self::get_pipes_sections(conf, None)?
+            .into_iter()
+            .flat_map(|section| section.iter().map(|(input, _)| input))
+            .collect();
+        for (name, _) in conf.iter() {
+            let Some(name) = name else { continue };
+            if name == "DEFAULT" || name == "PIPES" || name.starts_with("PIPES.") {
+                continue;
+            }
+            if !inputs.contains(name) {
+                Self::warn(
+                    settings.strict,
+                    format!("section '[{name}]' is not an input of any topology"),
+                )?;
+            }
+        }
+        Ok(())
+    }
+    /// Directory holding the pipes, `[DEFAULT] root`, by default in a
+    /// directory of its own for a named instance
+    fn get_root_directory(conf: &Ini, instance: Option<&str>) -> String {
+        match (conf.get_from(Some("DEFAULT"), "root"), instance) {
+            (Some(root), _) => Self::expand_instance(root, instance),
+            (None, Some(instance)) => format!("/tmp/cvnpipes/{instance}"),
+            (None, None) => "/tmp/cvnpipes".to_owned(),
+        }
+    }
+    /// Replace `{instance}` in a setting with the instance name
+    fn expand_instance(value: &str, instance: Option<&str>) -> String {
+        value.replace("{instance}", instance.unwrap_or_default())
+    }
+    /// Capacity of an output queue, `queue=` option
+    fn get_queue_size(options: &PipeOptions) -> Result<usize, ParseError> {
+        match options.number::<usize>("queue")? {
+            Some(0) => Err(ParseError::Configuration(
+                "Option 'queue' must be at least 1".into(),
+            )),
+            Some(size) => Ok(size),
+            None => Ok(QUEUE_SIZE),
+        }
+    }
+    /// Rolling transcript of a pipe, `transcript=` option
+    fn get_transcript(
+        root: &str,
+        configuration: &Config,
+        options: &PipeOptions,
+    ) -> Result<Option<Transcript>, ParseError> {
+        let Some(path) = options.get("transcript") else {
+            return Ok(None);
+        };
+        let size = options
+            .number::<u64>("transcript_size")?
+            .unwrap_or(transcript::DEFAULT_SIZE);
+        let window = options
+            .duration("transcript_window")?
+            .unwrap_or(transcript::DEFAULT_WINDOW);
+        let binary = matches!(
+            configuration.mode,
+            Some(OperationMode::BytesRead | OperationMode::BytesWrite)
+        );
+        Ok(Some(Transcript::new(
+            Self::get_pipe_path(root, path),
+            size,
+            window,
+            binary,
+        )))
+    }
+    /// Capacity of the read buffer of an input and bytes taken by a read
+    /// bypassing it, `read_buffer=` for text inputs and `read_chunk=`, both
+    /// at once, for byte inputs
+    fn get_read_sizes(
+        configuration: &Config,
+        options: &PipeOptions,
+    ) -> Result<(usize, usize), ParseError> {
+        let bytes = matches!(configuration.mode, Some(OperationMode::BytesRead));
+        let (option, other) = match bytes {
+            true => ("read_chunk", "read_buffer"),
+            false => ("read_buffer", "read_chunk"),
+        };
+        if options.get(other).is_some() {
+            return Err(ParseError::Configuration(format!(
+                "Option '{other}' cannot be used in mode '{}', use '{option}'",
+                if bytes { "rb" } else { "rt" }
+            )));
+        }
+        match options.number::<usize>(option)? {
+            Some(0) => Err(ParseError::Configuration(format!(
+                "Option '{option}' must be at least 1"
+            ))),
+            Some(size) => Ok((size, size)),
+            None => Ok((READ_BUFFER, MAX_PACKET)),
+        }
+    }
+    /// Record delimiting of an input, `framing=` option, with the size of
+    /// the chunks of `framing=chunk` in the `chunk=<bytes>` option
+    fn get_framing(configuration: &Config, options: &PipeOptions) -> Result<Framing, ParseError> {
+        match options.get("framing") {
+            None | Some("line") => Ok(Framing::Line),
+            Some("packet") => Ok(Framing::Packet),
+            Some("varint") => Ok(Framing::Varint),
+            Some("chunk") => {
+                if !matches!(configuration.mode, Some(OperationMode::BytesRead)) {
+                    return Err(ParseError::Configuration(
+                        "Option 'framing=chunk' requires the byte mode 'rb'".into(),
+                    ));
+                }
+                match options.number::<usize>("chunk")? {
+                    Some(0) => Err(ParseError::Configuration(
+                        "Option 'chunk' must be at least 1".into(),
+                    )),
+                    size => Ok(Framing::Chunk(size.unwrap_or(CHUNK_SIZE))),
+                }
+            }
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for option 'framing'"
+            ))),
+        }
+    }
+    /// Rewrites of an output's records, in the order they are applied
+    fn get_transforms(configuration: &Config, options: &PipeOptions) -> Result<Chain, ParseError> {
+        let transforms = Chain::build(options)?;
+        if transforms.contains("newline")
+            && matches!(configuration.mode, Some(OperationMode::BytesWrite))
+        {
+            return Err(ParseError::Configuration(
+                "Option 'newline' requires the text mode 'wt'".into(),
+            ));
+        }
+        Ok(transforms)
+    }
+    /// Custom sink of an output, `sink=<scheme>://<target>` option
+    fn get_sink(options: &PipeOptions) -> Result<Option<SinkTarget>, ParseError> {
+        let Some(uri) = options.get("sink") else {
+            return Ok(None);
+        };
+        if options.get("ack").is_some() {
+            return Err(ParseError::Configuration(
+                "Option 'ack' cannot be used with option 'sink'".into(),
+            ));
+        }
+        Self::check_secrets("sink", uri)?;
+        match SinkTarget::resolve_sink(uri) {
+            Some(target) => Ok(Some(target)),
+            None => Err(ParseError::Configuration(format!(
+                "No sink registered for '{uri}'"
+            ))),
+        }
+    }
+    /// Chunk size of the outputs of an input in turn, `rotate=<records>` or
+    /// `rotate_bytes=<bytes>` option
+    fn get_rotation(options: &PipeOptions) -> Result<Option<ChunkSize>, ParseError> {
+        let size = match (
+            options.number::<u64>("rotate")?,
+            options.number::<u64>("rotate_bytes")?,
+        ) {
+            (None, None) => return Ok(None),
+            (Some(records), None) => ChunkSize::Records(records),
+            (None, Some(bytes)) => ChunkSize::Bytes(bytes),
+            (Some(_), Some(_)) => {
+                return Err(ParseError::Configuration(
+                    "Options 'rotate' and 'rotate_bytes' cannot be combined".into(),
+                ))
+            }
+        };
+        match size {
+            ChunkSize::Records(0) | ChunkSize::Bytes(0) => Err(ParseError::Configuration(
+                "Rotation chunks must be at least 1".into(),
+            )),
+            size => Ok(Some(size)),
+        }
+    }
+    /// Refuse malformed `{secret:<scheme>://<name>}` references in `option`
+    fn check_secrets(option: &str, uri: &str) -> Result<(), ParseError> {
+        secrets::check(uri).map_err(|reference| {
+            ParseError::Configuration(format!(
+                "Invalid secret reference '{reference}' in option '{option}'"
+            ))
+        })
+    }
+    /// Custom source of an input, `source=<scheme>://<target>` option
+    fn get_source(options: &PipeOptions) -> Result<Option<SourceTarget>, ParseError> {
+        let Some(uri) = options.get("source") else {
+            return Ok(None);
+        };
+        Self::check_secrets("source", uri)?;
+        match SourceTarget::resolve_source(uri) {
+            Some(target) => Ok(Some(target)),
+            None => Err(ParseError::Configuration(format!(
+                "No source registered for '{uri}'"
+            ))),
+        }
+    }
+    /// Refuse options that would break the records of an input framed
+    /// other than by lines: merging packets, or splitting packets and messages
+    fn check_framing_options(
+        pipe: &str,
+        framing: Framing,
+        coalesce: bool,
+        oversize: Oversize,
+    ) -> Result<(), ParseError> {
+        let option = match (framing, coalesce, oversize) {
+            (Framing::Line, _, _) => return Ok(()),
+            (Framing::Packet, true, _) => "coalesce",
+            (_, _, Oversize::Split) => "oversize=split",
+            _ => return Ok(()),
+        };
+        Err(ParseError::Configuration(format!(
+            "Option '{option}' cannot be used on {pipe}, its input has {framing:?} framing"
+        )))
+    }
+    /// Refuse delivery groups among outputs taking turns, a group member
+    /// would miss every record of the others
+    fn check_rotation_groups(outputs: &[Arc<SplitOut>]) -> Result<(), ParseError> {
+        match outputs.iter().find(|output| output.group.is_some()) {
+            Some(output) => Err(ParseError::Configuration(format!(
+                "Option 'group' cannot be used on {}, its input rotates its outputs",
+                output.pipe
+            ))),
+            None => Ok(()),
+        }
+    }
+    /// Handling of records larger than `PIPE_BUF`, `oversize=` option
+    fn get_oversize(options: &PipeOptions) -> Result<Oversize, ParseError> {
+        match options.get("oversize") {
+            None | Some("warn") => Ok(Oversize::Warn),
+            Some("split") => Ok(Oversize::Split),
+            Some("reject") => Ok(Oversize::Reject),
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for option 'oversize'"
+            ))),
+        }
+    }
+    /// How an output FIFO is opened, `open=` option
+    fn get_open_mode(options: &PipeOptions) -> Result<OpenMode, ParseError> {
+        match options.get("open") {
+            None | Some("poll") => Ok(OpenMode::Poll),
+            Some("wait") => Ok(OpenMode::Wait),
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for option 'open'"
+            ))),
+        }
+    }
+    /// When an output FIFO is held open, `open_policy=` option
+    fn get_open_policy(options: &PipeOptions) -> Result<OpenPolicy, ParseError> {
+        match options.get("open_policy") {
+            None | Some("on_demand") => Ok(OpenPolicy::OnDemand),
+            Some("persistent") => Ok(OpenPolicy::Persistent),
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for option 'open_policy'"
+            ))),
+        }
+    }
+    /// Size in bytes of the overflow buffer of an output, `overflow=` option
+    fn get_overflow(options: &PipeOptions) -> Result<Option<usize>, ParseError> {
+        match options.number::<usize>("overflow")? {
+            Some(0) => Err(ParseError::Configuration(
+                "Option 'overflow' must be at least 1".into(),
+            )),
+            size => Ok(size),
+        }
+    }
+    /// High water mark of an output queue in percent, `high_water=` option
+    fn get_high_water(options: &PipeOptions) -> Result<Option<usize>, ParseError> {
+        match options.number::<usize>("high_water")? {
+            Some(percent) if !(1..=100).contains(&percent) => Err(ParseError::Configuration(
+                "Option 'high_water' must be a percentage between 1 and 100".into(),
+            )),
+            percent => Ok(percent),
+        }
+    }
+    /// Absolute path of the pipe `name`, relative to `root` unless absolute
+    fn get_pipe_path(root: &str, name: &str) -> String {
+        if name.starts_with('/') {
+            name.to_owned()
+        } else {
+            format!("{root}/{name}")
+        }
+    }
+    /// Absolute path of the configured pipe `name`. Paths of pipes are built
+    /// as strings: `root` and every pipe name come from the configuration,
+    /// which rust-ini only reads as UTF-8
+    fn get_fifo_path(settings: &Settings, name: &str) -> String {
+        format!("{}/{name}{}", settings.root, settings.pipe_suffix)
+    }
+    /// Boolean setting `key` of the `DEFAULT` section
+    fn get_flag(conf: &Ini, key: &str, default: bool) -> Result<bool, ParseError> {
+        match conf.get_from(Some("DEFAULT"), key).map(str::to_lowercase) {
+            None => Ok(default),
+            Some(value) => match value.as_str() {
+                "1" | "true" | "yes" => Ok(true),
+                "0" | "false" | "no" => Ok(false),
+                _ => Err(ParseError::Configuration(format!(
+                    "Invalid boolean '{value}' for setting '{key}'"
+                ))),
+            },
+        }
+    }
+    /// Process wide settings from the `DEFAULT` section
+    fn get_settings(conf: &Ini) -> Result<Settings, ParseError> {
+        let instance = conf.get_from(Some("DEFAULT"), "instance");
+        Ok(Settings {
+            root: Self::get_root_directory(conf, instance),
+            instance: instance.map(str::to_owned),
+            status_file: conf
+                .get_from(Some("DEFAULT"), "status_file")
+                .map(|file| Self::expand_instance(file, instance)),
+            state_file: conf
+                .get_from(Some("DEFAULT"), "state_file")
+                .map(|file| Self::expand_instance(file, instance)),
+            create_root: Self::get_flag(conf, "create_root", true)?,
+            root_permissions: Self::get_root_policy(conf)?,
+            fifo: FifoOptions {
+                mode: Self::get_fifo_mode(conf)?,
+                label: Self::get_fifo_label(conf),
+                non_fifo: Self::get_non_fifo_policy(conf)?,
+            },
+            pipe_suffix: Self::get_pipe_suffix(conf)?,
+            timing: Self::get_timing(conf)?,
+            restart: Self::get_restart_policy(conf)?,
+            watchdog: Self::get_optional_duration(conf, "watchdog")?,
+            throughput: Self::get_optional_duration(conf, "throughput_interval")?,
+            throttle: Self::get_throttle(conf)?,
+            limits: Self::get_limits(conf)?,
+            fd_warning: Self::get_fd_warning(conf)?,
+            keep_capabilities: Self::get_keep_capabilities(conf)?,
+            events: Arc::default(),
+            strict: Self::is_strict(conf)?,
+        })
+    }
+    /// Capabilities not dropped, `[DEFAULT] keep_capabilities`
+    fn get_keep_capabilities(conf: &Ini) -> Result<CapSet, ParseError> {
+        let names = conf
+            .get_from(Some("DEFAULT"), "keep_capabilities")
+            .unwrap_or_default();
+        CapSet::parse(names).map_err(|name| {
+            ParseError::Configuration(format!(
+                "Unknown capability '{name}' in setting 'keep_capabilities'"
+            ))
+        })
+    }
+    /// Percent of `RLIMIT_NOFILE` in use warned about, `[DEFAULT] fd_warning`
+    fn get_fd_warning(conf: &Ini) -> Result<u64, ParseError> {
+        match Self::get_count::<u64>(conf, "fd_warning")? {
+            None => Ok(FD_WARNING),
+            Some(percent) if percent <= 100 => Ok(percent),
+            Some(percent) => Err(ParseError::Configuration(format!(
+                "Invalid value '{percent}' for setting 'fd_warning'"
+            ))),
+        }
+    }
+    /// Positive number setting `key` of the `DEFAULT` section, `None` when unset
+    fn get_count<T: std::str::FromStr + Default + PartialEq>(
+        conf: &Ini,
+        key: &str,
+    ) -> Result<Option<T>, ParseError> {
+        let Some(value) = conf.get_from(Some("DEFAULT"), key) else {
+            return Ok(None);
+        };
+        match value.parse::<T>() {
+            Ok(count) if count != T::default() => Ok(Some(count)),
+            _ => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for setting '{key}'"
+            ))),
+        }
+    }
+    /// Resource limits of the process, `[DEFAULT] cgroup`, `memory_limit`,
+    /// `cpu_limit` and `open_files_limit`
+    fn get_limits(conf: &Ini) -> Result<Limits, ParseError> {
+        let limits = Limits {
+            cgroup: conf.get_from(Some("DEFAULT"), "cgroup").map(PathBuf::from),
+            memory: Self::get_count(conf, "memory_limit")?,
+            cpu: Self::get_count(conf, "cpu_limit")?,
+            open_files: Self::get_count(conf, "open_files_limit")?,
+        };
+        if limits.cpu.is_some() && limits.cgroup.is_none() {
+            return Err(ParseError::Configuration(
+                "Setting 'cpu_limit' requires setting 'cgroup'".into(),
+            ));
+        }
+        Ok(limits)
+    }
+    /// Bytes per second read by all inputs together, `[DEFAULT] max_throughput`
+    fn get_throttle(conf: &Ini) -> Result<Option<Arc<Throttle>>, ParseError> {
+        let rate = Self::get_count::<u64>(conf, "max_throughput")?;
+        Ok(rate.map(|rate| Arc::new(Throttle::new(rate))))
+    }
+    /// Duration setting `key` of the `DEFAULT` section, in (fractional) seconds
+    fn get_duration(
+        conf: &Ini,
+        key: &str,
+        default: time::Duration,
+    ) -> Result<time::Duration, ParseError> {
+        let Some(value) = conf.get_from(Some("DEFAULT"), key) else {
+            return Ok(default);
+        };
+        match value.parse::<f64>() {
+            Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(time::Duration::from_secs_f64(secs)),
+            _ => Err(ParseError::Configuration(format!(
+                "Invalid duration '{value}' for setting '{key}'"
+            ))),
+        }
+    }
+    /// Duration setting `key` of the `DEFAULT` section, `None` when unset
+    fn get_optional_duration(conf: &Ini, key: &str) -> Result<Option<time::Duration>, ParseError> {
+        match conf.get_from(Some("DEFAULT"), key) {
+            Some(_) => Self::get_duration(conf, key, TIME_OUT).map(Some),
+            None => Ok(None),
+        }
+    }
+    /// Intervals and delays of the worker loops
+    fn get_timing(conf: &Ini) -> Result<Timing, ParseError> {
+        let default = Timing::default();
+        Ok(Timing {
+            poll: Self::get_duration(conf, "poll_interval", default.poll)?,
+            retry: Self::get_duration(conf, "retry_delay", default.retry)?,
+            supervise: Self::get_duration(conf, "supervise_interval", default.supervise)?,
+            status: Self::get_duration(conf, "status_interval", default.status)?,
+            checkpoint: Self::get_duration(conf, "checkpoint_interval", default.checkpoint)?,
+            drain: match *DRAIN_OVERRIDE.lock().unwrap() {
+                Some(drain) => drain,
+                None => Self::get_duration(conf, "drain_timeout", default.drain)?,
+            },
+        })
+    }
+    /// Suffix of the pipe names, `[DEFAULT] pipe_suffix`, or `.<pid>` with
+    /// `[DEFAULT] ephemeral=true`
+    fn get_pipe_suffix(conf: &Ini) -> Result<String, ParseError> {
+        if let Some(suffix) = conf.get_from(Some("DEFAULT"), "pipe_suffix") {
+            return Ok(suffix.to_owned());
+        }
+        match Self::get_flag(conf, "ephemeral", false)? {
+            true => Ok(format!(".{}", std::process::id())),
+            false => Ok(String::new()),
+        }
+    }
+    /// Policy for files other than FIFOs at pipe paths, `[DEFAULT] non_fifo`
+    fn get_non_fifo_policy(conf: &Ini) -> Result<NonFifoPolicy, ParseError> {
+        match conf.get_from(Some("DEFAULT"), "non_fifo") {
+            None | Some("error") => Ok(NonFifoPolicy::Error),
+            Some("replace") => Ok(NonFifoPolicy::Replace),
+            Some("skip") => Ok(NonFifoPolicy::Skip),
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for setting 'non_fifo'"
+            ))),
+        }
+    }
+    /// What to do when a worker thread panics, `[DEFAULT] restart`
+    fn get_restart_policy(conf: &Ini) -> Result<RestartPolicy, ParseError> {
+        match conf.get_from(Some("DEFAULT"), "restart") {
+            None => Ok(RestartPolicy::Always),
+            Some(value) => RestartPolicy::parse(value).ok_or_else(|| {
+                ParseError::Configuration(format!("Invalid value '{value}' for setting 'restart'"))
+            }),
+        }
+    }
+    /// Disable the pipe at `pipe` when the `non_fifo` policy skips it.
+    /// Parsing leaves files alone, those to replace are removed once the
+    /// root is checked, or when the writer creates the FIFO.
+    fn check_pipe(
+        pipe: &str,
+        settings: &Settings,
+        configuration: &mut Config,
+    ) -> Result<(), ParseError> {
+        if settings.fifo.non_fifo == NonFifoPolicy::Replace {
+            return Ok(());
+        }
+        match security::check_fifo(Path::new(pipe), settings.fifo.non_fifo) {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                log!("Warning: skipping pipe that is not a FIFO <> {pipe}");
+                configuration.enabled = false;
+                Ok(())
+            }
+            Err(e) => Err(ParseError::Configuration(e.to_string())),
+        }
+    }
+    /// Security label of created FIFOs, `[DEFAULT] fifo_label` stored in the
+    /// extended attribute `[DEFAULT] fifo_label_attr`, SELinux by default
+    fn get_fifo_label(conf: &Ini) -> Option<(String, String)> {
+        let label = conf.get_from(Some("DEFAULT"), "fifo_label")?;
+        let attr = conf.get_from_or(Some("DEFAULT"), "fifo_label_attr", "security.selinux");
+        Some((attr.to_owned(), label.to_owned()))
+    }
+    /// Octal permission bits of created FIFOs, `[DEFAULT] fifo_mode`
+    fn get_fifo_mode(conf: &Ini) -> Result<Option<u32>, ParseError> {
+        let Some(value) = conf.get_from(Some("DEFAULT"), "fifo_mode") else {
+            return Ok(None);
+        };
+        match u32::from_str_radix(value.trim_start_matches("0o"), 8) {
+            Ok(mode) if mode <= 0o777 => Ok(Some(mode)),
+            _ => Err(ParseError::Configuration(format!(
+                "Invalid mode '{value}' for setting 'fifo_mode'"
+            ))),
+        }
+    }
+    /// Policy for an unsafe root directory, `[DEFAULT] root_permissions`
+    fn get_root_policy(conf: &Ini) -> Result<RootPolicy, ParseError> {
+        match conf.get_from(Some("DEFAULT"), "root_permissions") {
+            None | Some("refuse") => Ok(RootPolicy::Refuse),
+            Some("warn") => Ok(RootPolicy::Warn),
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid value '{value}' for setting 'root_permissions'"
+            ))),
+        }
+    }
+    /// Whether a pipe is enabled: `1`, or a condition evaluated when the
+    /// configuration is loaded, `env:NAME` when the environment variable
+    /// `NAME` is set to anything but an empty value or `0`, `file:<path>`
+    /// when a file exists at `path`. A condition is negated by a leading `!`.
+    fn get_enabled(value: &str) -> bool {
+        let value = value.trim();
+        let (negated, condition) = match value.strip_prefix('!') {
+            Some(condition) => (true, condition),
+            None => (false, value),
+        };
+        let holds = if let Some(name) = condition.strip_prefix("env:") {
+            std::env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0")
+        } else if let Some(path) = condition.strip_prefix("file:") {
+            Path::new(path).exists()
+        } else {
+            return value == "1";
+        };
+        holds != negated
     }
-    ///
-    fn get_split_configuration(config: &str) -> Result<Config, ParseError> {
-        let operation_config: Vec<&str> = config.split(",").collect();
+    /// Parse an `enabled,mode[,key=value...]` configuration value
+    fn get_split_configuration(config: &str) -> Result<(Config, PipeOptions), ParseError> {
+        let operation_config = options::tokenize(config)?;
 
-        let enabled = match operation_config.get(0) {
-            Some(s) => s.to_lowercase().as_str().eq("1"),
+        let enabled = match operation_config.first() {
+            Some(s) => Self::get_enabled(s),
             None => false,
         };
 
-        let mode = match operation_config.get(1) {
+        let mode = match operation_config.get(1).map(|s| s.trim()) {
+            Some("") | None => None,
             Some(s) => match s.to_lowercase().as_str() {
                 "rt" => Some(OperationMode::StringRead),
                 "rb" => Some(OperationMode::BytesRead),
@@ -196,24 +1231,107 @@ impl Parser {
                     )))
                 }
             },
-            None => None,
         };
 
-        Ok(Config { enabled, mode })
+        let options = PipeOptions::parse(operation_config.get(2..).unwrap_or_default())?;
+
+        Ok((Config { enabled, mode }, options))
     }
-    ///
+    /// Outputs listed in the section named after `input_pipe`
     fn get_split_outputs(
         conf: &Ini,
         input_pipe: &str,
-        root: &str,
+        settings: &Settings,
+        framing: Framing,
+        turns: &HashMap<String, Arc<WriteTurn>>,
     ) -> Result<Vec<Arc<SplitOut>>, ParseError> {
+        let root = settings.root.as_str();
         let outputs = if let Some(arg) = conf.section(Some(input_pipe)) {
             let mut out_puts = Vec::new();
 
             for (key, value) in arg.iter() {
+                let pipe = Self::get_fifo_path(settings, &naming::expand(key, input_pipe));
+                let timed = TimedPath::parse(&pipe);
+                let (mut configuration, options) =
+                    Self::get_write_config(&naming::expand(value, input_pipe))?;
+                Self::check_options(&pipe, &options, OUTPUT_OPTIONS, settings)?;
+                let sink = Self::get_sink(&options)?;
+                let turn = turns.get(&pipe).cloned();
+                if turn.is_some() && options.get("ack").is_some() {
+                    return Err(ParseError::Configuration(format!(
+                        "output '{pipe}' is shared by several inputs, it cannot be acknowledged"
+                    )));
+                }
+                let standby = options
+                    .get("standby")
+                    .map(|standby| Self::get_pipe_path(root, standby) + &settings.pipe_suffix);
+                if turn.is_some() && options.get("transcript").is_some() {
+                    return Err(ParseError::Configuration(format!(
+                        "output '{pipe}' is shared by several inputs, it cannot have a transcript"
+                    )));
+                }
+                if standby.is_some() && options.get("ack").is_some() {
+                    return Err(ParseError::Configuration(format!(
+                        "output '{pipe}' has a standby, it cannot be acknowledged"
+                    )));
+                }
+                if sink.is_none() {
+                    let current = match &timed {
+                        Some(timed) => timed.resolve(SystemTime::now()),
+                        None => pipe.clone(),
+                    };
+                    Self::check_pipe(&current, settings, &mut configuration)?;
+                }
+                let queue = Self::get_queue_size(&options)?;
+                let overflow = Self::get_overflow(&options)?;
+                let buffer_until_reader = options.flag("buffer_until_reader")?.unwrap_or(false);
+                let channel = RecordQueue::new(queue, overflow.map(Overflow::new));
+                if buffer_until_reader {
+                    channel.retain_until_attached();
+                }
+                let coalesce = options.flag("coalesce")?.unwrap_or(false);
+                let oversize = Self::get_oversize(&options)?;
+                let packet = framing == Framing::Packet;
+                Self::check_framing_options(&pipe, framing, coalesce, oversize)?;
+
                 out_puts.push(Arc::new(SplitOut {
-                    pipe: format!("{root}/{key}"),
-                    configuration: Self::get_write_config(value)?,
+                    evict_after: options.duration("evict_after")?,
+                    reprobe: options.duration("reprobe")?.unwrap_or(REPROBE_INTERVAL),
+                    ack: options
+                        .get("ack")
+                        .map(|ack| Self::get_pipe_path(root, ack) + &settings.pipe_suffix),
+                    ack_timeout: options.duration("ack_timeout")?.unwrap_or(ACK_TIMEOUT),
+                    notify: options
+                        .get("notify")
+                        .map(|notify| Self::get_pipe_path(root, notify) + &settings.pipe_suffix),
+                    standby,
+                    transcript: Self::get_transcript(root, &configuration, &options)?,
+                    ttl: options.duration("ttl")?,
+                    buffer_until_reader,
+                    on_attach: options.get("on_attach").map(str::to_owned),
+                    on_detach: options.get("on_detach").map(str::to_owned),
+                    lifecycle: Lifecycle::parse(&options),
+                    timed,
+                    channel,
+                    high_water: Self::get_high_water(&options)?,
+                    group: options.get("group").map(str::to_owned),
+                    label: options.get("label").map(str::to_owned),
+                    coalesce,
+                    oversize,
+                    open: Self::get_open_mode(&options)?,
+                    open_policy: Self::get_open_policy(&options)?,
+                    packet,
+                    transforms: Self::get_transforms(&configuration, &options)?,
+                    sink,
+                    turn,
+                    fifo: settings.fifo.clone(),
+                    timing: settings.timing,
+                    restart: settings.restart,
+                    worker: Liveness::default(),
+                    events: Arc::clone(&settings.events),
+                    pipe,
+                    configuration,
+                    status: OutputStatus::default(),
                 }))
             }
 
@@ -223,177 +1341,630 @@ impl Parser {
         };
         Ok(outputs)
     }
-    ///
+    /// Inputs listed in the `PIPES` section
     fn get_split_inputs(
-        root: &str,
+        settings: &Settings,
         input_pipes: &ini::Properties,
         conf: &Ini,
+        turns: &HashMap<String, Arc<WriteTurn>>,
     ) -> Result<Vec<Arc<SplitIn>>, ParseError> {
+        let root = settings.root.as_str();
         let mut split_configs = Vec::new();
 
         for (input_pipe, read_configuration) in input_pipes.iter() {
+            let pipe = Self::get_fifo_path(settings, input_pipe);
+            let (mut configuration, options) = Self::get_read_config(read_configuration)?;
+            Self::check_options(&pipe, &options, INPUT_OPTIONS, settings)?;
+            let source = Self::get_source(&options)?;
+            if source.is_none() {
+                Self::check_pipe(&pipe, settings, &mut configuration)?;
+            }
+            let framing = Self::get_framing(&configuration, &options)?;
+            let (read_buffer, read_chunk) = Self::get_read_sizes(&configuration, &options)?;
+            let rotate = Self::get_rotation(&options)?;
+            let outputs = Self::get_split_outputs(conf, input_pipe, settings, framing, turns)?;
+            if rotate.is_some() {
+                Self::check_rotation_groups(&outputs)?;
+            }
+
             let split_in = SplitIn {
-                pipe: format!("{root}/{input_pipe}"),
-                configuration: Self::get_read_config(read_configuration)?,
-                outputs: Self::get_split_outputs(&conf, input_pipe, root)?,
+                wal: options.get("wal").map(|wal| Self::get_pipe_path(root, wal)),
+                read_buffer,
+                read_chunk,
+                framing,
+                base64: options.flag("base64")?.unwrap_or(false),
+                source,
+                label: options.get("label").map(str::to_owned),
+                lifecycle: Lifecycle::parse(&options),
+                rotate,
+                throttle: settings.throttle.clone(),
+                timing: settings.timing,
+                restart: settings.restart,
+                worker: Liveness::default(),
+                events: Arc::clone(&settings.events),
+                transcript: Self::get_transcript(root, &configuration, &options)?,
+                pipe,
+                configuration,
+                outputs,
+                status: InputStatus::default(),
             };
 
             split_configs.push(Arc::new(split_in));
         }
         Ok(split_configs)
     }
-    ///
-    fn parse_config(conf: &Ini) -> Result<Vec<Arc<SplitIn>>, ParseError> {
-        let root = Self::get_root_directory(&conf);
+    /// Build the splitting configuration from a loaded INI document
+    fn parse_config(conf: &Ini, topology: Option<&str>) -> Result<Topology, ParseError> {
+        if let Some(plugins) = conf.get_from(Some("DEFAULT"), "plugins") {
+            plugin::load_plugins(plugins).map_err(ParseError::Configuration)?;
+        }
+        let settings = Self::get_settings(conf)?;
+        let root = settings.root.as_str();
         let root_path = Path::new(root);
 
         if !root_path.exists() {
-            match fs::create_dir_all(root_path) {
-                Err(_e) => {
-                    return Err(ParseError::Configuration(
-                        "Could not create pipe root directory".into(),
-                    ));
-                }
-                _ => {}
+            if !settings.create_root {
+                return Err(ParseError::Configuration(format!(
+                    "Pipe root directory '{root}' does not exist"
+                )));
+            }
+            if let Err(_e) = fs::create_dir_all(root_path) {
+                return Err(ParseError::Configuration(
+                    "Could not create pipe root directory".into(),
+                ));
             }
         }
 
-        let input_pipes = match conf.section(Some("PIPES")) {
-            Some(arg) => arg,
-            None => {
-                return Err(ParseError::Configuration(
-                    "configuration must contain a 'PIPES' section".into(),
-                ))
+        Self::check_sections(conf, &settings)?;
+        let sections = Self::get_pipes_sections(conf, topology)?;
+        let turns = Self::get_shared_outputs(conf, &settings, &sections);
+        let mut inputs = Vec::new();
+        for input_pipes in sections {
+            inputs.extend(Self::get_split_inputs(
+                &settings,
+                input_pipes,
+                conf,
+                &turns,
+            )?);
+        }
+
+        Ok(Topology { settings, inputs })
+    }
+    /// Write turns of the output FIFOs listed by more than one input
+    fn get_shared_outputs(
+        conf: &Ini,
+        settings: &Settings,
+        sections: &[&ini::Properties],
+    ) -> HashMap<String, Arc<WriteTurn>> {
+        let mut listed: HashMap<String, usize> = HashMap::new();
+        for input_pipes in sections {
+            for (input_pipe, _) in input_pipes.iter() {
+                let Some(outputs) = conf.section(Some(input_pipe)) else {
+                    continue;
+                };
+                for (key, _) in outputs.iter() {
+                    let pipe = Self::get_fifo_path(settings, &naming::expand(key, input_pipe));
+                    *listed.entry(pipe).or_default() += 1;
+                }
             }
-        };
+        }
+        listed
+            .into_iter()
+            .filter(|(_, inputs)| *inputs > 1)
+            .map(|(pipe, _)| (pipe, Arc::default()))
+            .collect()
+    }
+    /// Input sections of the topology `name`, `[PIPES.<name>]`, or of every
+    /// topology, `[PIPES]` and all `[PIPES.<name>]`, when `None`
+    fn get_pipes_sections<'a>(
+        conf: &'a Ini,
+        topology: Option<&str>,
+    ) -> Result<Vec<&'a ini::Properties>, ParseError> {
+        if let Some(name) = topology {
+            return match conf.section(Some(format!("PIPES.{name}"))) {
+                Some(section) => Ok(vec![section]),
+                None => Err(ParseError::Configuration(format!(
+                    "configuration has no topology '{name}'"
+                ))),
+            };
+        }
 
-        Self::get_split_inputs(root, input_pipes, &conf)
+        let sections: Vec<&ini::Properties> = conf
+            .iter()
+            .filter(|(name, _)| {
+                name.is_some_and(|name| name == "PIPES" || name.starts_with("PIPES."))
+            })
+            .map(|(_, section)| section)
+            .collect();
+        if sections.is_empty() {
+            return Err(ParseError::Configuration(
+                "configuration must contain a 'PIPES' section".into(),
+            ));
+        }
+        Ok(sections)
     }
 
-    ///
+    /// Load an INI document from disk
     fn load_ini_configuration<P: AsRef<Path>>(file_path: P) -> Result<Ini, ParseError> {
         let conf = match Ini::load_from_file(file_path) {
             Ok(config) => config,
             Err(e) => return Err(ParseError::Ini(e)),
         };
 
-        Ok(conf)
+        Self::normalize_names(conf)
     }
 
-    /// Loading Splitting configuration from an INI formatted configuration file
-    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> Result<Vec<Arc<SplitIn>>, ParseError> {
-        let conf = match Self::load_ini_configuration(file_path) {
-            Ok(value) => value,
-            Err(value) => return Err(value),
+    /// The document with sections and `DEFAULT` settings named as the parser
+    /// expects, whatever their case, and migrated to the current schema
+    /// version. With `lenient=no` a name in another case is an error
+    /// instead, for configurations to be validated as written.
+    fn normalize_names(conf: Ini) -> Result<Ini, ParseError> {
+        let (normalized, renamed) = migrate::canonical_names(&conf);
+        let normalized = match renamed {
+            Some(message) if !Self::get_flag(&normalized, "lenient", true)? => {
+                return Err(ParseError::Configuration(message))
+            }
+            Some(message) if Self::is_strict(&normalized)? => {
+                return Err(ParseError::Configuration(message))
+            }
+            _ => normalized,
         };
+        Ok(migrate::upgrade(normalized)?.0)
+    }
 
-        let split_configs = match Self::parse_config(&conf) {
-            Ok(value) => value,
-            Err(value) => return Err(value),
-        };
+    /// Configured names of the pipes and their paths
+    fn pipe_map(conf: &Ini) -> Result<Vec<(String, String)>, ParseError> {
+        let settings = Self::get_settings(conf)?;
+        let mut pipes = Vec::new();
+
+        for input_pipes in Self::get_pipes_sections(conf, None)? {
+            for (input_pipe, _) in input_pipes.iter() {
+                pipes.push((
+                    input_pipe.to_owned(),
+                    Self::get_fifo_path(&settings, input_pipe),
+                ));
+                if let Some(outputs) = conf.section(Some(input_pipe)) {
+                    for (output_pipe, _) in outputs.iter() {
+                        let output_pipe = naming::expand(output_pipe, input_pipe);
+                        pipes.push((
+                            output_pipe.clone(),
+                            Self::get_fifo_path(&settings, &output_pipe),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(pipes)
+    }
+
+    /// Loading Splitting configuration from an INI formatted configuration file
+    #[cfg(test)]
+    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> Result<Topology, ParseError> {
+        Self::load_topology(file_path, None)
+    }
+
+    /// Load the topology `name`, or every topology when `None`, from an INI
+    /// formatted configuration file
+    pub fn load_topology<P: AsRef<Path>>(
+        file_path: P,
+        name: Option<&str>,
+    ) -> Result<Topology, ParseError> {
+        let conf = Self::load_ini_configuration(file_path)?;
+
+        let split_configs = Self::parse_config(&conf, name)?;
 
         Ok(split_configs)
     }
 }
 
-///
+/// A record travelling from a reader to its writers
+#[derive(Clone, Debug)]
+struct Record {
+    /// Sequence number within the input
+    seq: u64,
+    /// Record contents
+    data: Vec<u8>,
+    /// When the record was read from the input
+    received: time::Instant,
+}
+
+/// Record being written to an output pipe
+struct Pending {
+    /// Record to write, framed for acknowledged delivery
+    record: Record,
+    /// Bytes of the record already written
+    offset: usize,
+    /// Record is written again, it was not taken from the channel
+    retransmit: bool,
+    /// Queued records merged into `record`, which carries the sequence
+    /// number of the last one
+    records: usize,
+    /// End offsets of the parts of `record` written one at a time
+    parts: Vec<usize>,
+}
+
+impl Pending {
+    /// Bytes to write next, up to the end of the current part
+    fn next_write(&self) -> &[u8] {
+        let end = self
+            .parts
+            .iter()
+            .find(|end| **end > self.offset)
+            .copied()
+            .unwrap_or(self.record.data.len());
+        &self.record.data[self.offset..end]
+    }
+}
+
+/// Split `data` into parts prefixed with `<part>/<parts> ` and no longer
+/// than `PIPE_BUF`, returning them back to back with their end offsets
+fn split_parts(data: &[u8]) -> (Vec<u8>, Vec<usize>) {
+    // The header width depends on the number of parts, which depends on the
+    // room left by the header
+    let mut parts = 1;
+    let payload = loop {
+        let payload = libc::PIPE_BUF - (2 * parts.to_string().len() + 2);
+        let needed = data.len().div_ceil(payload);
+        if needed <= parts {
+            break payload;
+        }
+        parts = needed;
+    };
+
+    let mut framed = Vec::with_capacity(libc::PIPE_BUF * parts);
+    let mut ends = Vec::with_capacity(parts);
+    for (index, chunk) in data.chunks(payload).enumerate() {
+        framed.extend_from_slice(format!("{}/{} ", index + 1, parts).as_bytes());
+        framed.extend_from_slice(chunk);
+        ends.push(framed.len());
+    }
+    (framed, ends)
+}
+
+/// Output worker, drains a channel into an output FIFO
 struct Writer {
     /// Flag to control Writing thread
     signal: Arc<Mutex<u8>>,
     /// Write output configuration
     config: Arc<SplitOut>,
-    /// Receiving channel for write data
-    receiver: mpsc::Receiver<String>,
-    /// Flag to ignore first data from channel
-    ignore_first_message: bool,
+    /// Record not yet fully written, kept across pipe reopens so no record
+    /// is lost on a reconnect
+    pending: Option<Pending>,
+    /// Last attempt to reach the consumer of an evicted output
+    last_probe: time::Instant,
+    /// Records awaiting acknowledgement, in acknowledged delivery mode
+    ack: Option<AckTracker>,
+    /// Doorbell rung once records were written
+    doorbell: Option<Doorbell>,
+    /// Time left to flush queued records once exit was requested
+    drain_deadline: Option<time::Instant>,
+    /// Generation of the writer, it stops once replaced by the watchdog
+    generation: u64,
+    /// Path of the output when last opened
+    path: String,
+    /// Records kept for the first consumer may still be queued
+    retained: bool,
+    /// The pipe is waiting for a free file descriptor to open
+    waiting_fd: bool,
+    /// Identifier of the writer, holding the turn of a shared output
+    id: u64,
+    /// Blocking open waiting for a consumer, `open=wait`
+    opening: Option<BlockingOpen>,
+    /// Records go to the standby FIFO, the output having no consumer
+    on_standby: bool,
+    /// Output FIFO opened by a consumer while records went to the standby
+    primary: Option<File>,
+    /// Standby FIFO held open once failed over to, so its consumer does not
+    /// see the end of the stream while records go to the output
+    standby: Option<File>,
+    /// Last attempt to open the output while on the standby
+    last_fail_back: time::Instant,
 }
 
 enum WriteFlow {
-    ///
+    /// Stop the writer
     Break,
-    ///
+    /// Reopen the pipe and continue
     Restart,
-    ///
+    /// Close the pipe until the reader has data again
     ClosePipe,
+    /// Pipe is full, wait until it is writable again
+    Wait,
+    /// The path of the output changed, open the new one
+    Switch,
+    /// A consumer opened the output, leave the standby for it
+    FailBack,
 }
-///
-impl<'a> Writer {
-    ///
-    ///
+impl Writer {
+    /// Create a FIFO at `path` with permission bits `mode` (0o644 when `None`)
     fn create<P: AsRef<Path>>(path: P, mode: Option<u32>) -> io::Result<()> {
-        let path = CString::new(path.as_ref().to_str().unwrap())?;
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
         let mode = mode.unwrap_or(0o644);
         let result: c_int = unsafe { mkfifo(path.as_ptr(), mode as mode_t) };
 
-        let result: i32 = result.into();
         if result == 0 {
             return Ok(());
         }
 
         let error = errno::errno();
         match error.0 {
-            EACCES => {
-                return Err(io::Error::new(
-                    io::ErrorKind::PermissionDenied,
-                    format!("could not open {:?}: {}", path, error),
-                ));
-            }
-            EEXIST => {
-                return Err(io::Error::new(
-                    io::ErrorKind::AlreadyExists,
-                    format!("could not open {:?}: {}", path, error),
-                ));
-            }
-            ENOENT => {
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("could not open {:?}: {}", path, error),
-                ));
-            }
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("could not open {:?}: {}", path, error),
-                ));
-            }
+            EACCES => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("could not open {:?}: {}", path, error),
+            )),
+            EEXIST => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("could not open {:?}: {}", path, error),
+            )),
+            ENOENT => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("could not open {:?}: {}", path, error),
+            )),
+            _ => Err(io::Error::other(format!(
+                "could not open {:?}: {}",
+                path, error
+            ))),
         }
     }
-    ///
-    ///
-    fn open_pipe(&mut self) -> Result<File, std::io::Error> {
-        let pipe = self.config.pipe.clone();
-
-        match Self::create(&pipe, Some(0o777)) {
+    /// Create a FIFO at `path` if missing, with the permission bits of `fifo`
+    /// regardless of the umask, or 0o777 masked by the umask when unset, and
+    /// the security label of `fifo`
+    fn create_fifo<P: AsRef<Path>>(path: P, fifo: &FifoOptions) -> io::Result<()> {
+        if !security::check_fifo(path.as_ref(), fifo.non_fifo)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a FIFO", path.as_ref().display()),
+            ));
+        }
+        match Self::create(&path, Some(fifo.mode.unwrap_or(0o777))) {
             Ok(_) => {}
             Err(e) => match e.kind() {
-                std::io::ErrorKind::AlreadyExists => {}
+                std::io::ErrorKind::AlreadyExists => return Ok(()),
                 _ => return Err(e),
             },
         };
+        if let Some(mode) = fifo.mode {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+        }
+        if let Some((attr, label)) = &fifo.label {
+            security::set_label(path.as_ref(), attr, label)?;
+        }
+        Ok(())
+    }
+    /// Create the output FIFO if missing and open it for non-blocking writes
+    fn open_pipe(&mut self) -> Result<File, std::io::Error> {
+        // A blocking open keeps its path until a consumer comes
+        if self.opening.is_none() {
+            if let Some(timed) = &self.config.timed {
+                self.path = timed.resolve(SystemTime::now());
+            }
+            self.config.create_fifo(&self.path)?;
+        }
+        let pipe = self.path.clone();
+
+        let f = match self.config.open {
+            OpenMode::Poll => Self::open_nonblocking(&pipe)?,
+            OpenMode::Wait => self.wait_open(&pipe)?,
+        };
+        self.set_packet(&f)?;
+        Ok(f)
+    }
 
-        let f = OpenOptions::new()
-            .write(true)
+    /// Open the FIFO at `pipe` for non-blocking writes, failing with `ENXIO`
+    /// while no consumer has it open
+    fn open_nonblocking(pipe: &str) -> io::Result<File> {
+        OpenOptions::new()
             .append(true)
             .custom_flags(libc::O_NONBLOCK)
-            .open(Path::new(&pipe));
-
-        f
+            .open(Path::new(pipe))
     }
 
-    ///
+    /// Make every write to `pipe` one packet, for an input in packet mode.
+    /// O_DIRECT cannot be given to open on a FIFO, it is set afterwards.
+    fn set_packet(&self, pipe: &File) -> io::Result<()> {
+        if self.config.packet {
+            let fd = pipe.as_raw_fd();
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_DIRECT) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Open the output, or its standby while no consumer has the output
+    /// open. The output opened while records went to the standby is
+    /// taken as is, a consumer seeing no writer come and go.
+    fn open_output(&mut self) -> io::Result<File> {
+        if let Some(pipe) = self.primary.take() {
+            self.on_standby = false;
+            log!("Failing back -> {}", &self.config);
+            return Ok(pipe);
+        }
+        let error = match self.open_pipe() {
+            Ok(pipe) => {
+                self.on_standby = false;
+                return Ok(pipe);
+            }
+            Err(e) => e,
+        };
+        let no_consumer = error.raw_os_error() == Some(libc::ENXIO)
+            || (error.kind() == io::ErrorKind::WouldBlock && self.opening.is_some());
+        let Some(standby) = self.config.standby.clone() else {
+            return Err(error);
+        };
+        if !no_consumer || self.config.status.is_evicted() {
+            return Err(error);
+        }
+        let pipe = match self.standby.take().filter(Self::has_reader) {
+            Some(held) => held,
+            None => {
+                self.config.create_fifo(&standby)?;
+                let Ok(pipe) = Self::open_nonblocking(&standby) else {
+                    return Err(error);
+                };
+                self.set_packet(&pipe)?;
+                pipe
+            }
+        };
+        self.standby = pipe.try_clone().ok();
+        if !self.on_standby {
+            log!("Failing over to {standby} -> {}", &self.config);
+            self.on_standby = true;
+        }
+        Ok(pipe)
+    }
+
+    /// Whether a consumer opened the output while records went to the
+    /// standby, the output being kept for [`Writer::open_output`]
+    fn should_fail_back(&mut self) -> bool {
+        if !self.on_standby || self.last_fail_back.elapsed() < self.config.timing.retry {
+            return false;
+        }
+        self.last_fail_back = time::Instant::now();
+        let opened = match self.config.open {
+            OpenMode::Poll => Self::open_nonblocking(&self.path).ok(),
+            OpenMode::Wait => match self.opening.as_mut() {
+                Some(opening) => opening.wait(time::Duration::ZERO).and_then(Result::ok),
+                None => None,
+            },
+        };
+        let Some(pipe) = opened else {
+            return false;
+        };
+        if matches!(self.config.open, OpenMode::Wait) {
+            self.opening = None;
+        }
+        match self.set_packet(&pipe) {
+            Ok(()) => {
+                self.primary = Some(pipe);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Wait a poll interval for a consumer to open `pipe`, failing with
+    /// `WouldBlock` while none came
+    fn wait_open(&mut self, pipe: &str) -> io::Result<File> {
+        let opening = match self.opening.as_mut() {
+            Some(opening) => opening,
+            None => self.opening.insert(BlockingOpen::start(pipe)?),
+        };
+        match opening.wait(self.config.timing.poll) {
+            Some(opened) => {
+                self.opening = None;
+                opened
+            }
+            None => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    /// Wait before opening the pipe again, the process is out of file
+    /// descriptors
+    fn wait_for_fd(&mut self) {
+        if !self.waiting_fd {
+            log!(
+                "Warning: out of file descriptors, delaying open <> {}",
+                &self.config
+            );
+            self.waiting_fd = true;
+        }
+        thread::sleep(self.config.timing.retry);
+    }
+
+    /// Whether a consumer still has the FIFO written to by `pipe` open
+    fn has_reader(pipe: &File) -> bool {
+        let mut fds = libc::pollfd {
+            fd: pipe.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut fds, 1, 0) };
+        ready >= 0 && fds.revents & libc::POLLERR == 0
+    }
+
+    /// Whether `pipe` can take data right now
+    fn is_writable(pipe: &File) -> bool {
+        let mut fds = libc::pollfd {
+            fd: pipe.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut fds, 1, 0) };
+        ready == 1 && fds.revents & libc::POLLOUT != 0
+    }
+
+    /// Exit was requested and the queued records are flushed, or could not
+    /// be within the drain timeout
     fn should_stop(&mut self) -> bool {
-        let state = self.signal.lock().unwrap();
-        *state == SIG_EXIT
+        self.config.worker.beat();
+        if !self.config.worker.is_current(self.generation) {
+            return true;
+        }
+        if *self.signal.lock().unwrap() != SIG_EXIT {
+            return false;
+        }
+        let drain = self.config.timing.drain;
+        let deadline = *self
+            .drain_deadline
+            .get_or_insert_with(|| time::Instant::now() + drain);
+        let drained = self.pending.is_none() && self.config.channel.is_empty();
+        if !drained && time::Instant::now() >= deadline {
+            if !drain.is_zero() {
+                log!(
+                    "Warning: drain timeout expired, abandoning queued records <> {}",
+                    &self.config
+                );
+            }
+            return true;
+        }
+        drained
+    }
+    /// The path of the output changed since it was opened
+    fn should_switch(&self) -> bool {
+        let Some(timed) = &self.config.timed else {
+            return false;
+        };
+        let path = timed.resolve(SystemTime::now());
+        if path == self.path {
+            return false;
+        }
+        log!("Switching output {} -> {}", &self.path, path);
+        true
     }
-    ///
+    /// Run `command`, the hook of `event` of the output, when configured
+    fn run_hook(&self, command: &Option<String>, event: &str, env: &[(&str, &str)]) {
+        if let Some(command) = command {
+            hooks::run(command, event, &self.path, self.config.name(), env);
+        }
+    }
+    /// Records kept until the first consumer attached are still to be
+    /// written to it
+    fn flushing_retained(&mut self) -> bool {
+        if !self.retained {
+            return false;
+        }
+        let idle = self.pending.is_none() && self.config.channel.is_empty();
+        if idle && !self.config.channel.retains() {
+            self.retained = false;
+        }
+        self.retained && !idle
+    }
+    /// The reader has no data, pipe should be closed, unless records kept
+    /// for the first consumer still have to reach it or the output is held
+    /// open persistently
     fn should_close_pipe(&mut self) -> bool {
+        if self.config.open_policy == OpenPolicy::Persistent || self.flushing_retained() {
+            return false;
+        }
         let state = self.signal.lock().unwrap();
         *state == SIG_CLOSE
     }
 
-    ///
+    /// Write `contents` to the pipe without blocking
     fn write(&mut self, contents: &[u8], sender: &pipe::Sender) -> Result<usize, io::Error> {
-        let op = sender.try_io(|| {
+        sender.try_io(|| {
             let buf_ptr = contents as *const _ as *const _;
             let res = unsafe { libc::write(sender.as_raw_fd(), buf_ptr, contents.len()) };
             if res != -1 {
@@ -401,12 +1972,15 @@ impl<'a> Writer {
             } else {
                 Err(io::Error::last_os_error())
             }
-        });
-        op
+        })
     }
 
-    ///
+    /// Keep the output pipe open until exit is requested
     fn run_loop(&mut self) -> Result<(), std::io::Error> {
+        if let Some(target) = self.config.sink.clone() {
+            self.run_sink(&target);
+            return Ok(());
+        }
         loop {
             // Exit loop
             if self.should_stop() {
@@ -415,24 +1989,78 @@ impl<'a> Writer {
 
             // At this point reader is'nt reading any data, so don't open the pipe
             if self.should_close_pipe() {
-                thread::sleep(TIME_OUT);
+                thread::sleep(self.config.timing.poll);
                 continue;
             }
 
-            let pipe = match self.open_pipe() {
+            // Output was evicted, release whatever is queued for it and
+            // only try to reach the consumer every once in a while
+            let probing = self.config.status.is_evicted();
+            if probing {
+                if let Some(pending) = self.pending.take() {
+                    self.discard(&pending);
+                }
+                while let Some(record) = self.config.channel.try_pop() {
+                    self.config.status.settle(record.seq);
+                }
+                if self.last_probe.elapsed() < self.config.reprobe {
+                    thread::sleep(self.config.timing.poll);
+                    continue;
+                }
+                self.last_probe = time::Instant::now();
+            }
+
+            let pipe = match self.open_output() {
                 Ok(f) => f,
                 Err(e) => match e.kind() {
                     io::ErrorKind::PermissionDenied => {
+                        log!("File -> {} Error {:?} ", &self.path, e);
+                        self.config.failed(&e);
                         return Err(e);
                     }
+                    _ if fds::is_exhausted(&e) => {
+                        self.wait_for_fd();
+                        continue;
+                    }
+                    io::ErrorKind::WouldBlock if self.opening.is_some() => {
+                        // The blocking open is still waiting for a consumer
+                        if !probing {
+                            self.config.status.blocked();
+                        }
+                        continue;
+                    }
                     _ => {
-                        thread::sleep(TIME_OUT);
+                        // No consumer has the pipe open for reading
+                        if !probing {
+                            self.config.status.blocked();
+                        }
+                        thread::sleep(self.config.timing.retry);
                         continue;
                     }
                 },
             };
+            // The pipe and its poll take a descriptor each
+            let mut poll = match Poll::new() {
+                Ok(poll) => poll,
+                Err(e) if fds::is_exhausted(&e) => {
+                    self.wait_for_fd();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            self.waiting_fd = false;
+            // A consumer has the pipe open, retained records go to it
+            self.config.channel.attach();
 
-            let mut poll = Poll::new()?;
+            if probing {
+                // A consumer holding the pipe without reading is still stalled
+                if !Self::is_writable(&pipe) {
+                    thread::sleep(self.config.timing.retry);
+                    continue;
+                }
+                self.config.status.reattach();
+                log!("Reattached output -> {}", &self.config);
+            }
 
             let mut sender = unsafe {
                 let fd = pipe.into_raw_fd();
@@ -442,25 +2070,124 @@ impl<'a> Writer {
             poll.registry()
                 .register(&mut sender, PIPE_SEND, Interest::WRITABLE)?;
 
-            println!("Writing data -> {}", &self.config);
+            log!("Writing data -> {}", &self.config);
+            self.run_hook(&self.config.on_attach, "attach", &[]);
+            self.config.events.emit(|| Event::ConsumerAttached {
+                output: self.config.name().to_owned(),
+            });
+
+            let flow = self.loop_till_stopped(&mut poll, &sender);
+            drop(sender);
+            self.release_turn();
+            let reason = match flow {
+                WriteFlow::Break => "exit",
+                WriteFlow::Restart => "consumer",
+                _ if self.config.status.is_evicted() => "evicted",
+                WriteFlow::Switch => "rollover",
+                WriteFlow::FailBack => "failback",
+                WriteFlow::ClosePipe | WriteFlow::Wait => "idle",
+            };
+            self.run_hook(
+                &self.config.on_detach,
+                "detach",
+                &[("PSPLIT_REASON", reason)],
+            );
+            if let WriteFlow::Break = flow {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write queued records to the output's custom sink until exit is
+    /// requested, reopening it after failures
+    fn run_sink(&mut self, target: &SinkTarget) {
+        let mut sink: Option<Box<dyn Sink>> = None;
+        // Report a sink that cannot be opened once, not on every retry
+        let mut reported = false;
+        loop {
+            if self.should_stop() {
+                break;
+            }
 
-            match self.loop_till_stopped(&mut poll, &sender) {
-                WriteFlow::Break => {
-                    break;
+            let Some(open) = sink.as_mut() else {
+                match target.open_sink() {
+                    Ok(opened) => {
+                        log!("Writing data -> {}", &self.config);
+                        self.config.channel.attach();
+                        sink = Some(opened);
+                        reported = false;
+                    }
+                    Err(e) => {
+                        if !reported {
+                            log!("Sink -> {} Error {:?}", target, e);
+                            self.config.failed(&e);
+                            reported = true;
+                        }
+                        self.config.status.blocked();
+                        thread::sleep(self.config.timing.retry);
+                    }
                 }
-                WriteFlow::Restart | WriteFlow::ClosePipe => {
-                    continue;
+                continue;
+            };
+
+            let pending = match self.pending.take() {
+                Some(pending) => pending,
+                None => match self.config.channel.pop_wait(Some(self.config.timing.poll)) {
+                    Popped::Record(record) => {
+                        if self.is_expired(&record) {
+                            self.config.dropped(&record, DropReason::Expired);
+                            continue;
+                        }
+                        Pending {
+                            record,
+                            offset: 0,
+                            retransmit: false,
+                            records: 1,
+                            parts: Vec::new(),
+                        }
+                    }
+                    Popped::Woken | Popped::TimedOut => continue,
+                    Popped::Closed => break,
+                },
+            };
+
+            match open.write(&pending.record.data) {
+                Ok(()) => {
+                    self.config
+                        .status
+                        .written(pending.records, pending.record.data.len());
+                    self.ring_doorbell();
+                    self.discard(&pending);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.config.status.blocked();
+                    match open.ready_fd() {
+                        Some(fd) => {
+                            registry::wait_ready(fd, libc::POLLOUT, self.config.timing.retry)
+                        }
+                        None => thread::sleep(self.config.timing.retry),
+                    }
+                    self.pending = Some(pending);
+                }
+                Err(e) => {
+                    log!("Sink -> {} Error {:?}", target, e);
+                    self.config.failed(&e);
+                    open.close();
+                    sink = None;
+                    self.pending = Some(pending);
                 }
             }
         }
 
-        Ok(())
+        if let Some(mut sink) = sink {
+            sink.close();
+            log!("Stopping write <> {}", &self.config);
+        }
     }
 
-    ///
-    ///
-    ///
-    ///
+    /// Poll the output pipe until it becomes writable and forward messages
     fn loop_till_stopped(&mut self, poll: &mut Poll, sender: &pipe::Sender) -> WriteFlow {
         let mut events = Events::with_capacity(8);
         loop {
@@ -470,11 +2197,19 @@ impl<'a> Writer {
             }
 
             // If the reader is'nt reading any data close the target pipe
-            if self.should_close_pipe() {
+            if self.should_close_pipe() || self.config.status.is_evicted() {
                 return WriteFlow::ClosePipe;
             }
 
-            match poll.poll(&mut events, Some(TIME_OUT)) {
+            if self.should_switch() {
+                return WriteFlow::Switch;
+            }
+
+            if self.pending.as_ref().is_none_or(|p| p.offset == 0) && self.should_fail_back() {
+                return WriteFlow::FailBack;
+            }
+
+            match poll.poll(&mut events, Some(self.config.timing.poll)) {
                 Ok(_) => {}
                 Err(_) => {
                     return WriteFlow::Restart;
@@ -484,7 +2219,11 @@ impl<'a> Writer {
             for event in &events {
                 if event.token() == PIPE_SEND && event.is_writable() {
                     let flow = self.loop_write_messages(event, sender);
-                    println!("Stopping write <> {}", &self.config);
+                    if let WriteFlow::Wait = flow {
+                        // Keep the pipe, the consumer has yet to make room
+                        continue;
+                    }
+                    log!("Stopping write <> {}", &self.config);
                     return flow;
                 }
             }
@@ -492,6 +2231,95 @@ impl<'a> Writer {
         WriteFlow::Break
     }
 
+    /// Record to write before taking a new one from the channel: the
+    /// unfinished one, or an unacknowledged one due for retransmission
+    fn next_pending(&mut self) -> Option<Pending> {
+        if let Some(pending) = self.pending.take() {
+            return Some(pending);
+        }
+        let data = self.ack.as_mut()?.next_retransmit()?;
+        self.config.status.retransmitted();
+        Some(Pending {
+            record: Record {
+                seq: 0,
+                data,
+                received: time::Instant::now(),
+            },
+            offset: 0,
+            retransmit: true,
+            records: 1,
+            parts: Vec::new(),
+        })
+    }
+
+    /// Give up on writing `pending`
+    fn discard(&self, pending: &Pending) {
+        if !pending.retransmit {
+            for _ in 0..pending.records {
+                self.config.status.settle(pending.record.seq);
+            }
+        }
+    }
+
+    /// Append records that still fit in a single `PIPE_BUF` write to
+    /// `pending`, so they reach the consumer in one atomic write
+    fn coalesce(&self, pending: &mut Pending) {
+        let data = &mut pending.record.data;
+        while data.len() < libc::PIPE_BUF {
+            let Some(record) = self
+                .config
+                .channel
+                .try_pop_fitting(libc::PIPE_BUF - data.len())
+            else {
+                break;
+            };
+            if self.is_expired(&record) {
+                self.config.dropped(&record, DropReason::Expired);
+                continue;
+            }
+            data.extend_from_slice(&record.data);
+            pending.record.seq = record.seq;
+            pending.records += 1;
+        }
+    }
+
+    /// Apply the output's `oversize` policy to a record larger than
+    /// `PIPE_BUF`, returning whether it is to be written
+    fn check_size(&self, pending: &mut Pending) -> bool {
+        let len = pending.record.data.len();
+        if len <= libc::PIPE_BUF {
+            return true;
+        }
+        let first = self.config.status.oversized();
+
+        match self.config.oversize {
+            Oversize::Warn => {
+                if first {
+                    log!(
+                        "Warning: record of {} bytes exceeds PIPE_BUF and may interleave <> {}",
+                        len,
+                        &self.config
+                    );
+                }
+                true
+            }
+            Oversize::Reject => false,
+            Oversize::Split => {
+                let (data, parts) = split_parts(&pending.record.data);
+                pending.record.data = data;
+                pending.parts = parts;
+                true
+            }
+        }
+    }
+
+    /// Whether `record` waited longer than the output's TTL
+    fn is_expired(&self, record: &Record) -> bool {
+        self.config
+            .ttl
+            .is_some_and(|ttl| record.received.elapsed() > ttl)
+    }
+
     /// Read messages from channel while sender is writable
     fn loop_write_messages(
         &mut self,
@@ -499,185 +2327,723 @@ impl<'a> Writer {
         sender: &pipe::Sender,
     ) -> WriteFlow {
         loop {
-            if event.is_write_closed() || self.should_stop() {
+            if self.should_stop() {
                 break;
             }
+            // Consumer went away, reopen and wait for the next one
+            if event.is_write_closed() {
+                return WriteFlow::Restart;
+            }
             // If the reader is'nt reading any data close the target pipe
-            if self.should_close_pipe() {
+            if self.should_close_pipe() || self.config.status.is_evicted() {
                 return WriteFlow::ClosePipe;
             }
 
-            match self.receiver.recv_timeout(TIME_OUT) {
-                Ok(m) => {
-                    if self.ignore_first_message {
-                        self.ignore_first_message = false;
-                        continue;
+            if let Some(ack) = self.ack.as_mut() {
+                if let Err(e) = ack.receive() {
+                    log!("Ack -> {} Error {:?}", &self.config, e);
+                    self.config.failed(&e);
+                }
+            }
+
+            let mut pending = match self.next_pending() {
+                Some(pending) => pending,
+                None if self.ack.as_ref().is_some_and(AckTracker::is_full) => {
+                    // Wait for the consumer to acknowledge before sending more
+                    thread::sleep(self.config.timing.retry);
+                    continue;
+                }
+                None => match self.wait_record() {
+                    Popped::Record(mut record) => {
+                        // Drop stale records rather than replaying a backlog
+                        if self.is_expired(&record) {
+                            self.config.dropped(&record, DropReason::Expired);
+                            continue;
+                        }
+                        if let Some(ack) = self.ack.as_mut() {
+                            record.data = ack.track(record.data);
+                        }
+                        let mut pending = Pending {
+                            record,
+                            offset: 0,
+                            retransmit: false,
+                            records: 1,
+                            parts: Vec::new(),
+                        };
+                        // Acknowledged records are tracked one by one
+                        if self.config.coalesce && self.ack.is_none() {
+                            self.coalesce(&mut pending);
+                        }
+                        if !self.check_size(&mut pending) {
+                            self.discard(&pending);
+                            continue;
+                        }
+                        pending
                     }
-                    let contents = m.as_bytes();
+                    // Check the signals again
+                    Popped::Woken | Popped::TimedOut => continue,
+                    // Reader has gone away
+                    Popped::Closed => return WriteFlow::Break,
+                },
+            };
+
+            // A record waited for across a boundary goes to the new path,
+            // one partly written is finished first
+            if pending.offset == 0 && self.should_switch() {
+                self.pending = Some(pending);
+                return WriteFlow::Switch;
+            }
+            if pending.offset == 0 && self.should_fail_back() {
+                self.pending = Some(pending);
+                return WriteFlow::FailBack;
+            }
 
-                    match self.write(contents, sender) {
-                        Err(e) => match e.kind() {
-                            io::ErrorKind::BrokenPipe => {
-                                self.ignore_first_message = true;
-                                return WriteFlow::Restart;
+            // Wait for the other inputs of a shared output to finish the
+            // record they are writing
+            if !self.take_turn() {
+                self.pending = Some(pending);
+                thread::sleep(TURN_WAIT);
+                continue;
+            }
+
+            let contents = pending.next_write();
+            match self.write(contents, sender) {
+                Ok(n) if pending.offset + n < pending.record.data.len() => {
+                    pending.offset += n;
+                    self.pending = Some(pending);
+                }
+                Ok(_) => {
+                    self.config
+                        .status
+                        .written(pending.records, pending.record.data.len());
+                    self.release_turn();
+                    self.ring_doorbell();
+                    self.discard(&pending);
+                }
+                Err(e) => match e.kind() {
+                    io::ErrorKind::BrokenPipe => {
+                        // Resend the whole record to the next consumer, along
+                        // with everything it did not acknowledge
+                        match self.ack.as_mut() {
+                            Some(ack) => {
+                                ack.rewind();
+                                self.discard(&pending);
                             }
-                            _others => {
-                                println!("{}", e)
+                            None => {
+                                pending.offset = 0;
+                                self.pending = Some(pending);
                             }
-                        },
-                        _ => {}
+                        }
+                        self.release_turn();
+                        return WriteFlow::Restart;
                     }
-                }
-                Err(e) => match e {
-                    mpsc::RecvTimeoutError::Timeout => {
-                        thread::sleep(TIME_OUT);
-                        continue;
+                    io::ErrorKind::WouldBlock => {
+                        // Consumer is not keeping up with the data
+                        self.config.status.blocked();
+                        self.pending = Some(pending);
+                        return WriteFlow::Wait;
                     }
-                    mpsc::RecvTimeoutError::Disconnected => {
-                        // Sending End has disconnected
-                        return WriteFlow::Break;
+                    _others => {
+                        log!("{}", e);
+                        self.config.failed(&e);
+                        self.release_turn();
+                        self.discard(&pending);
                     }
                 },
-            };
+            }
         }
 
         WriteFlow::Break
     }
 
-    ///
-    ///
-    ///
-    fn new(
-        signal: Arc<Mutex<u8>>,
-        config: Arc<SplitOut>,
-        receiver: mpsc::Receiver<String>,
-    ) -> Writer {
+    /// Take the turn of a shared output before writing, false while the
+    /// writer of another input is in the middle of a record
+    fn take_turn(&self) -> bool {
+        match &self.config.turn {
+            Some(turn) => turn.take(self.id),
+            None => true,
+        }
+    }
+
+    /// Let the writers of the other inputs of a shared output write again
+    fn release_turn(&self) {
+        if let Some(turn) = &self.config.turn {
+            turn.release(self.id);
+        }
+    }
+
+    /// Tell the consumer records were written, when the output has a doorbell
+    fn ring_doorbell(&mut self) {
+        let Some(doorbell) = self.doorbell.as_mut() else {
+            return;
+        };
+        if let Err(e) = doorbell.ring() {
+            log!("Notify -> {} Error {:?}", &self.config, e);
+            self.config.failed(&e);
+        }
+    }
+
+    /// Next record of the queue, the writer is idle while it waits
+    fn wait_record(&self) -> Popped {
+        let timeout = self.wait_timeout();
+        if timeout.is_none() {
+            self.config.worker.idle();
+        }
+        let popped = self.config.channel.pop_wait(timeout);
+        self.config.worker.beat();
+        popped
+    }
+
+    /// Longest wait for a record, writers block until woken unless they have
+    /// acknowledgements to read and retransmits to make
+    fn wait_timeout(&self) -> Option<time::Duration> {
+        self.ack.as_ref().map(|_| self.config.timing.poll)
+    }
+
+    /// Writer for the output `config`, fed by its queue
+    fn new(signal: Arc<Mutex<u8>>, config: Arc<SplitOut>) -> Writer {
         Writer {
-            ignore_first_message: false,
+            generation: config.worker.start(),
+            pending: None,
+            last_probe: time::Instant::now(),
+            ack: config
+                .ack
+                .as_ref()
+                .map(|ack| AckTracker::new(ack.into(), config.fifo.clone(), config.ack_timeout)),
+            doorbell: config
+                .notify
+                .as_ref()
+                .map(|notify| Doorbell::new(notify.into(), config.fifo.clone())),
+            retained: config.buffer_until_reader,
+            waiting_fd: false,
+            id: shared::writer_id(),
+            opening: None,
+            on_standby: false,
+            primary: None,
+            standby: None,
+            last_fail_back: time::Instant::now(),
+            path: config.pipe.clone(),
             signal,
             config,
-            receiver,
+            drain_deadline: None,
+        }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        self.release_turn();
+        // A writer restarted after a panic keeps feeding from the queue, as
+        // does the one replacing a wedged writer
+        if !thread::panicking() && self.config.worker.is_current(self.generation) {
+            self.config.channel.close()
         }
     }
 }
 
 struct MessageSender {
-    /// if the sender has been dropped
+    /// if the writer has gone away
     disconnected: bool,
-    /// send channel
-    sender: mpsc::SyncSender<String>,
+    /// output fed by the channel
+    output: Arc<SplitOut>,
 }
 
-///
+impl MessageSender {
+    /// Queue `record`, dropping it when the queue is full
+    fn push(&mut self, record: Record) {
+        if let Some(transcript) = &self.output.transcript {
+            transcript.record(&record.data);
+        }
+        self.output.status.reserve();
+        match self.output.channel.try_push(record) {
+            Ok(dropped) => {
+                self.output.status.queued();
+                for record in dropped {
+                    self.output.dropped(&record, DropReason::Overflow);
+                }
+            }
+            Err(PushError::Full(record)) => self.output.dropped(&record, DropReason::QueueFull),
+            Err(PushError::Closed) => {
+                self.output.status.release();
+                self.disconnected = true;
+            }
+        }
+    }
+}
+
+/// What becomes of a record for one output
+enum Delivery {
+    /// Not for the output: disconnected, evicted or not its turn
+    Skip,
+    /// Dropped by a filter of the output
+    Filtered,
+    /// Record to queue, transformed for the output
+    Queue(Record),
+}
+
+/// Thread of a reader or writer
+type WorkerThread = thread::JoinHandle<Result<(), std::io::Error>>;
+
+/// Input worker, reads an input FIFO and fans records out to writers
 struct Reader {
     signal: Arc<Mutex<u8>>,
     config: Arc<SplitIn>,
     send_channels: Vec<MessageSender>,
     write_signal: Arc<Mutex<u8>>,
+    /// Sequence number of the next record
+    next_seq: u64,
+    /// Write-ahead log, when enabled on the input
+    wal: Option<Wal>,
+    /// Last time output positions were written to the write-ahead log
+    last_checkpoint: time::Instant,
+    /// Start of a record not completely read yet, for varint and chunk framing
+    partial: Vec<u8>,
+    /// Reads into pooled buffers, for varint and chunk framing of byte inputs
+    vectored: Option<VectoredReader>,
+    /// Writer threads of the outputs
+    writers: Vec<(Arc<SplitOut>, WorkerThread)>,
+    /// Generation of the reader, it stops once replaced by the watchdog
+    generation: u64,
+    /// Output taking the current chunk, when the outputs take turns
+    rotation: Option<Rotation>,
 }
 
 impl Drop for Reader {
     fn drop(&mut self) {
-        self.stop_writers()
+        self.stop_writers();
+        // The queues of a wedged reader feed the writers of its replacement
+        if self.config.worker.is_current(self.generation) {
+            for c in self.send_channels.iter() {
+                c.output.channel.close();
+            }
+        }
+        // Writers flush their queues within the drain timeout
+        for (_, handle) in self.writers.drain(..) {
+            let _ = handle.join();
+        }
     }
 }
 
-///
 impl<'a> Reader {
-    ///
-    fn should_stop(&self) -> bool {
+    /// Exit was requested
+    fn should_stop(&mut self) -> bool {
+        self.config.worker.beat();
+        if !self.config.worker.is_current(self.generation) {
+            return true;
+        }
+        self.respawn_writers();
         let state = self.signal.lock().unwrap();
         *state == SIG_EXIT
     }
-    ///
+    /// Start a new writer for every output whose writer was found wedged,
+    /// leaving the wedged thread to exit on its own
+    fn respawn_writers(&mut self) {
+        for i in 0..self.writers.len() {
+            let output = Arc::clone(&self.writers[i].0);
+            if output.worker.take_respawn() {
+                self.writers[i].1 = self.spawn_writer(&output);
+            }
+        }
+    }
+    /// Ask all writers to exit
     fn stop_writers(&mut self) {
         // Signal exit
         let mut num = self.write_signal.lock().unwrap();
         *num = SIG_EXIT;
+        self.wake_writers();
+    }
+    /// Interrupt writers waiting for records, so they see a new signal
+    fn wake_writers(&self) {
+        for c in self.send_channels.iter() {
+            c.output.channel.wake();
+        }
     }
-    ///
+    /// Ask all writers to close their pipes
     fn close_writing_pipes(&mut self) {
         let mut num = self.write_signal.lock().unwrap();
         *num = SIG_CLOSE;
+
+        for c in self.send_channels.iter() {
+            c.output.status.idle();
+        }
+        self.wake_writers();
     }
-    ///
+    /// Ask all writers to open their pipes
     fn open_writing_pipes(&mut self) {
         let mut num = self.write_signal.lock().unwrap();
         *num = SIG_RUN;
     }
-    ///
-    fn send_message(&mut self, m: String) {
-        for c in self.send_channels.iter_mut() {
-            if c.disconnected {
+    /// Queue a record on every connected writer, or on the one whose turn it
+    /// is when outputs take turns, dropping it when a queue is full. Outputs
+    /// of a delivery group all get the record or all drop it.
+    fn send_message(&mut self, m: Record) {
+        let channels = &self.send_channels;
+        let turn = self.rotation.as_mut().map(|rotation| {
+            rotation.select(m.data.len(), channels.len(), |index| {
+                !channels[index].disconnected && !channels[index].output.status.is_evicted()
+            })
+        });
+
+        // Transform for every output first, a group is settled as a whole
+        let mut deliveries = Vec::with_capacity(self.send_channels.len());
+        for (index, c) in self.send_channels.iter().enumerate() {
+            let delivery = if c.disconnected {
+                Delivery::Skip
+            } else if c.output.status.is_evicted() {
+                c.output.dropped(&m, DropReason::Evicted);
+                Delivery::Skip
+            } else if turn.is_some_and(|turn| turn != Some(index)) {
+                c.output.status.skipped(m.seq);
+                Delivery::Skip
+            } else {
+                match c.output.transform(&m) {
+                    Some(record) => Delivery::Queue(record),
+                    None => Delivery::Filtered,
+                }
+            };
+            deliveries.push(delivery);
+        }
+
+        let mut groups: Vec<String> = Vec::new();
+        for (c, delivery) in self.send_channels.iter_mut().zip(deliveries.iter_mut()) {
+            if let Some(group) = &c.output.group {
+                if !groups.contains(group) {
+                    groups.push(group.clone());
+                }
                 continue;
             }
-            match c.sender.try_send(m.clone()) {
-                Err(e) => match e {
-                    mpsc::TrySendError::Disconnected(_) => {
-                        c.disconnected = true;
-                    }
-                    _ => {}
-                },
-                _ => {}
+            match std::mem::replace(delivery, Delivery::Skip) {
+                Delivery::Skip => {}
+                Delivery::Filtered => c.output.status.filtered(m.seq),
+                Delivery::Queue(record) => c.push(record),
             }
         }
+        for group in groups {
+            self.send_group(&group, m.seq, &mut deliveries);
+        }
     }
 
-    ///
-    fn new(signal: Arc<Mutex<u8>>, config: Arc<SplitIn>) -> Reader {
-        let cap = config.outputs.len();
+    /// Queue the records of the outputs of delivery group `group` on all of
+    /// them or none. A record filtered out by one output is filtered out for
+    /// the group, one a queue has no room for is dropped by every output.
+    /// Outputs evicted or disconnected are left out of the group.
+    fn send_group(&mut self, group: &str, seq: u64, deliveries: &mut [Delivery]) {
+        let mut filtered = Vec::new();
+        let mut members = Vec::new();
+        for (index, c) in self.send_channels.iter().enumerate() {
+            if c.output.group.as_deref() != Some(group) {
+                continue;
+            }
+            match std::mem::replace(&mut deliveries[index], Delivery::Skip) {
+                Delivery::Skip => {}
+                Delivery::Filtered => filtered.push(index),
+                Delivery::Queue(record) => members.push((index, record)),
+            }
+        }
+        if !filtered.is_empty() {
+            filtered.extend(members.iter().map(|(index, _)| *index));
+            for index in filtered {
+                self.send_channels[index].output.status.filtered(seq);
+            }
+            return;
+        }
 
-        Reader {
-            signal,
-            config,
-            write_signal: Arc::new(Mutex::new(SIG_CLOSE)),
-            send_channels: Vec::with_capacity(cap),
+        // Queues stay locked until the record is queued on all of them, so a
+        // writer cannot close its queue in between
+        let indexes: Vec<usize> = members.iter().map(|(index, _)| *index).collect();
+        let outputs: Vec<Arc<SplitOut>> = indexes
+            .iter()
+            .map(|index| Arc::clone(&self.send_channels[*index].output))
+            .collect();
+        let mut pushers: Vec<_> = outputs.iter().map(|o| o.channel.lock()).collect();
+        let mut closed = Vec::new();
+        let mut accepted = true;
+        for (i, (pusher, (_, record))) in pushers.iter_mut().zip(members.iter()).enumerate() {
+            if pusher.is_closed() {
+                closed.push(i);
+            } else if !pusher.accepts(record) {
+                accepted = false;
+            }
+        }
+        for (i, (pusher, (_, record))) in pushers.iter_mut().zip(members).enumerate() {
+            if closed.contains(&i) {
+                continue;
+            }
+            let output = &outputs[i];
+            if let Some(transcript) = &output.transcript {
+                transcript.record(&record.data);
+            }
+            output.status.reserve();
+            if !accepted {
+                output.dropped(&record, DropReason::QueueFull);
+                continue;
+            }
+            match pusher.push(record) {
+                Ok(dropped) => {
+                    output.status.queued();
+                    for record in dropped {
+                        output.dropped(&record, DropReason::Overflow);
+                    }
+                }
+                Err(PushError::Full(record)) => output.dropped(&record, DropReason::QueueFull),
+                Err(PushError::Closed) => output.status.release(),
+            }
+        }
+        drop(pushers);
+
+        for i in closed {
+            log!(
+                "Group -> {} left by disconnected output <> {}",
+                group,
+                outputs[i]
+            );
+            self.send_channels[indexes[i]].disconnected = true;
         }
     }
 
-    ///
-    fn open_pipe(&mut self) -> Result<File, std::io::Error> {
-        let f = OpenOptions::new()
-            .read(true)
-            .custom_flags(libc::O_NONBLOCK)
-            .open(Path::new(&self.config.pipe));
+    /// Number the record, log it when the write-ahead log is enabled and
+    /// queue it on the writers
+    fn dispatch(&mut self, data: Vec<u8>) {
+        if let Some(wal) = self.wal.as_mut() {
+            match wal.append(&data) {
+                Ok(seq) => self.next_seq = seq,
+                Err(e) => {
+                    log!("WAL -> {} Error {:?}", self.config.name(), e);
+                    self.config.failed(&e);
+                }
+            }
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.config.status.numbered(self.next_seq);
+        tap::mirror(self.config.name(), &data);
+        if let Some(transcript) = &self.config.transcript {
+            transcript.record(&data);
+        }
 
-        f
+        self.send_message(Record {
+            seq,
+            data,
+            received: time::Instant::now(),
+        });
+        self.checkpoint(false);
     }
 
-    /// Create output workers defined in the
-    fn start_write_channels(&'a mut self) -> &'a mut Self {
+    /// Save output positions to the write-ahead log, at most once every
+    /// checkpoint interval unless `force`d
+    fn checkpoint(&mut self, force: bool) {
+        let Some(wal) = self.wal.as_mut() else {
+            return;
+        };
+        if !force && self.last_checkpoint.elapsed() < self.config.timing.checkpoint {
+            return;
+        }
+        self.last_checkpoint = time::Instant::now();
+
+        let positions: Vec<(&str, u64)> = self
+            .send_channels
+            .iter()
+            .map(|c| (c.output.pipe.as_str(), c.output.status.settled()))
+            .collect();
+        if let Err(e) = wal.checkpoint(&positions) {
+            log!("WAL -> {} Error {:?}", self.config.name(), e);
+            self.config.failed(&e);
+        }
+    }
+
+    /// Open the write-ahead log and queue the records not settled by every
+    /// output in the previous run. Replayed records wait for room in the
+    /// queues rather than being dropped.
+    fn replay(&mut self) -> Result<(), std::io::Error> {
+        let Some(path) = self.config.wal.as_ref() else {
+            return Ok(());
+        };
+        let wal = Wal::open(path)?;
+        self.next_seq = self.next_seq.max(wal.next_seq());
+
+        // Positions restored from the state file may be more recent
+        let positions: Vec<u64> = self
+            .send_channels
+            .iter()
+            .map(|c| wal.position(&c.output.pipe).max(c.output.status.settled()))
+            .collect();
+        for (c, position) in self.send_channels.iter().zip(positions.iter()) {
+            c.output.status.restore(*position);
+        }
+
+        let after = positions.iter().copied().min().unwrap_or(0);
+        let records = wal.replay(after)?;
+        self.wal = Some(wal);
+
+        if records.is_empty() {
+            return Ok(());
+        }
+        log!("Replaying {} records <- {}", records.len(), &self.config);
+        self.open_writing_pipes();
+
+        for (seq, data) in records {
+            let record = Record {
+                seq,
+                data,
+                received: time::Instant::now(),
+            };
+            for (c, position) in self.send_channels.iter_mut().zip(positions.iter()) {
+                if seq <= *position {
+                    continue;
+                }
+                let Some(record) = c.output.transform(&record) else {
+                    c.output.status.filtered(seq);
+                    continue;
+                };
+                loop {
+                    if self.signal.lock().map(|s| *s == SIG_EXIT).unwrap_or(true) {
+                        return Ok(());
+                    }
+                    self.config.worker.beat();
+                    if c.disconnected {
+                        c.output.status.skipped(seq);
+                        break;
+                    }
+                    if c.output.status.is_evicted() {
+                        c.output.dropped(&record, DropReason::Evicted);
+                        break;
+                    }
+                    c.output.status.reserve();
+                    match c.output.channel.try_push(record.clone()) {
+                        Ok(dropped) => {
+                            c.output.status.queued();
+                            for record in dropped {
+                                c.output.dropped(&record, DropReason::Overflow);
+                            }
+                            break;
+                        }
+                        Err(PushError::Full(_)) => {
+                            c.output.status.release();
+                            thread::sleep(self.config.timing.retry);
+                        }
+                        Err(PushError::Closed) => {
+                            c.output.status.release();
+                            c.disconnected = true;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reader for the input `config`, stopped through `signal`
+    fn new(signal: Arc<Mutex<u8>>, config: Arc<SplitIn>) -> Reader {
+        let cap = config.outputs.len();
+
+        Reader {
+            generation: config.worker.start(),
+            signal,
+            write_signal: Arc::new(Mutex::new(SIG_CLOSE)),
+            send_channels: Vec::with_capacity(cap),
+            next_seq: config.status.next_seq(),
+            wal: None,
+            last_checkpoint: time::Instant::now(),
+            partial: Vec::new(),
+            vectored: None,
+            writers: Vec::new(),
+            rotation: config.rotate.map(Rotation::new),
+            config,
+        }
+    }
+
+    /// Open the input FIFO for non-blocking reads
+    fn open_pipe(&mut self) -> Result<File, std::io::Error> {
+        let f = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(Path::new(&self.config.pipe));
+
+        f
+    }
+
+    /// Create output workers defined in the
+    fn start_write_channels(&'a mut self) -> &'a mut Self {
         self.send_channels.clear();
 
         for out in self.config.outputs.iter() {
             if !out.configuration.enabled {
                 continue;
             }
-            let signal = Arc::clone(&self.write_signal);
-            let config = Arc::clone(out);
-
-            let (sender, receiver) = mpsc::sync_channel(1);
+            // Queues of a reader restarted after a panic were closed
+            out.channel.reopen();
 
             self.send_channels.push(MessageSender {
                 disconnected: false,
-                sender,
+                output: Arc::clone(out),
             });
 
-            thread::spawn(move || -> Result<(), std::io::Error> {
-                let mut witter = Writer::new(signal, config, receiver);
-                witter.run_loop()
-            });
+            let handle = self.spawn_writer(out);
+            self.writers.push((Arc::clone(out), handle));
         }
         self
     }
 
-    ///
+    /// Start the writer thread of `output`
+    fn spawn_writer(&self, output: &Arc<SplitOut>) -> WorkerThread {
+        let signal = Arc::clone(&self.write_signal);
+        let config = Arc::clone(output);
+        thread::spawn(move || -> Result<(), std::io::Error> {
+            let mut generation = 0;
+            let mut started = false;
+            config.lifecycle.started(&config.pipe, config.name());
+            let result = restart::supervise(
+                &config,
+                config.restart,
+                config.timing.retry,
+                |message| config.failed(&message),
+                || {
+                    if std::mem::replace(&mut started, true) {
+                        config.restarted(RestartReason::Panic);
+                    }
+                    let mut writer = Writer::new(Arc::clone(&signal), Arc::clone(&config));
+                    generation = writer.generation;
+                    writer.run_loop()
+                },
+            );
+            // A writer replaced by the watchdog no longer speaks for the output
+            if config.worker.is_current(generation) {
+                config.channel.close();
+                config
+                    .lifecycle
+                    .stopped(&config.pipe, config.name(), result.is_ok());
+            }
+            result
+        })
+    }
+
+    /// Open the input pipe and read until exit is requested
     fn run(&mut self) -> Result<(), std::io::Error> {
-        let pipe = match self.open_pipe() {
-            Ok(f) => f,
-            Err(e) => {
-                println!("File -> {} Error {:?} ", &self.config.pipe, e);
-                return Err(e);
+        if let Some(target) = self.config.source.clone() {
+            return self.run_source(&target);
+        }
+        let mut waiting_fd = false;
+        let pipe = loop {
+            match self.open_pipe() {
+                Ok(f) => break f,
+                Err(e) if fds::is_exhausted(&e) => {
+                    if !waiting_fd {
+                        log!(
+                            "Warning: out of file descriptors, delaying open <> {}",
+                            &self.config
+                        );
+                        waiting_fd = true;
+                    }
+                    if self.should_stop() {
+                        return Ok(());
+                    }
+                    thread::sleep(self.config.timing.retry);
+                }
+                Err(e) => {
+                    log!("File -> {} Error {:?} ", &self.config.pipe, e);
+                    self.config.failed(&e);
+                    return Err(e);
+                }
             }
         };
         let mut poll = Poll::new()?;
@@ -685,45 +3051,155 @@ impl<'a> Reader {
             let fd = pipe.into_raw_fd();
             pipe::Receiver::from_raw_fd(fd)
         };
-        let mut reader = unsafe {
-            let fd = receiver.as_raw_fd();
-            std::io::BufReader::new(File::from_raw_fd(fd))
-        };
 
         poll.registry()
             .register(&mut receiver, PIPE_RECV, Interest::READABLE)?;
 
-        println!("Reading data <- {}", &self.config);
+        if let Err(e) = self.replay() {
+            log!("WAL -> {} Error {:?} ", self.config.name(), e);
+            self.config.failed(&e);
+            return Err(e);
+        }
+
+        log!("Reading data <- {}", &self.config);
+
+        // Reads borrow `receiver`, which owns and closes the descriptor even
+        // when the reader unwinds
+        let mut reader = BufReader::with_capacity(self.config.read_buffer, &receiver);
+        let framed = matches!(self.config.framing, Framing::Varint | Framing::Chunk(_));
+        self.vectored = match self.config.configuration.mode {
+            Some(OperationMode::BytesRead) if framed => Some(VectoredReader::for_pipe(
+                receiver.as_raw_fd(),
+                self.config.read_chunk,
+            )),
+            _ => None,
+        };
+        self.loop_till_stopped(&mut poll, &mut reader)
+    }
+
+    /// Forward the records of the input's custom source until exit is
+    /// requested, reopening it after the stream ended or failed
+    fn run_source(&mut self, target: &SourceTarget) -> Result<(), std::io::Error> {
+        if let Err(e) = self.replay() {
+            log!("WAL -> {} Error {:?} ", self.config.name(), e);
+            self.config.failed(&e);
+            return Err(e);
+        }
+
+        let mut source: Option<Box<dyn Source>> = None;
+        // Report a source that cannot be opened once, not on every retry
+        let mut reported = false;
+        loop {
+            if self.should_stop() {
+                self.stop_writers();
+                self.checkpoint(true);
+                break;
+            }
+
+            let Some(open) = source.as_mut() else {
+                match target.open_source() {
+                    Ok(opened) => {
+                        log!("Reading data <- {}", &self.config);
+                        self.open_writing_pipes();
+                        source = Some(opened);
+                        reported = false;
+                    }
+                    Err(e) => {
+                        if !reported {
+                            log!("Source -> {} Error {:?}", target, e);
+                            self.config.failed(&e);
+                            reported = true;
+                        }
+                        thread::sleep(self.config.timing.retry);
+                    }
+                }
+                continue;
+            };
+
+            match open.read() {
+                Ok(Some(data)) => self.accept(data),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.checkpoint(false);
+                    match open.ready_fd() {
+                        Some(fd) => registry::wait_ready(fd, libc::POLLIN, self.config.timing.poll),
+                        None => thread::sleep(self.config.timing.poll),
+                    }
+                }
+                result => {
+                    if let Err(e) = result {
+                        log!("Source -> {} Error {:?}", target, e);
+                        self.config.failed(&e);
+                    }
+                    open.close();
+                    source = None;
+                    log!("Stopping read <> {}", &self.config);
+                    self.close_writing_pipes();
+                }
+            }
+        }
+
+        if let Some(mut source) = source {
+            source.close();
+        }
+        Ok(())
+    }
+
+    /// Dispatch a record read from the input, decoding it first when the
+    /// input carries base64 lines
+    fn accept(&mut self, data: Vec<u8>) {
+        self.config.status.read(data.len());
+        self.throttle(data.len());
+        if !self.config.base64 {
+            self.dispatch(data);
+        } else if let Some(data) = transform::decode_base64(&data) {
+            self.dispatch(data);
+        } else {
+            log!(
+                "Warning: dropping record that is not base64 <> {}",
+                &self.config
+            );
+        }
+    }
 
-        match self.loop_till_stopped(&mut poll, &mut reader) {
-            Ok(_) => return Ok(()),
-            Err(err) => {
-                return Err(err);
+    /// Hold the reader back while the inputs read more than
+    /// `[DEFAULT] max_throughput` allows, `len` bytes were just read
+    fn throttle(&mut self, len: usize) {
+        let Some(throttle) = &self.config.throttle else {
+            return;
+        };
+        let until = time::Instant::now() + throttle.take(len);
+        loop {
+            let now = time::Instant::now();
+            if now >= until || self.should_stop() {
+                break;
             }
+            thread::sleep((until - now).min(self.config.timing.poll));
         }
     }
 
-    ///
+    /// Poll the input pipe and forward data while readable
     fn loop_till_stopped(
         &mut self,
         poll: &mut Poll,
-        reader: &mut BufReader<File>,
+        reader: &mut BufReader<&pipe::Receiver>,
     ) -> Result<(), std::io::Error> {
         let mut events = Events::with_capacity(8);
 
         loop {
             if self.should_stop() {
                 self.stop_writers();
+                self.checkpoint(true);
                 break;
             }
 
-            poll.poll(&mut events, Some(TIME_OUT))?;
+            poll.poll(&mut events, Some(self.config.timing.poll))?;
+            self.checkpoint(false);
 
             for event in &events {
                 if event.token() == PIPE_RECV && event.is_readable() {
                     self.open_writing_pipes();
                     self.loop_read_pipe(event, reader);
-                    println!("Stopping read <> {}", &self.config);
+                    log!("Stopping read <> {}", &self.config);
                     self.close_writing_pipes();
                 }
             }
@@ -732,32 +3208,130 @@ impl<'a> Reader {
         Ok(())
     }
 
-    ///
-    fn loop_read_pipe(&mut self, event: &mio::event::Event, reader: &mut BufReader<File>) {
+    /// Next record of the pipe, delimited as configured, `None` once closed
+    fn read_record(
+        &mut self,
+        reader: &mut BufReader<&pipe::Receiver>,
+    ) -> io::Result<Option<Vec<u8>>> {
+        match self.config.framing {
+            Framing::Line => {
+                let mut buffer = String::new();
+                match std::io::BufRead::read_line(reader, &mut buffer)? {
+                    0 => Ok(None),
+                    _ => Ok(Some(buffer.into_bytes())),
+                }
+            }
+            Framing::Packet => {
+                // Bypass the buffer, a single read returns a single packet
+                let mut buffer = vec![0u8; self.config.read_chunk];
+                match reader.get_mut().read(&mut buffer)? {
+                    0 => Ok(None),
+                    n => {
+                        buffer.truncate(n);
+                        Ok(Some(buffer))
+                    }
+                }
+            }
+            Framing::Varint => loop {
+                // Keep what was read of the message when the pipe runs dry
+                match varint::message_len(&self.partial) {
+                    Ok(Some(len)) => {
+                        let rest = self.partial.split_off(len);
+                        return Ok(Some(std::mem::replace(&mut self.partial, rest)));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.partial.clear();
+                        return Err(e);
+                    }
+                }
+                let available = Self::fill_buf(&mut self.vectored, reader)?;
+                if available.is_empty() {
+                    if !self.partial.is_empty() {
+                        log!(
+                            "Warning: dropping truncated message <> {}",
+                            self.config.name()
+                        );
+                        self.partial.clear();
+                    }
+                    return Ok(None);
+                }
+                let n = available.len();
+                self.partial.extend_from_slice(available);
+                Self::consume(&mut self.vectored, reader, n);
+            },
+            Framing::Chunk(size) => loop {
+                // Keep what was read of the chunk when the pipe runs dry
+                if self.partial.len() == size {
+                    return Ok(Some(std::mem::take(&mut self.partial)));
+                }
+                let available = Self::fill_buf(&mut self.vectored, reader)?;
+                if available.is_empty() {
+                    // The end of the stream ends its last chunk
+                    return match self.partial.is_empty() {
+                        true => Ok(None),
+                        false => Ok(Some(std::mem::take(&mut self.partial))),
+                    };
+                }
+                let n = available.len().min(size - self.partial.len());
+                self.partial.extend_from_slice(&available[..n]);
+                Self::consume(&mut self.vectored, reader, n);
+            },
+        }
+    }
+
+    /// Bytes read and not yet framed, through the pooled buffers of
+    /// `vectored` when the input has them
+    fn fill_buf<'b>(
+        vectored: &'b mut Option<VectoredReader>,
+        reader: &'b mut BufReader<&pipe::Receiver>,
+    ) -> io::Result<&'b [u8]> {
+        match vectored {
+            Some(vectored) => vectored.fill_buf(reader.get_ref().as_raw_fd()),
+            None => std::io::BufRead::fill_buf(reader),
+        }
+    }
+
+    /// Mark `n` bytes returned by [`Reader::fill_buf`] as framed
+    fn consume(
+        vectored: &mut Option<VectoredReader>,
+        reader: &mut BufReader<&pipe::Receiver>,
+        n: usize,
+    ) {
+        match vectored {
+            Some(vectored) => vectored.consume(n),
+            None => std::io::BufRead::consume(reader, n),
+        }
+    }
+
+    /// Read records from the pipe until it is drained or closed
+    fn loop_read_pipe(
+        &mut self,
+        event: &mio::event::Event,
+        reader: &mut BufReader<&pipe::Receiver>,
+    ) {
         loop {
             if event.is_read_closed() {
                 break;
             }
 
-            let mut buffer = String::new();
-
-            match std::io::BufRead::read_line(reader, &mut buffer) {
-                Ok(bytes_read) => {
-                    if bytes_read == 0 {
-                        break;
-                    }
-                    self.send_message(buffer);
-                }
+            match self.read_record(reader) {
+                Ok(None) => break,
+                Ok(Some(data)) => self.accept(data),
                 Err(err) => match err.kind() {
                     io::ErrorKind::BrokenPipe => {
-                        println!("{:?}", err)
+                        log!("{:?}", err)
                     }
                     io::ErrorKind::WouldBlock => {
-                        // Pipe has no data to be read
-                        thread::sleep(TIME_OUT);
+                        // Pipe has no data to be read, its writer may stay
+                        // connected without sending anything
+                        if self.should_stop() {
+                            break;
+                        }
+                        thread::sleep(self.config.timing.retry);
                     }
                     _ => {
-                        println!("{:?}", err)
+                        log!("{:?}", err)
                     }
                 },
             };
@@ -766,155 +3340,2481 @@ impl<'a> Reader {
 }
 
 fn create_splitting_threads(
-    entries: &Vec<Arc<SplitIn>>,
+    entries: &[Arc<SplitIn>],
     signal: &Arc<Mutex<u8>>,
 ) -> Vec<thread::JoinHandle<Result<(), std::io::Error>>> {
     let mut reading_threads = Vec::with_capacity(entries.len());
 
     for input in entries.iter() {
-        if !input.configuration.enabled || input.enabled_outputs() == 0 {
+        if !input.runs() {
             continue;
         }
+        reading_threads.push(spawn_reader(signal, input));
+    }
 
-        let signal = Arc::clone(&signal);
-        let config = Arc::clone(input);
+    reading_threads
+}
 
-        let handle = thread::spawn(move || -> Result<(), std::io::Error> {
-            let mut reader = Reader::new(signal, config);
-            reader.start_write_channels().run()
-        });
+/// Start the reader thread of `input`, stopped through `signal`
+fn spawn_reader(signal: &Arc<Mutex<u8>>, input: &Arc<SplitIn>) -> WorkerThread {
+    let signal = Arc::clone(signal);
+    let config = Arc::clone(input);
+    thread::spawn(move || -> Result<(), std::io::Error> {
+        let mut generation = 0;
+        let mut started = false;
+        config.lifecycle.started(&config.pipe, config.name());
+        let result = restart::supervise(
+            &config,
+            config.restart,
+            config.timing.retry,
+            |message| config.failed(&message),
+            || {
+                if std::mem::replace(&mut started, true) {
+                    config.restarted(RestartReason::Panic);
+                }
+                let mut reader = Reader::new(Arc::clone(&signal), Arc::clone(&config));
+                generation = reader.generation;
+                reader.start_write_channels().run()
+            },
+        );
+        if config.worker.is_current(generation) {
+            config
+                .lifecycle
+                .stopped(&config.pipe, config.name(), result.is_ok());
+        }
+        result
+    })
+}
 
-        reading_threads.push(handle);
-    }
-    
-    reading_threads
+/// Write log messages to the file at `path` instead of standard output,
+/// rotating it as described by `rotation`
+pub fn log_to_file<P: AsRef<Path>>(path: P, rotation: LogRotation) -> Result<(), std::io::Error> {
+    console::set_log_file(logfile::LogFile::open(path, rotation)?);
+    Ok(())
 }
 
-///
-pub fn split_pipes<P: AsRef<Path>>(config_path: P) -> Result<(), std::io::Error> {
-    let entries = match Parser::load_from_file(config_path) {
-        Ok(r) => r,
-        Err(e) => panic!("{}", e),
-    };
+/// Mirror every record read from now on over UDP to `addr`, `host:port`,
+/// or stop mirroring when `None`, for live debugging
+pub fn set_tap(addr: Option<&str>) -> Result<(), std::io::Error> {
+    tap::set(addr)
+}
 
-    if entries.len() == 0 {
-        return Ok(());
-    }
+/// Print log messages to standard error rather than standard output, with
+/// colours when it is a terminal
+pub fn log_to_stderr() {
+    console::use_stderr();
+}
 
-    let signal = Arc::new(Mutex::new(SIG_RUN));
-    let _splitting_threads = create_splitting_threads(&entries, &signal);
+/// Point standard input at `/dev/null` and standard output and error at the
+/// log file set by [`log_to_file`], following it as it is rotated or
+/// reopened, for a splitter detached from any terminal
+pub fn redirect_stdio() -> Result<(), std::io::Error> {
+    console::redirect_stdio()
+}
 
-    loop {
-        thread::sleep(TIME_OUT);
-    }
+/// Forbid the process and the hooks it runs from gaining privileges, through
+/// set-user-ID binaries or file capabilities
+pub fn no_new_privs() -> Result<(), std::io::Error> {
+    caps::no_new_privs()
+}
+
+/// Names of the pipes configured in the file at `config_path` and the paths
+/// of their FIFOs, which carry the instance suffix of an ephemeral setup
+pub fn pipe_map<P: AsRef<Path>>(config_path: P) -> Result<Vec<(String, String)>, std::io::Error> {
+    Parser::load_ini_configuration(config_path)
+        .and_then(|conf| Parser::pipe_map(&conf))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Graph in `format` of the topology `name` of the configuration file at
+/// `config_path`, or of every topology when `None`
+pub fn topology_graph<P: AsRef<Path>>(
+    config_path: P,
+    name: Option<&str>,
+    format: GraphFormat,
+) -> Result<String, std::io::Error> {
+    Parser::load_topology(config_path, name)
+        .map(|topology| graph::render(&topology.inputs, format))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
 }
+
+/// Rewrite the configuration at `config_path` in the current schema
+/// version, returning what the applied migrations changed, nothing when the
+/// file was already current.
 ///
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::env::temp_dir;
-    use std::fs::File;
-    use std::io::Write;
+/// Comments and the layout of the file are not kept, the original is copied
+/// to `<config_path>.bak` first. The new file keeps the mode and owner of the
+/// original but is a new inode, so the migration fails while a splitter
+/// holds the lock of the file, which would stay on the original.
+pub fn migrate_config<P: AsRef<Path>>(config_path: P) -> Result<Vec<&'static str>, io::Error> {
+    let config_path = config_path.as_ref();
+    let invalid = |e: ParseError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+    let conf = Ini::load_from_file(config_path).map_err(|e| invalid(ParseError::Ini(e)))?;
+    let (conf, applied) = migrate::upgrade(conf).map_err(invalid)?;
+    if !applied.is_empty() {
+        let _lock = lock::acquire(config_path, None, None)?;
+        let mut contents = Vec::new();
+        conf.write_to(&mut contents)?;
+        let mut backup = config_path.as_os_str().to_owned();
+        backup.push(".bak");
+        fs::copy(config_path, backup)?;
+        state::rewrite_atomic(config_path, &contents)?;
+    }
+    Ok(applied)
+}
 
-    #[test]
-    fn load_from_file() {
-        let file_name = temp_dir().join("p_split_split_config");
-        let file_content = "
-[DEFAULT]
-root=/tmp
-[PIPES]
-cvAnalogsMapperExt=
-[cvAnalogsMapperExt]
-cvAnalogsMapperExtFuelApp=
-"
-        .as_bytes();
+/// Push `records` known records through a scratch topology under a
+/// temporary root and check every output receives them all, in order
+pub fn self_test(records: usize) -> Result<(), std::io::Error> {
+    match selftest::run(records)? {
+        true => Ok(()),
+        false => Err(io::Error::other("self-test failed")),
+    }
+}
 
-        {
-            let mut file = File::create(&file_name).expect("create");
-            file.write_all(file_content).expect("write");
-        }
-        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+/// Run a scratch topology under chaos, killing its consumers, deleting its
+/// FIFOs and pausing its producer, and check the splitter neither deadlocks,
+/// grows without bound nor loses track of the records it drops
+pub fn soak(options: &SoakOptions) -> Result<(), std::io::Error> {
+    let violations = soak::run(options)?;
+    for violation in &violations {
+        log!("Soak -> {}", violation);
+    }
+    match violations.is_empty() {
+        true => Ok(()),
+        false => Err(io::Error::other("soak test failed")),
+    }
+}
 
-        assert_eq!(1, config.len());
+/// Make warnings about the configurations loaded after errors, unknown
+/// options, sections no input reads and names in another case failing the
+/// load as `[DEFAULT] strict=true` does
+pub fn set_strict() {
+    STRICT_OVERRIDE.store(true, Ordering::Relaxed);
+}
 
-        let first_config = config.get(0).unwrap();
+/// Let writers flush their queued records for up to `timeout` on shutdown,
+/// overriding `[DEFAULT] drain_timeout` of the configurations loaded after
+pub fn set_drain_timeout(timeout: time::Duration) {
+    *DRAIN_OVERRIDE.lock().unwrap() = Some(timeout);
+}
 
-        assert_eq!(1, first_config.outputs.len());
-        assert!(first_config.configuration.enabled, "Should be enabled");
+/// How a run of the splitter ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitStatus {
+    /// Every pipe ran without errors
+    Clean,
+    /// The configuration could not be loaded
+    ConfigError,
+    /// The splitter could not run, or every pipe hit errors
+    RuntimeError,
+    /// Some pipes hit errors while the others ran
+    PartialFailure,
+}
 
-        assert_eq!(
-            1,
-            first_config
-                .outputs
-                .iter()
-                .filter(|x| x.configuration.enabled)
-                .count()
-        )
+impl ExitStatus {
+    /// Exit code of the process: 0 clean, 2 configuration error, 3 runtime
+    /// error and 4 partial failure
+    pub fn code(self) -> u8 {
+        match self {
+            ExitStatus::Clean => 0,
+            ExitStatus::ConfigError => 2,
+            ExitStatus::RuntimeError => 3,
+            ExitStatus::PartialFailure => 4,
+        }
     }
-    #[test]
-    fn needs_pipes_section() {
-        let file_name = temp_dir().join("p_split_bad_config_pipes");
-        let file_content = "
-[DEFAULT]
-root=/tmp
-[cvAnalogsMapperExt]
-cvAnalogsMapperExtFuelApp=
-"
-        .as_bytes();
 
-        {
-            let mut file = File::create(&file_name).expect("create");
-            file.write_all(file_content).expect("write");
+    /// Status of a run where `failed` of the `running` pipes hit errors
+    fn of_failures(failed: usize, running: usize) -> ExitStatus {
+        match failed {
+            0 => ExitStatus::Clean,
+            _ if failed == running => ExitStatus::RuntimeError,
+            _ => ExitStatus::PartialFailure,
         }
-        let config = Parser::load_from_file(&file_name);
-        assert_eq!(config.is_err(), true);
-        let error_matches = match config {
-            Err(e) => match e {
-                ParseError::Configuration(s) => {
-                    s.as_str() == "configuration must contain a 'PIPES' section"
-                }
-                _ => false,
-            },
-            Ok(_) => false,
-        };
-        assert_eq!(error_matches, true);
     }
-    #[test]
-    fn valid_pipe_configuration() {
-        let file_name = temp_dir().join("p_split_bad_config_configuration");
-        let file_content = "
-[DEFAULT]
-root=/tmp
-[PIPES]
-cvAnalogsMapperExt=
-[cvAnalogsMapperExt]
-cvAnalogsMapperExtFuelApp=1,wf
-"
-        .as_bytes();
+}
 
-        {
-            let mut file = File::create(&file_name).expect("create");
-            file.write_all(file_content).expect("write");
+/// Split pipes as described by the configuration file at `config_path`
+pub fn split_pipes<P: AsRef<Path>>(config_path: P) -> ExitStatus {
+    split_topology(config_path, None)
+}
+
+/// Split the pipes of the topology `name` of the configuration file at
+/// `config_path`, or of every topology when `None`, until `SIGTERM` or
+/// `SIGINT`. A summary of every pipe is logged on exit.
+pub fn split_topology<P: AsRef<Path>>(config_path: P, name: Option<&str>) -> ExitStatus {
+    match Splitter::load(&config_path, name) {
+        Ok(splitter) => splitter.run(),
+        Err(e) => {
+            log!(
+                "Configuration -> {} Error {}",
+                config_path.as_ref().display(),
+                e
+            );
+            ExitStatus::ConfigError
         }
-        let config = Parser::load_from_file(&file_name);
-        assert_eq!(config.is_err(), true);
+    }
+}
 
-        let error_matches = match config {
-            Err(e) => match e {
-                ParseError::Configuration(s) => s.as_str() == "Unknown operation type 'wf'",
-                _ => false,
-            },
-            Ok(_) => false,
+/// Splitter of the pipes of a configuration, loaded but not yet running
+pub struct Splitter {
+    /// Configuration file the topology was loaded from
+    config_path: PathBuf,
+    /// Topology split, every topology of the configuration when `None`
+    name: Option<String>,
+    /// Pipes and settings, shared with the handles
+    topology: Arc<Topology>,
+    /// Run of the splitter, shared with the handles
+    control: Arc<Control>,
+}
+
+/// Handle on a splitter, usable from other threads while it runs to look at
+/// its pipes, tune them and stop it
+#[derive(Clone)]
+pub struct SplitterHandle {
+    topology: Arc<Topology>,
+    control: Arc<Control>,
+}
+
+/// Progress of the run of a splitter
+#[derive(Default)]
+struct RunState {
+    /// Thread splitting the pipes, from when it reads its signals until the
+    /// run ends
+    thread: Option<libc::pthread_t>,
+    /// A stop was requested, possibly before the run started
+    stop: bool,
+    /// How the run ended
+    exit: Option<ExitStatus>,
+}
+
+/// Run of a splitter, stopped and waited for through its handles
+#[derive(Default)]
+struct Control {
+    state: Mutex<RunState>,
+    /// Signalled when the run starts and when it ends
+    changed: Condvar,
+    /// Thread started by [`Splitter::spawn`], joined by the first waiter
+    join: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl Control {
+    /// The run started on the calling thread, which reads the signals
+    fn started(&self) {
+        let mut state = self.state.lock().unwrap();
+        let thread = unsafe { libc::pthread_self() };
+        state.thread = Some(thread);
+        if state.stop {
+            // Read as soon as the supervisor waits for signals
+            unsafe { libc::pthread_kill(thread, libc::SIGTERM) };
+        }
+        self.changed.notify_all();
+    }
+
+    /// The run stopped reading the signals, which are about to be unblocked
+    /// on its thread: `SIGTERM` is no longer sent to stop it, as its default
+    /// action would end the whole process
+    fn stopping(&self) {
+        self.state.lock().unwrap().thread = None;
+    }
+
+    /// The run ended with `exit`
+    fn ended(&self, exit: ExitStatus) {
+        let mut state = self.state.lock().unwrap();
+        state.thread = None;
+        state.exit = Some(exit);
+        self.changed.notify_all();
+    }
+}
+
+impl SplitterHandle {
+    /// Inputs and outputs of the splitter, with the current state and
+    /// counters of their pipes
+    pub fn topology(&self) -> TopologyView {
+        inspect::view(&self.topology.settings, &self.topology.inputs)
+    }
+
+    /// Channel receiving the events of the splitter from now on, see
+    /// [`Event`]
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<Event> {
+        self.topology.settings.events.subscribe()
+    }
+
+    /// Call `callback` on every event of the splitter from now on, from the
+    /// thread it happened on
+    pub fn on_event<F: Fn(&Event) + Send + Sync + 'static>(&self, callback: F) {
+        self.topology.settings.events.on_event(callback)
+    }
+
+    /// Call `callback` with every record dropped from now on, on a full
+    /// queue or overflow buffer, past its TTL or for an evicted output, so
+    /// it can be salvaged. It runs on the thread dropping the record.
+    pub fn on_drop<F: Fn(&DroppedRecord) + Send + Sync + 'static>(&self, callback: F) {
+        self.topology.settings.events.on_drop(callback)
+    }
+
+    /// Let the queue of `output`, named by its label or path, hold up to
+    /// `capacity` records, from the next record on and without restarting
+    /// its writer. An output listed by several inputs has all its queues
+    /// resized.
+    pub fn set_queue(&self, output: &str, capacity: usize) -> Result<(), io::Error> {
+        if capacity == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "queue capacity must be at least 1",
+            ));
+        }
+        let mut found = false;
+        for out in self.topology.inputs.iter().flat_map(|i| i.outputs.iter()) {
+            if out.name() == output || out.pipe == output {
+                out.channel.set_capacity(capacity);
+                log!("Resized queue to {capacity} -> {}", out.name());
+                found = true;
+            }
+        }
+        match found {
+            true => Ok(()),
+            false => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no output '{output}'"),
+            )),
+        }
+    }
+
+    /// Whether the splitter is splitting its pipes
+    pub fn is_running(&self) -> bool {
+        self.control.state.lock().unwrap().thread.is_some()
+    }
+
+    /// Stop the splitter as `SIGTERM` does, or as soon as it runs when it
+    /// is not running yet, and wait for its run to end, see
+    /// [`SplitterHandle::wait`]
+    pub fn stop(&self) -> ExitStatus {
+        let mut state = self.control.state.lock().unwrap();
+        state.stop = true;
+        if let Some(thread) = state.thread {
+            unsafe { libc::pthread_kill(thread, libc::SIGTERM) };
+        }
+        drop(state);
+        self.wait()
+    }
+
+    /// Wait for the run of the splitter to end, its readers and writers
+    /// drained and joined, and return how it ended. A splitter that is not
+    /// running yet is waited for until it runs and stops.
+    pub fn wait(&self) -> ExitStatus {
+        let mut state = self.control.state.lock().unwrap();
+        let exit = loop {
+            match state.exit {
+                Some(exit) => break exit,
+                None => state = self.control.changed.wait(state).unwrap(),
+            }
+        };
+        drop(state);
+        if let Some(join) = self.control.join.lock().unwrap().take() {
+            let _ = join.join();
+        }
+        exit
+    }
+}
+
+impl Splitter {
+    /// Load the topology `name` of the configuration file at `config_path`,
+    /// or every topology when `None`
+    pub fn load<P: AsRef<Path>>(config_path: P, name: Option<&str>) -> Result<Splitter, io::Error> {
+        let topology = Parser::load_topology(&config_path, name)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        Ok(Splitter {
+            config_path: config_path.as_ref().to_path_buf(),
+            name: name.map(str::to_owned),
+            topology: Arc::new(topology),
+            control: Arc::default(),
+        })
+    }
+
+    /// Handle to inspect and stop the splitter, before, while and after it
+    /// runs
+    pub fn handle(&self) -> SplitterHandle {
+        SplitterHandle {
+            topology: Arc::clone(&self.topology),
+            control: Arc::clone(&self.control),
+        }
+    }
+
+    /// Split the pipes on a new thread, returning once it runs or failed to
+    /// start, see [`SplitterHandle::wait`] for how it ended
+    pub fn spawn(self) -> Result<SplitterHandle, io::Error> {
+        let handle = self.handle();
+        let control = Arc::clone(&self.control);
+        let join = thread::Builder::new()
+            .name("psplit".to_owned())
+            .spawn(move || {
+                let run = std::panic::AssertUnwindSafe(|| self.run());
+                if std::panic::catch_unwind(run).is_err() {
+                    control.ended(ExitStatus::RuntimeError);
+                }
+            })?;
+        *handle.control.join.lock().unwrap() = Some(join);
+        let mut state = handle.control.state.lock().unwrap();
+        while state.thread.is_none() && state.exit.is_none() {
+            state = handle.control.changed.wait(state).unwrap();
+        }
+        drop(state);
+        Ok(handle)
+    }
+
+    /// Split the pipes until `SIGTERM` or `SIGINT` or a stop through a
+    /// handle, see [`split_topology`]
+    pub fn run(self) -> ExitStatus {
+        let exit = self.split();
+        self.control.ended(exit);
+        exit
+    }
+
+    /// Run of [`Splitter::run`]
+    fn split(&self) -> ExitStatus {
+        let config_path = &self.config_path;
+        let topology = &*self.topology;
+        let entries = &topology.inputs;
+
+        if entries.is_empty() {
+            return ExitStatus::Clean;
+        }
+
+        let settings = &topology.settings;
+        let instance = settings.instance.as_deref();
+        let _lock = match lock::acquire(config_path, instance, self.name.as_deref()) {
+            Ok(lock) => lock,
+            Err(e) => {
+                log!("Lock -> {} Error {:?}", config_path.display(), e);
+                return ExitStatus::RuntimeError;
+            }
+        };
+        if let Err(e) = security::check_root(Path::new(&settings.root), settings.root_permissions) {
+            log!("Root -> {} Error {:?}", settings.root, e);
+            return ExitStatus::RuntimeError;
+        }
+        if settings.fifo.non_fifo == NonFifoPolicy::Replace {
+            // Writers replace the files at output paths when creating FIFOs
+            for input in entries.iter().filter(|input| input.source.is_none()) {
+                if let Err(e) = security::check_fifo(Path::new(&input.pipe), NonFifoPolicy::Replace)
+                {
+                    log!("Pipe -> {} Error {:?}", input.pipe, e);
+                    return ExitStatus::RuntimeError;
+                }
+            }
+        }
+        if let Err(e) = settings.limits.apply() {
+            log!("Limits -> {:?} Error {:?}", settings.limits, e);
+            return ExitStatus::RuntimeError;
+        }
+        match caps::drop_except(settings.keep_capabilities) {
+            Ok(Some(kept)) => log!("Dropped capabilities, keeping {}", kept),
+            Ok(None) => {}
+            Err(e) => {
+                log!(
+                    "Capabilities -> {} Error {:?}",
+                    settings.keep_capabilities,
+                    e
+                );
+                return ExitStatus::RuntimeError;
+            }
+        }
+        if let Some(state_file) = &settings.state_file {
+            match ResumeState::load(state_file) {
+                Ok(state) => {
+                    if !state.is_clean() {
+                        log!(
+                            "Warning: state not saved at exit, records read since its last save are numbered again <> {}",
+                            state_file
+                        );
+                    }
+                    state.apply(entries);
+                }
+                Err(e) => {
+                    log!("State file -> {} Error {:?}", state_file, e);
+                    return ExitStatus::RuntimeError;
+                }
+            }
+        }
+        console::topology(entries);
+        let mut signals = match Signals::install() {
+            Ok(signals) => signals,
+            Err(e) => {
+                log!("Signals -> {} Error {:?}", config_path.display(), e);
+                return ExitStatus::RuntimeError;
+            }
+        };
+        self.control.started();
+
+        let signal = Arc::new(Mutex::new(SIG_RUN));
+        let mut splitting_threads = create_splitting_threads(entries, &signal);
+        // The watchdog owns the reader threads while it runs, it replaces them
+        let watchdog = settings.watchdog.map(|timeout| {
+            watchdog::spawn(
+                entries.clone(),
+                Arc::clone(&signal),
+                std::mem::take(&mut splitting_threads),
+                timeout,
+                settings.timing.supervise,
+            )
+        });
+
+        let supervised = supervise(topology, &mut signals);
+        if let Err(e) = &supervised {
+            log!("Supervisor -> {} Error {:?}", config_path.display(), e);
+        }
+        // Signals sent until then stay pending and are read when `signals` drops
+        self.control.stopping();
+
+        // Readers stop their writers and wait for them to drain their queues
+        *signal.lock().unwrap() = SIG_EXIT;
+        if let Some(watchdog) = watchdog {
+            splitting_threads = watchdog.join().unwrap_or_default();
+        }
+        for handle in splitting_threads {
+            let _ = handle.join();
+        }
+        if let Some(state_file) = &settings.state_file {
+            if let Err(e) = state::save(state_file, entries, true) {
+                log!("State file -> {} Error {:?}", state_file, e);
+            }
+        }
+        if let Some(status_file) = &topology.settings.status_file {
+            if let Err(e) = status::write_report(status_file, &topology.settings, entries) {
+                log!("Status file -> {} Error {:?}", status_file, e);
+            }
+        }
+        for line in status::summary(entries) {
+            log!("{}", line);
+        }
+        if supervised.is_err() {
+            return ExitStatus::RuntimeError;
+        }
+        let (failed, running) = status::failures(entries);
+        ExitStatus::of_failures(failed, running)
+    }
+}
+
+/// Token of the signals in the supervising loop
+const SIGNALS: Token = Token(0);
+/// Token of the timer of the stall and high water checks
+const CHECKS: Token = Token(1);
+/// Token of the timer of the throughput lines
+const THROUGHPUT: Token = Token(2);
+/// Token of the timer of the status file updates
+const STATUS: Token = Token(3);
+/// Token of the timer of the state file saves
+const STATE: Token = Token(4);
+
+/// Supervise the workers of `topology` until a signal stops them: every
+/// periodic task has a timer of its own, polled along with `signals`
+fn supervise(topology: &Topology, signals: &mut Signals) -> io::Result<()> {
+    let settings = &topology.settings;
+    let entries = &topology.inputs;
+    let mut poll = Poll::new()?;
+    poll.registry()
+        .register(signals, SIGNALS, Interest::READABLE)?;
+    let mut timers = Vec::new();
+    timers.push((CHECKS, Timer::every(settings.timing.supervise)?));
+    if let Some(interval) = settings.throughput {
+        timers.push((THROUGHPUT, Timer::every(interval)?));
+    }
+    if settings.status_file.is_some() {
+        timers.push((STATUS, Timer::every(settings.timing.status)?));
+    }
+    if settings.state_file.is_some() {
+        timers.push((STATE, Timer::every(settings.timing.checkpoint)?));
+    }
+    for (token, timer) in timers.iter_mut() {
+        poll.registry()
+            .register(timer, *token, Interest::READABLE)?;
+    }
+
+    let mut throughput = status::Throughput::new(entries);
+    let mut fd_budget = FdBudget::new(settings.fd_warning);
+    let mut events = Events::with_capacity(timers.len() + 1);
+    loop {
+        match poll.poll(&mut events, None) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => result?,
+        }
+        for event in events.iter() {
+            if event.token() == SIGNALS {
+                for number in signals.received()? {
+                    match number {
+                        libc::SIGHUP => logfile::reopen(),
+                        libc::SIGUSR1 => {
+                            for line in status::report(settings, entries).lines() {
+                                log!("Status <> {}", line);
+                            }
+                        }
+                        _ => {
+                            log!("Stopping on signal {}", number);
+                            return Ok(());
+                        }
+                    }
+                }
+                continue;
+            }
+            let Some((_, timer)) = timers.iter().find(|(token, _)| *token == event.token()) else {
+                continue;
+            };
+            if timer.expirations()? == 0 {
+                continue;
+            }
+            match event.token() {
+                CHECKS => {
+                    status::warn_stalled(entries);
+                    status::evict_stalled(entries);
+                    status::check_high_water(entries);
+                    if let Err(e) = fd_budget.check() {
+                        log!("Descriptors -> /proc/self/fd Error {:?}", e);
+                    }
+                }
+                THROUGHPUT => {
+                    for line in throughput.lines(entries) {
+                        log!("{}", line);
+                    }
+                }
+                STATUS => {
+                    if let Some(status_file) = &settings.status_file {
+                        if let Err(e) = status::write_report(status_file, settings, entries) {
+                            log!("Status file -> {} Error {:?}", status_file, e);
+                        }
+                    }
+                }
+                STATE => {
+                    if let Some(state_file) = &settings.state_file {
+                        if let Err(e) = state::save(state_file, entries, false) {
+                            log!("State file -> {} Error {:?}", state_file, e);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    #[test]
+    fn load_from_file() {
+        let file_name = temp_dir().join("p_split_split_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+
+        assert_eq!(1, config.inputs.len());
+
+        let first_config = config.inputs.first().unwrap();
+
+        assert_eq!(1, first_config.outputs.len());
+        assert!(first_config.configuration.enabled, "Should be enabled");
+
+        assert_eq!(
+            1,
+            first_config
+                .outputs
+                .iter()
+                .filter(|x| x.configuration.enabled)
+                .count()
+        )
+    }
+    #[test]
+    fn non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::fs::FileTypeExt;
+
+        let file_name = temp_dir().join(OsStr::from_bytes(b"p_split_config_\xff"));
+        fs::write(
+            &file_name,
+            "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+",
+        )
+        .expect("write");
+        let splitter = Splitter::load(&file_name, None).expect("Should load configuration ");
+        assert_eq!(splitter.handle().topology().inputs.len(), 1);
+        let _ = fs::remove_file(&file_name);
+
+        let fifo = temp_dir().join(OsStr::from_bytes(b"p_split_fifo_\xfe"));
+        let _ = fs::remove_file(&fifo);
+        Writer::create(&fifo, None).expect("create");
+        assert!(fs::metadata(&fifo).unwrap().file_type().is_fifo());
+        let _ = fs::remove_file(fifo);
+    }
+    #[test]
+    fn tune_queue() {
+        let file_name = temp_dir().join("p_split_tune_config");
+        fs::write(
+            &file_name,
+            "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+cvGpsMapper=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,queue=8,label=fuel
+cvSharedApp=1,wt
+[cvGpsMapper]
+cvSharedApp=1,wt
+",
+        )
+        .expect("write");
+        let splitter = Splitter::load(&file_name, None).expect("Should load configuration ");
+        let handle = splitter.handle();
+        handle.set_queue("fuel", 32).unwrap();
+        handle.set_queue("/tmp/cvSharedApp", 2).unwrap();
+        let inputs = &splitter.topology.inputs;
+        assert_eq!(inputs[0].outputs[0].channel.capacity(), 32);
+        assert_eq!(inputs[0].outputs[1].channel.capacity(), 2);
+        assert_eq!(inputs[1].outputs[0].channel.capacity(), 2);
+        assert_eq!(handle.topology().inputs[0].outputs[0].capacity, 32);
+        assert!(handle.topology().process.is_some_and(|p| p.threads > 0));
+
+        let e = handle.set_queue("gps", 4).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        let e = handle.set_queue("fuel", 0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        let _ = fs::remove_file(&file_name);
+    }
+    #[test]
+    fn lenient_names() {
+        let file_name = temp_dir().join("p_split_lenient_config");
+        let load = |lenient: &str| {
+            fs::write(
+                &file_name,
+                format!(
+                    "
+[Default]
+Root=/tmp/p_split_lenient
+create_root=yes
+{lenient}
+[pipes]
+cvAnalogsMapperExt=1,rt
+[cvanalogsmapperext]
+cvAnalogsMapperExtFuelApp=1,wt
+"
+                ),
+            )
+            .expect("write");
+            Parser::load_from_file(&file_name)
+        };
+        let config = load("").expect("Should load configuration ");
+        assert_eq!(config.settings.root, "/tmp/p_split_lenient");
+        assert_eq!(config.inputs.len(), 1);
+        assert_eq!(config.inputs[0].outputs.len(), 1);
+        assert!(load("lenient=no").is_err());
+    }
+    #[test]
+    fn strict_mode() {
+        let file_name = temp_dir().join("p_split_strict_config");
+        let load = |strict: &str, config: &str| {
+            fs::write(
+                &file_name,
+                format!(
+                    "
+[DEFAULT]
+root=/tmp/p_split_strict
+{strict}
+[PIPES]
+cvAnalogsMapperExt=1,rt
+{config}
+"
+                ),
+            )
+            .expect("write");
+            Parser::load_from_file(&file_name)
+        };
+        let configs = [
+            "[cvAnalogsMapperExt]\ncvAnalogsMapperExtFuelApp=1,wt,colour=red",
+            "[cvAnalogsMapperExt]\ncvAnalogsMapperExtFuelApp=1,wt\n[cvGpsMapper]\ngps=1,wt",
+            "[cvanalogsmapperext]\ncvAnalogsMapperExtFuelApp=1,wt",
+        ];
+        for config in configs {
+            load("", config).expect("Should load configuration ");
+            assert!(matches!(
+                load("strict=true", config),
+                Err(ParseError::Configuration(_))
+            ));
+        }
+        let config = load(
+            "strict=true",
+            "[cvAnalogsMapperExt]\ncvAnalogsMapperExtFuelApp=1,wt",
+        )
+        .expect("Should load configuration ");
+        assert!(config.settings.strict);
+    }
+    #[test]
+    fn migrate_config_file() {
+        let file_name = temp_dir().join("p_split_migrate_config");
+        fs::write(
+            &file_name,
+            "
+[Default]
+Root=/tmp/p_split_migrate
+[pipes]
+cvAnalogsMapperExt=1,rt
+[cvanalogsmapperext]
+cvAnalogsMapperExtFuelApp=1,wt,label=\"fuel, main\"
+",
+        )
+        .expect("write");
+        fs::set_permissions(&file_name, fs::Permissions::from_mode(0o640)).expect("chmod");
+        let lock = lock::acquire(&file_name, None, None).expect("lock");
+        let e = migrate_config(&file_name).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        drop(lock);
+        assert_eq!(migrate_config(&file_name).unwrap().len(), 1);
+        assert!(migrate_config(&file_name).unwrap().is_empty());
+        let contents = fs::read_to_string(&file_name).unwrap();
+        assert!(contents.contains("[DEFAULT]") && contents.contains("version=2"));
+        let mode = fs::metadata(&file_name).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        let backup = fs::read_to_string(temp_dir().join("p_split_migrate_config.bak")).unwrap();
+        assert!(backup.contains("[Default]") && !backup.contains("version"));
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        assert_eq!(config.inputs[0].outputs[0].name(), "fuel, main");
+
+        fs::write(&file_name, "[DEFAULT]\nversion=3\n[PIPES]\n").expect("write");
+        assert!(Parser::load_from_file(&file_name).is_err());
+        let e = migrate_config(&file_name).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+    #[test]
+    fn needs_pipes_section() {
+        let file_name = temp_dir().join("p_split_bad_config_pipes");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name);
+        assert!(config.is_err());
+        let error_matches = match config {
+            Err(e) => match e {
+                ParseError::Configuration(s) => {
+                    s.as_str() == "configuration must contain a 'PIPES' section"
+                }
+                _ => false,
+            },
+            Ok(_) => false,
+        };
+        assert!(error_matches);
+    }
+    #[test]
+    fn missing_root_not_created() {
+        let file_name = temp_dir().join("p_split_missing_root");
+        let file_content = "
+[DEFAULT]
+root=/tmp/p_split_no_such_root
+create_root=false
+[PIPES]
+cvAnalogsMapperExt=
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let error_matches = match Parser::load_from_file(&file_name) {
+            Err(ParseError::Configuration(s)) => {
+                s.as_str() == "Pipe root directory '/tmp/p_split_no_such_root' does not exist"
+            }
+            _ => false,
+        };
+        assert!(error_matches);
+        assert!(!Path::new("/tmp/p_split_no_such_root").exists());
+    }
+    #[test]
+    fn fifo_mode_overrides_umask() {
+        let path = temp_dir().join("p_split_fifo_mode");
+        let _ = fs::remove_file(&path);
+
+        let fifo = FifoOptions {
+            mode: Some(0o666),
+            ..FifoOptions::default()
+        };
+        Writer::create_fifo(&path, &fifo).expect("create");
+        let mode = fs::metadata(&path).expect("metadata").permissions().mode();
+        assert_eq!(mode & 0o777, 0o666);
+        Writer::create_fifo(&path, &fifo).expect("existing FIFO");
+    }
+    #[test]
+    fn non_fifo_policy() {
+        let root = temp_dir().join("p_split_non_fifo");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("fuel"), b"").unwrap();
+
+        let load = |policy: &str| {
+            let file_name = temp_dir().join("p_split_non_fifo_config");
+            let file_content = format!(
+                "
+[DEFAULT]
+root={}
+non_fifo={policy}
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+fuel=
+",
+                root.display()
+            );
+            fs::write(&file_name, file_content).expect("write");
+            Parser::load_from_file(&file_name)
+        };
+
+        assert!(load("error").is_err());
+        let config = load("skip").expect("Should load configuration ");
+        assert!(!config.inputs[0].outputs[0].configuration.enabled);
+        assert!(root.join("fuel").is_file());
+        // Loading the configuration leaves the file, the writer replaces it
+        let config = load("replace").expect("Should load configuration ");
+        let output = &config.inputs[0].outputs[0];
+        assert!(output.configuration.enabled);
+        assert!(root.join("fuel").is_file());
+        output.create_fifo(root.join("fuel")).expect("replace");
+        assert!(fs::symlink_metadata(root.join("fuel"))
+            .unwrap()
+            .file_type()
+            .is_fifo());
+    }
+    #[test]
+    fn ephemeral_pipe_names() {
+        let file_name = temp_dir().join("p_split_ephemeral_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+ephemeral=true
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,ack=fuel.ack,notify=fuel.bell
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let pid = std::process::id();
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let output = &config.inputs[0].outputs[0];
+        assert_eq!(output.pipe, format!("/tmp/cvAnalogsMapperExtFuelApp.{pid}"));
+        assert_eq!(output.ack, Some(format!("/tmp/fuel.ack.{pid}")));
+        assert_eq!(output.notify, Some(format!("/tmp/fuel.bell.{pid}")));
+
+        let pipes = pipe_map(&file_name).expect("Should resolve pipes");
+        assert_eq!(
+            pipes[0],
+            (
+                "cvAnalogsMapperExt".to_owned(),
+                format!("/tmp/cvAnalogsMapperExt.{pid}")
+            )
+        );
+        assert_eq!(
+            pipes[1],
+            ("cvAnalogsMapperExtFuelApp".to_owned(), output.pipe.clone())
+        );
+        assert_eq!(pipes.len(), 2);
+    }
+    #[test]
+    fn instance_namespacing() {
+        let file_name = temp_dir().join("p_split_instance_config");
+        let file_content = "
+[DEFAULT]
+instance=fuel
+status_file=/tmp/psplit-{instance}.status
+[PIPES]
+cvAnalogsMapperExt=
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        assert_eq!(config.settings.root, "/tmp/cvnpipes/fuel");
+        assert_eq!(
+            config.settings.status_file.as_deref(),
+            Some("/tmp/psplit-fuel.status")
+        );
+        let report = status::report(&config.settings, &config.inputs);
+        assert!(report.starts_with("INSTANCE(name: fuel)\nPROCESS(pid: "));
+    }
+    #[test]
+    fn exit_summary() {
+        let file_name = temp_dir().join("p_split_summary_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt,label=analogs
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,label=fuel
+cvAnalogsMapperExtLogApp=1,wt,label=log
+cvAnalogsMapperExtGpsApp=0,wt
+";
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let input = &config.inputs[0];
+        input.status.read(8);
+        input.outputs[0].status.written(1, 8);
+        input.outputs[1].status.reserve();
+        input.outputs[1].status.dropped(1);
+        input.outputs[1].status.failed();
+
+        assert_eq!(
+            status::summary(&config.inputs),
+            [
+                "Summary <> IN(pipe: analogs, records: 1, errors: 0)",
+                "Summary <> OUT(pipe: fuel, records: 1, drops: 0, errors: 0)",
+                "Summary <> OUT(pipe: log, records: 0, drops: 1, errors: 1)",
+                "Summary <> OUT(pipe: /tmp/cvAnalogsMapperExtGpsApp, records: 0, drops: 0, errors: 0)",
+            ]
+        );
+        // The disabled output does not count
+        assert_eq!(status::failures(&config.inputs), (1, 3));
+        assert_eq!(ExitStatus::of_failures(1, 3), ExitStatus::PartialFailure);
+        assert_eq!(ExitStatus::of_failures(3, 3), ExitStatus::RuntimeError);
+        assert_eq!(ExitStatus::of_failures(0, 3), ExitStatus::Clean);
+        assert_eq!(ExitStatus::PartialFailure.code(), 4);
+
+        let missing = temp_dir().join("p_split_missing_config");
+        assert_eq!(split_pipes(missing), ExitStatus::ConfigError);
+    }
+    #[test]
+    fn conditional_enable() {
+        let marker = temp_dir().join("p_split_debug_marker");
+        fs::write(&marker, "").expect("marker");
+        std::env::set_var("P_SPLIT_TAP", "1");
+        std::env::set_var("P_SPLIT_OFF", "0");
+        let file_name = temp_dir().join("p_split_conditional_config");
+        let file_content = format!(
+            "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=env:P_SPLIT_TAP,wt
+cvAnalogsMapperExtLogApp=env:P_SPLIT_OFF,wt
+cvAnalogsMapperExtGpsApp=env:P_SPLIT_UNSET,wt
+cvAnalogsMapperExtDebugApp=file:{},wt
+cvAnalogsMapperExtTraceApp=!file:{},wt
+",
+            marker.display(),
+            marker.display()
+        );
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let enabled: Vec<bool> = config.inputs[0]
+            .outputs
+            .iter()
+            .map(|output| output.configuration.enabled)
+            .collect();
+        assert_eq!(enabled, [true, false, false, true, false]);
+        assert!(Parser::get_enabled("!env:P_SPLIT_UNSET"));
+        assert!(!Parser::get_enabled("yes"));
+        let _ = fs::remove_file(marker);
+    }
+    #[test]
+    fn rotation_options() {
+        let load = |options: &str, output: &str| {
+            let file_name = temp_dir().join("p_split_rotation_config");
+            let file_content = format!(
+                "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt,{options}
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt{output}
+cvAnalogsMapperExtLogApp=1,wt
+"
+            );
+            fs::write(&file_name, file_content).expect("write");
+            Parser::load_from_file(&file_name)
+        };
+
+        let config = load("rotate=100", "").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].rotate, Some(ChunkSize::Records(100)));
+        let config = load("rotate_bytes=65536", "").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].rotate, Some(ChunkSize::Bytes(65536)));
+        assert!(load("rotate=0", "").is_err());
+        assert!(load("rotate=10,rotate_bytes=10", "").is_err());
+        // Outputs taking turns cannot all get a record
+        assert!(load("rotate=100", ",group=fuel").is_err());
+        assert!(load("label=can", ",group=fuel").is_ok());
+    }
+    #[test]
+    fn output_placeholders() {
+        let file_name = temp_dir().join("p_split_placeholder_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+pipe_suffix=
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+{input}FuelApp_{hostname}=1,wt
+cvAnalogsMapperExtLog-{date}=1,wt
+";
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let outputs = &config.inputs[0].outputs;
+        assert_eq!(
+            outputs[0].pipe,
+            format!("/tmp/cvAnalogsMapperExtFuelApp_{}", naming::hostname())
+        );
+        assert!(outputs[0].timed.is_none());
+
+        let timed = outputs[1].timed.as_ref().expect("dated output");
+        let today = naming::format_local(SystemTime::now(), "%Y-%m-%d");
+        assert_eq!(outputs[1].pipe, "/tmp/cvAnalogsMapperExtLog-{date}");
+        assert_eq!(
+            timed.resolve(SystemTime::now()),
+            format!("/tmp/cvAnalogsMapperExtLog-{today}")
+        );
+
+        // The pipe map names outputs as they are created
+        let pipes = pipe_map(&file_name).expect("Should resolve pipes");
+        let paths: Vec<&str> = pipes.iter().map(|(_, path)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/tmp/cvAnalogsMapperExt",
+                outputs[0].pipe.as_str(),
+                outputs[1].pipe.as_str()
+            ]
+        );
+    }
+    #[test]
+    fn shared_outputs() {
+        let file_name = temp_dir().join("p_split_shared_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+cvDigitalsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvMapperExtLogApp=1,wt,prefix=[{input}]
+cvAnalogsMapperExtFuelApp=1,wt
+[cvDigitalsMapperExt]
+cvMapperExtLogApp=1,wt,prefix=[{input}]
+";
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let (analogs, digitals) = (&config.inputs[0], &config.inputs[1]);
+        let turn = analogs.outputs[0].turn.as_ref().expect("shared output");
+        assert!(Arc::ptr_eq(
+            turn,
+            digitals.outputs[0].turn.as_ref().expect("shared output")
+        ));
+        assert!(analogs.outputs[1].turn.is_none());
+        assert_eq!(
+            analogs.outputs[0].transforms.apply(b"fuel=12\n"),
+            Some(b"[cvAnalogsMapperExt]fuel=12\n".to_vec())
+        );
+        assert_eq!(
+            digitals.outputs[0].transforms.apply(b"door=1\n"),
+            Some(b"[cvDigitalsMapperExt]door=1\n".to_vec())
+        );
+
+        // Acknowledgements of a shared output would not tell the inputs apart
+        let acked = file_content.replace("prefix=[{input}]", "ack=cvMapperExtLogAck");
+        fs::write(&file_name, acked).expect("write");
+        assert!(Parser::load_from_file(&file_name).is_err());
+    }
+    #[test]
+    fn standby_outputs() {
+        let file_name = temp_dir().join("p_split_standby_config");
+        let load = |options: &str| {
+            fs::write(
+                &file_name,
+                format!(
+                    "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt{options}
+"
+                ),
+            )
+            .expect("write");
+            Parser::load_from_file(&file_name)
+        };
+        let config = load("").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].outputs[0].standby, None);
+        let config =
+            load(",standby=cvAnalogsMapperExtFuelSpool").expect("Should load configuration ");
+        assert_eq!(
+            config.inputs[0].outputs[0].standby.as_deref(),
+            Some("/tmp/cvAnalogsMapperExtFuelSpool")
+        );
+        // Acknowledgements would be awaited from the standby consumer
+        assert!(
+            load(",standby=cvAnalogsMapperExtFuelSpool,ack=cvAnalogsMapperExtFuelAck").is_err()
+        );
+    }
+    #[test]
+    fn transcripts() {
+        let file_name = temp_dir().join("p_split_transcript_config");
+        let load = |input: &str, output: &str| {
+            fs::write(
+                &file_name,
+                format!(
+                    "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt{input}
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt{output}
+"
+                ),
+            )
+            .expect("write");
+            Parser::load_from_file(&file_name)
+        };
+        let config = load("", "").expect("Should load configuration ");
+        assert!(config.inputs[0].transcript.is_none());
+        assert!(config.inputs[0].outputs[0].transcript.is_none());
+        let config = load(
+            ",transcript=p_split_transcript_in,transcript_window=300",
+            ",transcript=p_split_transcript_out,transcript_size=4096",
+        )
+        .expect("Should load configuration ");
+        let input = config.inputs[0].transcript.as_ref().expect("transcript");
+        input.record(b"fuel=12\n");
+        let output = config.inputs[0].outputs[0].transcript.as_ref();
+        output.expect("transcript").record(b"fuel=12\n");
+        for path in ["/tmp/p_split_transcript_in", "/tmp/p_split_transcript_out"] {
+            assert!(fs::read_to_string(path).unwrap().ends_with(" fuel=12\n"));
+            let _ = fs::remove_file(path);
+        }
+        assert!(load("", ",transcript=p_split_transcript_out,transcript_size=big").is_err());
+    }
+    #[test]
+    fn open_modes() {
+        let file_name = temp_dir().join("p_split_open_config");
+        let load = |open: &str| {
+            fs::write(
+                &file_name,
+                format!(
+                    "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt{open}
+"
+                ),
+            )
+            .expect("write");
+            Parser::load_from_file(&file_name)
+        };
+        let config = load("").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].outputs[0].open, OpenMode::Poll);
+        assert_eq!(
+            config.inputs[0].outputs[0].open_policy,
+            OpenPolicy::OnDemand
+        );
+        let config = load(",open=wait").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].outputs[0].open, OpenMode::Wait);
+        assert!(load(",open=block").is_err());
+        let config = load(",open_policy=persistent").expect("Should load configuration ");
+        assert_eq!(
+            config.inputs[0].outputs[0].open_policy,
+            OpenPolicy::Persistent
+        );
+        assert!(load(",open_policy=always").is_err());
+    }
+    #[test]
+    fn supervise_on_timers() {
+        let status_file = temp_dir().join("p_split_supervise_status");
+        let _ = fs::remove_file(&status_file);
+        let file_name = temp_dir().join("p_split_supervise_config");
+        let file_content = format!(
+            "
+[DEFAULT]
+root=/tmp
+status_file={}
+status_interval=0.05
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt
+",
+            status_file.display()
+        );
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+
+        // Signals are only read by the thread that installed them
+        let mut signals = Signals::install().expect("signals");
+        let supervisor = unsafe { libc::pthread_self() };
+        let stopper = thread::spawn(move || {
+            thread::sleep(time::Duration::from_millis(300));
+            unsafe { libc::pthread_kill(supervisor, libc::SIGTERM) };
+        });
+        let start = time::Instant::now();
+        supervise(&config, &mut signals).expect("supervise");
+        assert!(start.elapsed() < time::Duration::from_secs(2));
+        stopper.join().unwrap();
+        let report = fs::read_to_string(&status_file).expect("status file");
+        assert!(report.contains("IN(pipe: /tmp/cvAnalogsMapperExt"));
+        let _ = fs::remove_file(status_file);
+    }
+    #[test]
+    fn inspect_running_splitter() {
+        let root = temp_dir().join(format!("p_split_inspect_{}", std::process::id()));
+        fs::create_dir_all(&root).expect("root");
+        let file_name = temp_dir().join("p_split_inspect_config");
+        let file_content = format!(
+            "
+[DEFAULT]
+root={}
+drain_timeout=0.1
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wb,label=fuel
+cvAnalogsMapperExtLogApp=0,wt
+",
+            root.display()
+        );
+        fs::write(&file_name, file_content).expect("write");
+        Writer::create(root.join("cvAnalogsMapperExt"), None).expect("fifo");
+        let splitter = Splitter::load(&file_name, None).expect("Should load configuration ");
+        let handle = splitter.handle();
+
+        let view = handle.topology();
+        let input = &view.inputs[0];
+        assert_eq!(input.pipe, format!("{}/cvAnalogsMapperExt", root.display()));
+        assert_eq!(
+            (input.mode, input.state, input.records),
+            (Some(PipeMode::ReadText), PipeState::Running, 0)
+        );
+        let outputs: Vec<_> = input
+            .outputs
+            .iter()
+            .map(|o| (o.name.as_str(), o.mode, o.state))
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                ("fuel", Some(PipeMode::WriteBytes), PipeState::Running),
+                (
+                    &*format!("{}/cvAnalogsMapperExtLogApp", root.display()),
+                    Some(PipeMode::WriteText),
+                    PipeState::Disabled
+                ),
+            ]
+        );
+
+        // Feed the input, attach a consumer and look at the splitter from
+        // another thread while it runs, then stop it
+        let pipe = input.pipe.clone();
+        let fuel = root.join("cvAnalogsMapperExtFuelApp");
+        let events = handle.subscribe();
+        let splitter_thread = unsafe { libc::pthread_self() };
+        let inspector = thread::spawn(move || {
+            thread::sleep(time::Duration::from_millis(300));
+            let mut producer = OpenOptions::new().write(true).open(&pipe).expect("open");
+            producer.write_all(b"fuel=12\nfuel=13\n").expect("feed");
+            // The writer creates its FIFO once started
+            let _consumer = (0..20).find_map(|_| {
+                thread::sleep(time::Duration::from_millis(50));
+                OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(&fuel)
+                    .ok()
+            });
+            thread::sleep(time::Duration::from_millis(300));
+            let view = handle.topology();
+            drop(producer);
+            unsafe { libc::pthread_kill(splitter_thread, libc::SIGTERM) };
+            view
+        });
+        splitter.run();
+        let view = inspector.join().unwrap();
+        assert_eq!(view.inputs[0].records, 2);
+        assert_eq!(view.inputs[0].bytes, 16);
+        let attached = Event::ConsumerAttached {
+            output: "fuel".into(),
+        };
+        assert!(events.try_iter().any(|event| event == attached));
+        let _ = fs::remove_dir_all(root);
+    }
+    #[test]
+    fn spawn_and_stop() {
+        let root = temp_dir().join(format!("p_split_spawn_{}", std::process::id()));
+        fs::create_dir_all(&root).expect("root");
+        let file_name = temp_dir().join("p_split_spawn_config");
+        let file_content = format!(
+            "
+[DEFAULT]
+root={}
+drain_timeout=0.1
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt
+",
+            root.display()
+        );
+        fs::write(&file_name, file_content).expect("write");
+        Writer::create(root.join("cvAnalogsMapperExt"), None).expect("fifo");
+
+        let splitter = Splitter::load(&file_name, None).expect("Should load configuration ");
+        let handle = splitter.spawn().expect("spawn");
+        assert!(handle.is_running());
+        let waiter = handle.clone();
+        let waiting = thread::spawn(move || waiter.wait());
+        assert_eq!(handle.stop(), ExitStatus::Clean);
+        assert!(!handle.is_running());
+        assert_eq!(waiting.join().unwrap(), ExitStatus::Clean);
+        assert_eq!(handle.wait(), ExitStatus::Clean);
+
+        // A stop requested before the run takes effect once it starts
+        let splitter = Splitter::load(&file_name, None).expect("Should load configuration ");
+        let early = splitter.handle();
+        let stopping = thread::spawn(move || early.stop());
+        thread::sleep(time::Duration::from_millis(100));
+        let start = time::Instant::now();
+        assert_eq!(splitter.run(), ExitStatus::Clean);
+        assert!(start.elapsed() < time::Duration::from_secs(2));
+        assert_eq!(stopping.join().unwrap(), ExitStatus::Clean);
+        let _ = fs::remove_dir_all(root);
+    }
+    #[test]
+    fn throughput_lines() {
+        let file_name = temp_dir().join("p_split_throughput_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+throughput_interval=10
+[PIPES]
+cvAnalogsMapperExt=1,rt,label=analogs
+cvSpeedMapperExt=0,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,label=fuel,queue=4
+cvAnalogsMapperExtGpsApp=0,wt
+[cvSpeedMapperExt]
+cvSpeedMapperExtFuelApp=
+";
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        assert_eq!(
+            config.settings.throughput,
+            Some(time::Duration::from_secs(10))
+        );
+        let input = &config.inputs[0];
+        input.status.read(8);
+        let mut throughput = status::Throughput::new(&config.inputs);
+        input.status.read(8);
+        input.outputs[0].status.reserve();
+
+        // Only the running pipes are reported, with what changed since
+        let lines = throughput.lines(&config.inputs);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Throughput <> IN(pipe: analogs, records/s: "));
+        assert!(!lines[0].contains("records/s: 0.0,"));
+        assert!(lines[1].starts_with(
+            "Throughput <> OUT(pipe: fuel, records/s: 0.0, bytes/s: 0.0, drops/s: 0.0, queue: 1/4)"
+        ));
+        let lines = throughput.lines(&config.inputs);
+        assert!(
+            lines[0].starts_with("Throughput <> IN(pipe: analogs, records/s: 0.0, bytes/s: 0.0)")
+        );
+    }
+    #[test]
+    fn named_topologies() {
+        let file_name = temp_dir().join("p_split_topologies_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES.gateway]
+cvAnalogsMapperExt=
+[PIPES.sensor]
+cvDigitalsMapperExt=
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_topology(&file_name, Some("sensor")).expect("Should load");
+        assert_eq!(config.inputs.len(), 1);
+        assert_eq!(config.inputs[0].pipe, "/tmp/cvDigitalsMapperExt");
+
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        assert_eq!(config.inputs.len(), 2);
+        assert!(Parser::load_topology(&file_name, Some("missing")).is_err());
+    }
+    #[test]
+    fn timing_settings() {
+        let file_name = temp_dir().join("p_split_timing_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+poll_interval=0.005
+retry_delay=0.0005
+[PIPES]
+cvAnalogsMapperExt=
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let timing = config.inputs[0].timing;
+        assert_eq!(timing.poll, time::Duration::from_millis(5));
+        assert_eq!(timing.retry, time::Duration::from_micros(500));
+        assert_eq!(timing.status, STATUS_INTERVAL);
+    }
+    #[test]
+    fn throughput_cap() {
+        let load = |rate: &str| {
+            let file_name = temp_dir().join("p_split_throttle_config");
+            let file_content = format!(
+                "
+[DEFAULT]
+root=/tmp
+max_throughput={rate}
+[PIPES]
+cvAnalogsMapperExt=
+cvAnalogsMapperExtLog=
+"
+            );
+            fs::write(&file_name, file_content).expect("write");
+            Parser::load_from_file(&file_name)
+        };
+
+        let config = load("65536").expect("Should load configuration ");
+        let throttle = config.settings.throttle.as_ref().expect("throttle");
+        assert_eq!(throttle.rate(), 65536);
+        // A single bucket for all the inputs
+        for input in config.inputs.iter() {
+            assert!(Arc::ptr_eq(input.throttle.as_ref().unwrap(), throttle));
+        }
+        assert!(load("0").is_err());
+        assert!(load("fast").is_err());
+    }
+    #[test]
+    fn resource_limits() {
+        let load = |limits: &str| {
+            let file_name = temp_dir().join("p_split_limits_config");
+            let file_content = format!(
+                "
+[DEFAULT]
+root=/tmp
+{limits}
+[PIPES]
+cvAnalogsMapperExt=
+"
+            );
+            fs::write(&file_name, file_content).expect("write");
+            Parser::load_from_file(&file_name)
+        };
+
+        let config = load("memory_limit=33554432\nopen_files_limit=256")
+            .expect("Should load configuration ");
+        assert_eq!(config.settings.limits.memory, Some(32 << 20));
+        assert_eq!(config.settings.limits.open_files, Some(256));
+        let config =
+            load("cgroup=/sys/fs/cgroup/psplit\ncpu_limit=50").expect("Should load configuration ");
+        assert_eq!(config.settings.limits.cpu, Some(50));
+        assert!(load("cpu_limit=50").is_err());
+        assert!(load("memory_limit=0").is_err());
+        assert_eq!(config.settings.fd_warning, FD_WARNING);
+        let config = load("fd_warning=90").expect("Should load configuration ");
+        assert_eq!(config.settings.fd_warning, 90);
+        assert!(load("fd_warning=150").is_err());
+        assert_eq!(config.settings.keep_capabilities, CapSet(0));
+        let config =
+            load("keep_capabilities=chown,dac_override").expect("Should load configuration ");
+        assert_eq!(config.settings.keep_capabilities, CapSet(0b11));
+        assert!(load("keep_capabilities=chown,teleport").is_err());
+    }
+    #[test]
+    fn split_oversized_records() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let (framed, ends) = split_parts(&data);
+
+        let mut start = 0;
+        let mut joined = Vec::new();
+        for (index, end) in ends.iter().enumerate() {
+            let part = &framed[start..*end];
+            assert!(part.len() <= libc::PIPE_BUF);
+            let header = format!("{}/{} ", index + 1, ends.len());
+            assert!(part.starts_with(header.as_bytes()));
+            joined.extend_from_slice(&part[header.len()..]);
+            start = *end;
+        }
+        assert_eq!(ends.len(), 3);
+        assert_eq!(joined, data);
+    }
+    #[test]
+    fn valid_pipe_configuration() {
+        let file_name = temp_dir().join("p_split_bad_config_configuration");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wf
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name);
+        assert!(config.is_err());
+
+        let error_matches = match config {
+            Err(e) => match e {
+                ParseError::Configuration(s) => s.as_str() == "Unknown operation type 'wf'",
+                _ => false,
+            },
+            Ok(_) => false,
+        };
+
+        assert!(error_matches);
+    }
+    #[test]
+    fn output_eviction_option() {
+        let file_name = temp_dir().join("p_split_evict_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,evict_after=30
+cvAnalogsMapperExtLogApp=1
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let outputs = &config.inputs[0].outputs;
+
+        assert_eq!(outputs[0].evict_after, Some(time::Duration::from_secs(30)));
+        assert_eq!(outputs[1].evict_after, None);
+    }
+    #[test]
+    fn output_queue_options() {
+        let file_name = temp_dir().join("p_split_queue_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt,exec_on_start=logger -t psplit started
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,queue=64,high_water=75,group=fuel,label=fuel-telemetry,newline=yes
+cvAnalogsMapperExtLogApp=1,wt,columns=1,3,7,queue=8,overflow=4096,buffer_until_reader=true
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let output = &config.inputs[0].outputs[0];
+
+        assert_eq!(output.channel.capacity(), 64);
+        assert_eq!(output.high_water, Some(75));
+        assert_eq!(output.group.as_deref(), Some("fuel"));
+        let lifecycle = &config.inputs[0].lifecycle;
+        assert_eq!(
+            lifecycle.on_start.as_deref(),
+            Some("logger -t psplit started")
+        );
+        assert_eq!(lifecycle.on_error, None);
+        assert_eq!(output.name(), "fuel-telemetry");
+        assert_eq!(output.transforms.names(), ["newline"]);
+        let columns = &config.inputs[0].outputs[1].transforms;
+        assert_eq!(columns.names(), ["columns"]);
+        assert_eq!(columns.apply(b"a,b,c,d,e,f,g\n"), Some(b"a,c,g\n".to_vec()));
+        assert_eq!(config.inputs[0].outputs[1].channel.capacity(), 8);
+        assert_eq!(
+            config.inputs[0].outputs[1].channel.overflow_size(),
+            Some(4096)
+        );
+        assert!(config.inputs[0].outputs[1].channel.retains());
+        assert_eq!(output.channel.overflow_size(), None);
+        assert!(!output.buffer_until_reader);
+        assert_eq!(config.inputs[0].name(), "/tmp/cvAnalogsMapperExt");
+
+        let file_name = temp_dir().join("p_split_bad_queue_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,high_water=120
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let error_matches = match Parser::load_from_file(&file_name) {
+            Err(ParseError::Configuration(s)) => {
+                s.as_str() == "Option 'high_water' must be a percentage between 1 and 100"
+            }
+            _ => false,
+        };
+        assert!(error_matches);
+    }
+    #[test]
+    fn packet_framing_option() {
+        let file_name = temp_dir().join("p_split_packet_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt,framing=packet
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        assert_eq!(config.inputs[0].framing, Framing::Packet);
+        assert!(config.inputs[0].outputs[0].packet);
+
+        let file_name = temp_dir().join("p_split_bad_packet_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt,framing=packet
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,coalesce=true
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let error_matches = match Parser::load_from_file(&file_name) {
+            Err(ParseError::Configuration(s)) => s.starts_with("Option 'coalesce' cannot be used"),
+            _ => false,
+        };
+        assert!(error_matches);
+
+        let file_name = temp_dir().join("p_split_varint_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rb,framing=varint
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wb,coalesce=true
+cvAnalogsMapperExtLogApp=1,wb,oversize=split
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let error_matches = match Parser::load_from_file(&file_name) {
+            Err(ParseError::Configuration(s)) => {
+                s.starts_with("Option 'oversize=split' cannot be used")
+            }
+            _ => false,
+        };
+        assert!(error_matches);
+    }
+    #[test]
+    fn chunk_framing_option() {
+        let load = |options: &str| {
+            let file_name = temp_dir().join("p_split_chunk_config");
+            let file_content = format!(
+                "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,{options}
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wb
+"
+            );
+            fs::write(&file_name, file_content).expect("write");
+            Parser::load_from_file(&file_name)
+        };
+
+        let config = load("rb,framing=chunk,chunk=4096").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].framing, Framing::Chunk(4096));
+        let config = load("rb,framing=chunk").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].framing, Framing::Chunk(CHUNK_SIZE));
+        assert!(load("rt,framing=chunk").is_err());
+        assert!(load("rb,framing=chunk,chunk=0").is_err());
+    }
+    #[test]
+    fn read_sizes() {
+        let load = |options: &str| {
+            let file_name = temp_dir().join("p_split_read_sizes_config");
+            let file_content = format!(
+                "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,{options}
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wb
+"
+            );
+            fs::write(&file_name, file_content).expect("write");
+            Parser::load_from_file(&file_name)
+        };
+
+        let config = load("rt").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].read_buffer, READ_BUFFER);
+        assert_eq!(config.inputs[0].read_chunk, MAX_PACKET);
+        let config = load("rt,read_buffer=1024").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].read_buffer, 1024);
+        let config = load("rb,read_chunk=1048576").expect("Should load configuration ");
+        assert_eq!(config.inputs[0].read_buffer, 1 << 20);
+        assert_eq!(config.inputs[0].read_chunk, 1 << 20);
+        assert!(load("rb,read_buffer=1024").is_err());
+        assert!(load("rt,read_chunk=1024").is_err());
+        assert!(load("rt,read_buffer=0").is_err());
+    }
+    #[test]
+    fn custom_sink_output() {
+        struct Memory(Arc<Mutex<Vec<Vec<u8>>>>);
+        impl Sink for Memory {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn write(&mut self, record: &[u8]) -> io::Result<()> {
+                self.0.lock().unwrap().push(record.to_vec());
+                Ok(())
+            }
+        }
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink_written = Arc::clone(&written);
+        register_sink("memory", move |target| {
+            assert_eq!(target, "fuel");
+            Ok(Box::new(Memory(Arc::clone(&sink_written))))
+        });
+
+        let file_name = temp_dir().join("p_split_sink_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,sink=memory://fuel,queue=4
+cvAnalogsMapperExtLogApp=1,wt,sink=unknown://log
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let error_matches = match Parser::load_from_file(&file_name) {
+            Err(ParseError::Configuration(s)) => s == "No sink registered for 'unknown://log'",
+            _ => false,
+        };
+        assert!(error_matches);
+
+        let file_content = String::from_utf8(file_content.to_vec()).unwrap();
+        let file_content = file_content.replace("unknown://log", "memory://fuel");
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let output = Arc::clone(&config.inputs[0].outputs[0]);
+        for seq in 1..=2 {
+            output.status.reserve();
+            let record = Record {
+                seq,
+                data: format!("record {seq}\n").into_bytes(),
+                received: time::Instant::now(),
+            };
+            assert!(output.channel.try_push(record).is_ok());
+        }
+        output.channel.close();
+
+        let signal = Arc::new(Mutex::new(SIG_RUN));
+        Writer::new(signal, Arc::clone(&output))
+            .run_loop()
+            .expect("run");
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![b"record 1\n".to_vec(), b"record 2\n".to_vec()]
+        );
+        assert_eq!(output.status.settled(), 2);
+    }
+    #[test]
+    fn drain_on_shutdown() {
+        /// Sink of a consumer that stopped reading when `stuck`
+        struct Memory(Arc<Mutex<Vec<Vec<u8>>>>, bool);
+        impl Sink for Memory {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn write(&mut self, record: &[u8]) -> io::Result<()> {
+                if self.1 {
+                    return Err(io::ErrorKind::WouldBlock.into());
+                }
+                self.0.lock().unwrap().push(record.to_vec());
+                Ok(())
+            }
+        }
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink_written = Arc::clone(&written);
+        register_sink("drain", move |target| {
+            Ok(Box::new(Memory(
+                Arc::clone(&sink_written),
+                target == "stuck",
+            )))
+        });
+
+        let file_name = temp_dir().join("p_split_drain_config");
+        for (target, expected) in [("fuel", 3), ("stuck", 0)] {
+            let file_content = format!(
+                "
+[DEFAULT]
+root=/tmp
+drain_timeout=0.3
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,sink=drain://{target},queue=4
+"
+            );
+            fs::write(&file_name, file_content).expect("write");
+            let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+            let output = Arc::clone(&config.inputs[0].outputs[0]);
+            for seq in 1..=3 {
+                output.status.reserve();
+                let record = Record {
+                    seq,
+                    data: format!("record {seq}\n").into_bytes(),
+                    received: time::Instant::now(),
+                };
+                assert!(output.channel.try_push(record).is_ok());
+            }
+
+            // Exit is requested while the records are still queued
+            written.lock().unwrap().clear();
+            let start = time::Instant::now();
+            let signal = Arc::new(Mutex::new(SIG_EXIT));
+            Writer::new(signal, Arc::clone(&output))
+                .run_loop()
+                .expect("run");
+            assert!(start.elapsed() < time::Duration::from_secs(2));
+            assert_eq!(written.lock().unwrap().len(), expected);
+        }
+    }
+    #[test]
+    fn group_all_or_none() {
+        let file_name = temp_dir().join("p_split_group_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,queue=1,group=fuel
+cvAnalogsMapperExtLogApp=1,wt,queue=1,group=fuel,require_json=true
+";
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let input = Arc::clone(&config.inputs[0]);
+        let (fuel, log) = (&input.outputs[0], &input.outputs[1]);
+        let mut reader = Reader::new(Arc::new(Mutex::new(SIG_RUN)), Arc::clone(&input));
+        for output in input.outputs.iter() {
+            reader.send_channels.push(MessageSender {
+                disconnected: false,
+                output: Arc::clone(output),
+            });
+        }
+        let record = |seq: u64, data: &[u8]| Record {
+            seq,
+            data: data.to_vec(),
+            received: time::Instant::now(),
+        };
+
+        // The queue of the fuel output is full, neither output gets the record
+        fuel.status.reserve();
+        assert!(fuel.channel.try_push(record(1, b"{}")).is_ok());
+        reader.send_message(record(2, b"{\"fuel\":12}"));
+        assert!(log.channel.is_empty());
+        assert_eq!((fuel.status.drops(), log.status.drops()), (1, 1));
+        assert_eq!(fuel.channel.try_pop().map(|r| r.seq), Some(1));
+        fuel.status.settle(1);
+        assert!(fuel.channel.is_empty());
+
+        // Filtered out by the log output, the record is filtered for both
+        reader.send_message(record(3, b"fuel=12"));
+        assert!(fuel.channel.is_empty() && log.channel.is_empty());
+        assert_eq!((fuel.status.filters(), log.status.filters()), (1, 1));
+
+        reader.send_message(record(4, b"{\"fuel\":13}"));
+        assert_eq!(fuel.channel.try_pop().map(|r| r.seq), Some(4));
+        assert_eq!(log.channel.try_pop().map(|r| r.seq), Some(4));
+
+        // A disconnected output leaves the group, the other one still gets
+        // the records
+        log.channel.close();
+        reader.send_message(record(5, b"{\"fuel\":14}"));
+        assert!(reader.send_channels[1].disconnected);
+        assert_eq!(fuel.channel.try_pop().map(|r| r.seq), Some(5));
+        assert!(log.channel.is_empty());
+    }
+    #[test]
+    fn resend_after_broken_pipe() {
+        use testing::{Consumer, Harness, Producer};
+
+        let config = "
+[DEFAULT]
+root={root}
+[PIPES]
+canBus=1,rt
+[canBus]
+canBusLogApp=1,wt,queue=8
+";
+        let harness = Harness::spawn(config).expect("spawn");
+        let timeout = time::Duration::from_secs(5);
+        let path = harness.pipe("canBusLogApp").unwrap();
+        let mut producer = Producer::open(harness.pipe("canBus").unwrap(), timeout).unwrap();
+        let mut first = Consumer::open(path).unwrap();
+        producer.write(b"fuel=1\n", timeout).unwrap();
+        assert_eq!(first.collect(7, timeout).unwrap(), b"fuel=1\n");
+
+        // The consumer goes away, the next record hits a broken pipe
+        drop(first);
+        let record: Vec<u8> = [b'x'; 2047].iter().chain(b"\n").copied().collect();
+        producer.write(&record, timeout).unwrap();
+        let input = Arc::clone(&harness.inputs()[0]);
+        let output = Arc::clone(&input.outputs[0]);
+        let start = time::Instant::now();
+        while input.status.records() < 2 && start.elapsed() < timeout {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        thread::sleep(time::Duration::from_millis(200));
+        // Kept for the next consumer, not counted as written
+        assert_eq!((output.status.records(), output.status.queue_len()), (1, 1));
+        producer.write(b"fuel=3\n", timeout).unwrap();
+
+        // The next consumer gets the record whole, then the one after it
+        let mut second = Consumer::open(path).unwrap();
+        let expected: Vec<u8> = record.iter().chain(b"fuel=3\n").copied().collect();
+        assert_eq!(second.collect(expected.len(), timeout).unwrap(), expected);
+        assert_eq!(output.status.drops(), 0);
+    }
+    #[test]
+    fn expire_stale_records() {
+        use testing::{Consumer, Harness, Producer};
+
+        let config = "
+[DEFAULT]
+root={root}
+[PIPES]
+canBus=1,rt
+[canBus]
+canBusLogApp=1,wt,queue=8,ttl=0.3
+";
+        let harness = Harness::spawn(config).expect("spawn");
+        let timeout = time::Duration::from_secs(5);
+        let input = Arc::clone(&harness.inputs()[0]);
+        let output = Arc::clone(&input.outputs[0]);
+        let events = output.events.subscribe();
+        let mut producer = Producer::open(harness.pipe("canBus").unwrap(), timeout).unwrap();
+
+        // Queued while no consumer has the pipe open, for longer than the TTL
+        producer.write(b"fuel=1\nfuel=2\n", timeout).unwrap();
+        let start = time::Instant::now();
+        while input.status.records() < 2 && start.elapsed() < timeout {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        thread::sleep(time::Duration::from_millis(500));
+
+        let mut log = Consumer::open(harness.pipe("canBusLogApp").unwrap()).unwrap();
+        producer.write(b"fuel=3\n", timeout).unwrap();
+        assert_eq!(log.collect(7, timeout).unwrap(), b"fuel=3\n");
+        let late = log.collect(1, time::Duration::from_millis(200)).unwrap();
+        assert!(late.is_empty());
+        assert_eq!(output.status.expirations(), 2);
+
+        let expired: Vec<u64> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::RecordDropped {
+                    seq,
+                    reason: DropReason::Expired,
+                    ..
+                } => Some(seq),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(expired.len(), 2);
+    }
+    #[test]
+    fn coalesce_small_records() {
+        let file_name = temp_dir().join("p_split_coalesce_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,queue=128,coalesce=yes
+";
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let output = Arc::clone(&config.inputs[0].outputs[0]);
+        let record = |seq: u64, len: usize| Record {
+            seq,
+            data: vec![b'x'; len],
+            received: time::Instant::now(),
         };
+        let pending = |record: Record| Pending {
+            record,
+            offset: 0,
+            retransmit: false,
+            records: 1,
+            parts: Vec::new(),
+        };
+        let writer = Writer::new(Arc::new(Mutex::new(SIG_RUN)), Arc::clone(&output));
+
+        // Records of 100 bytes, 40 of them fit in a single write
+        for seq in 2..=50 {
+            assert!(output.channel.try_push(record(seq, 100)).is_ok());
+        }
+        let mut merged = pending(record(1, 100));
+        writer.coalesce(&mut merged);
+        assert_eq!(merged.records, 40);
+        assert_eq!(merged.record.seq, 40);
+        assert_eq!(merged.record.data.len(), 4000);
+        assert!(merged.record.data.len() <= libc::PIPE_BUF);
+        assert_eq!(output.channel.try_pop().map(|r| r.seq), Some(41));
+        while output.channel.try_pop().is_some() {}
+
+        // A record larger than PIPE_BUF is written on its own
+        assert!(output
+            .channel
+            .try_push(record(2, libc::PIPE_BUF + 1))
+            .is_ok());
+        let mut small = pending(record(1, 100));
+        writer.coalesce(&mut small);
+        assert_eq!((small.records, small.record.data.len()), (1, 100));
+        let mut large = pending(output.channel.try_pop().unwrap());
+        assert!(output.channel.try_push(record(3, 100)).is_ok());
+        writer.coalesce(&mut large);
+        assert_eq!(large.records, 1);
+        assert_eq!(large.record.data.len(), libc::PIPE_BUF + 1);
+        assert_eq!(output.channel.try_pop().map(|r| r.seq), Some(3));
+    }
+    #[test]
+    fn recover_from_panics() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use testing::{Consumer, Harness, Producer};
+
+        /// Panics on its first record, in the reader
+        struct Flaky(AtomicBool);
+        impl Transform for Flaky {
+            fn apply(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+                assert!(self.0.swap(true, Ordering::SeqCst), "flaky transform");
+                Some(data)
+            }
+        }
+        /// Panics on its first record, in the writer
+        struct Memory(Arc<Mutex<Vec<u8>>>);
+        impl Sink for Memory {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn write(&mut self, record: &[u8]) -> io::Result<()> {
+                static PANICKED: AtomicBool = AtomicBool::new(false);
+                assert!(PANICKED.swap(true, Ordering::SeqCst), "flaky sink");
+                self.0.lock().unwrap().extend_from_slice(record);
+                Ok(())
+            }
+        }
+        register_transform("flaky", |_| Ok(Box::new(Flaky(AtomicBool::new(false)))));
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink_written = Arc::clone(&written);
+        register_sink("flaky", move |_| {
+            Ok(Box::new(Memory(Arc::clone(&sink_written))))
+        });
+
+        let config = "
+[DEFAULT]
+root={root}
+[PIPES]
+canBus=1,rt
+[canBus]
+canBusFuelApp=1,wt,queue=8,transforms=flaky
+canBusLogApp=1,wt,queue=8,sink=flaky://log
+";
+        let harness = Harness::spawn(config).expect("spawn");
+        let timeout = time::Duration::from_secs(5);
+        let mut fuel = Consumer::open(harness.pipe("canBusFuelApp").unwrap()).unwrap();
+        let mut producer = Producer::open(harness.pipe("canBus").unwrap(), timeout).unwrap();
+
+        // The reader panics on the first record and is started again
+        producer.write(b"fuel=1\n", timeout).unwrap();
+        thread::sleep(time::Duration::from_millis(500));
+        // The writer of the sink panics on the second and is started again
+        producer.write(b"fuel=2\n", timeout).unwrap();
+        assert_eq!(fuel.collect(7, timeout).unwrap(), b"fuel=2\n");
+        producer.write(b"fuel=3\n", timeout).unwrap();
+        assert_eq!(fuel.collect(7, timeout).unwrap(), b"fuel=3\n");
+
+        let start = time::Instant::now();
+        while written.lock().unwrap().is_empty() && start.elapsed() < timeout {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        assert_eq!(written.lock().unwrap().as_slice(), b"fuel=3\n");
+    }
+    #[test]
+    fn report_stalled_outputs() {
+        use testing::{Consumer, Harness, Producer};
+
+        let config = "
+[DEFAULT]
+root={root}
+[PIPES]
+canBus=1,rt
+[canBus]
+canBusLogApp=1,wt,queue=1
+";
+        let harness = Harness::spawn(config).expect("spawn");
+        let timeout = time::Duration::from_secs(5);
+        // Opened but never read, the pipe fills up and the writer blocks
+        let _log = Consumer::open(harness.pipe("canBusLogApp").unwrap()).unwrap();
+        let mut producer = Producer::open(harness.pipe("canBus").unwrap(), timeout).unwrap();
+        let record: Vec<u8> = [b'x'; 4000].iter().chain(b"\n").copied().collect();
+        let output = Arc::clone(&harness.inputs()[0].outputs[0]);
+
+        // Records keep coming, a producer going quiet is not a stall
+        let start = time::Instant::now();
+        while !output.status.is_stalled() && start.elapsed() < status::STALL_AFTER * 3 {
+            assert!(output.status.to_string().contains("stalled: false"));
+            producer.write(&record, timeout).unwrap();
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        assert!(start.elapsed() >= status::STALL_AFTER);
+        assert!(output.status.drops() > 0);
+        let report = output.status.to_string();
+        assert!(report.contains("stalled: true"), "{report}");
+    }
+    #[test]
+    fn lifecycle_hooks() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        /// Yields a single record
+        struct Once(AtomicBool);
+        impl Source for Once {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+                match self.0.swap(true, Ordering::SeqCst) {
+                    false => Ok(Some(b"fuel=1\n".to_vec())),
+                    true => Err(io::ErrorKind::WouldBlock.into()),
+                }
+            }
+        }
+        /// Fails every write
+        struct Full;
+        impl Sink for Full {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn write(&mut self, _record: &[u8]) -> io::Result<()> {
+                Err(io::Error::other("disk full"))
+            }
+        }
+        register_source("once", |_| Ok(Box::new(Once(AtomicBool::new(false)))));
+        register_sink("full", |_| Ok(Box::new(Full)));
+
+        let hooks = temp_dir().join(format!("p_split_hooks_{}", std::process::id()));
+        let _ = fs::remove_file(&hooks);
+        let file_name = temp_dir().join("p_split_hooks_config");
+        let file_content = format!(
+            "
+[DEFAULT]
+root=/tmp
+drain_timeout=0.3
+[PIPES]
+canBus=1,rt,source=once://can0
+[canBus]
+canBusLogApp=1,wt,sink=full://log,exec_on_start=echo $PSPLIT_EVENT >> {hooks},exec_on_stop=echo $PSPLIT_EVENT $PSPLIT_STATUS >> {hooks},exec_on_error=echo $PSPLIT_EVENT $PSPLIT_ERRORS $PSPLIT_ERROR >> {hooks}
+",
+            hooks = hooks.display()
+        );
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let output = Arc::clone(&config.inputs[0].outputs[0]);
+        let signal = Arc::new(Mutex::new(SIG_RUN));
+        let threads = create_splitting_threads(&config.inputs, &signal);
+
+        // The record is written again after every failure
+        let timeout = time::Duration::from_secs(5);
+        let start = time::Instant::now();
+        while output.status.errors() < 3 && start.elapsed() < timeout {
+            thread::sleep(time::Duration::from_millis(20));
+        }
+        assert!(output.status.errors() >= 3);
+        *signal.lock().unwrap() = SIG_EXIT;
+        for handle in threads {
+            handle.join().unwrap().expect("reader");
+        }
 
-        assert_eq!(error_matches, true);
+        // Hooks run on their own, the stop hook last
+        let start = time::Instant::now();
+        let mut lines = Vec::new();
+        while !lines.contains(&"stop ok".to_owned()) && start.elapsed() < timeout {
+            thread::sleep(time::Duration::from_millis(20));
+            let hooked = fs::read_to_string(&hooks).unwrap_or_default();
+            lines = hooked.lines().map(str::to_owned).collect();
+        }
+        lines.sort();
+        // The error hook ran once for the first of the failures
+        assert_eq!(lines, ["error 1 disk full", "start", "stop ok"]);
+        let _ = fs::remove_file(hooks);
+    }
+    #[test]
+    fn custom_source_input() {
+        struct Lines(Arc<Mutex<Vec<Vec<u8>>>>);
+        impl Source for Lines {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+                match self.0.lock().unwrap().pop() {
+                    Some(line) => Ok(Some(line)),
+                    None => Err(io::ErrorKind::WouldBlock.into()),
+                }
+            }
+        }
+        struct Collect(Arc<Mutex<Vec<Vec<u8>>>>);
+        impl Sink for Collect {
+            fn open(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+            fn write(&mut self, record: &[u8]) -> io::Result<()> {
+                self.0.lock().unwrap().push(record.to_vec());
+                Ok(())
+            }
+        }
+        let lines = Arc::new(Mutex::new(vec![b"b\n".to_vec(), b"a\n".to_vec()]));
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let source_lines = Arc::clone(&lines);
+        register_source("lines", move |_| {
+            Ok(Box::new(Lines(Arc::clone(&source_lines))))
+        });
+        let sink_collected = Arc::clone(&collected);
+        register_sink("collect", move |_| {
+            Ok(Box::new(Collect(Arc::clone(&sink_collected))))
+        });
+
+        let file_name = temp_dir().join("p_split_source_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+[PIPES]
+canBus=1,rt,source=lines://can0
+[canBus]
+canBusLogApp=1,wt,sink=collect://log,queue=4
+"
+        .as_bytes();
+
+        {
+            let mut file = File::create(&file_name).expect("create");
+            file.write_all(file_content).expect("write");
+        }
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let signal = Arc::new(Mutex::new(SIG_RUN));
+        let threads = create_splitting_threads(&config.inputs, &signal);
+
+        for _ in 0..100 {
+            if collected.lock().unwrap().len() == 2 {
+                break;
+            }
+            thread::sleep(time::Duration::from_millis(20));
+        }
+        *signal.lock().unwrap() = SIG_EXIT;
+        for handle in threads {
+            handle.join().unwrap().expect("reader");
+        }
+        assert_eq!(
+            *collected.lock().unwrap(),
+            vec![b"a\n".to_vec(), b"b\n".to_vec()]
+        );
+        assert_eq!(config.inputs[0].status.records(), 2);
     }
     #[test]
     fn test_it_works() {
         let file_name = temp_dir().join("pipe_split");
         let file_content = "
 [DEFAULT]
-root=/tmp
+root=/tmp/p_split_it_works
 [PIPES]
 cvAnalogsMapperExt=
 [cvAnalogsMapperExt]
@@ -927,8 +5827,7 @@ cvAnalogsMapperExtFuelApp=
             file.write_all(file_content).expect("write");
         }
 
-        let _handle =
-            thread::spawn(move || -> Result<(), std::io::Error> { split_pipes(&file_name) });
+        let _handle = thread::spawn(move || split_pipes(&file_name));
 
         thread::sleep(time::Duration::from_secs(20))
     }
@@ -0,0 +1,108 @@
+//! Resource limits the splitter applies to itself at startup, so that a
+//! misconfiguration cannot let it starve the applications sharing the
+//! device. With `[DEFAULT] cgroup=<dir>` the process moves to that cgroup v2
+//! directory after its memory and CPU limits are written there, otherwise
+//! the memory limit becomes `RLIMIT_AS`. `open_files_limit` is always
+//! applied as `RLIMIT_NOFILE`.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Resource limits of the process, from the `DEFAULT` section
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Limits {
+    /// cgroup v2 directory the process moves to, `cgroup`
+    pub cgroup: Option<PathBuf>,
+    /// Bytes of memory, `memory_limit`
+    pub memory: Option<u64>,
+    /// Percent of one CPU, `cpu_limit`, only enforced by a cgroup
+    pub cpu: Option<u32>,
+    /// Open file descriptors, `open_files_limit`
+    pub open_files: Option<u64>,
+}
+
+/// Period of the CPU quota written to `cpu.max`, in microseconds
+const CPU_PERIOD: u64 = 100_000;
+
+/// Set both the soft and the hard limit of `resource` to `value`
+fn set_rlimit(resource: libc::__rlimit_resource_t, value: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: value,
+        rlim_max: value,
+    };
+    match unsafe { libc::setrlimit(resource, &limit) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+impl Limits {
+    /// Apply the limits to the running process
+    pub fn apply(&self) -> io::Result<()> {
+        match &self.cgroup {
+            Some(cgroup) => self.join_cgroup(cgroup)?,
+            None => {
+                if let Some(memory) = self.memory {
+                    set_rlimit(libc::RLIMIT_AS, memory)?;
+                }
+            }
+        }
+        if let Some(open_files) = self.open_files {
+            set_rlimit(libc::RLIMIT_NOFILE, open_files)?;
+        }
+        Ok(())
+    }
+
+    /// Write the limits of the cgroup at `cgroup`, created when missing,
+    /// then move the process there
+    fn join_cgroup(&self, cgroup: &Path) -> io::Result<()> {
+        fs::create_dir_all(cgroup)?;
+        let mut controllers = Vec::new();
+        if self.memory.is_some() {
+            controllers.push("+memory");
+        }
+        if self.cpu.is_some() {
+            controllers.push("+cpu");
+        }
+        // Enabling them may be refused, writing the limits then fails
+        if let (Some(parent), false) = (cgroup.parent(), controllers.is_empty()) {
+            let _ = fs::write(parent.join("cgroup.subtree_control"), controllers.join(" "));
+        }
+        if let Some(memory) = self.memory {
+            fs::write(cgroup.join("memory.max"), memory.to_string())?;
+        }
+        if let Some(cpu) = self.cpu {
+            let quota = CPU_PERIOD * cpu as u64 / 100;
+            fs::write(cgroup.join("cpu.max"), format!("{quota} {CPU_PERIOD}"))?;
+        }
+        fs::write(cgroup.join("cgroup.procs"), std::process::id().to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn write_cgroup_limits() {
+        let parent = std::env::temp_dir().join(format!("psplit-cgroup-{}", std::process::id()));
+        let cgroup = parent.join("psplit");
+        let limits = Limits {
+            cgroup: Some(cgroup.clone()),
+            memory: Some(64 << 20),
+            cpu: Some(25),
+            open_files: None,
+        };
+        limits.apply().unwrap();
+
+        let read = |file: &str| fs::read_to_string(cgroup.join(file)).unwrap();
+        assert_eq!(read("memory.max"), "67108864");
+        assert_eq!(read("cpu.max"), "25000 100000");
+        assert_eq!(read("cgroup.procs"), std::process::id().to_string());
+        assert_eq!(
+            fs::read_to_string(parent.join("cgroup.subtree_control")).unwrap(),
+            "+memory +cpu"
+        );
+        let _ = fs::remove_dir_all(parent);
+    }
+}
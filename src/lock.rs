@@ -0,0 +1,133 @@
+//! Advisory lock keeping two splitters off the same topology.
+//!
+//! The lock is taken on the configuration file itself, or on
+//! `psplit-<instance>.lock` in the temporary directory for a named instance,
+//! and is released by the kernel when the process exits. A run of a single
+//! topology of the configuration, `-t <name>`, shares that lock with the runs
+//! of the other topologies and takes `psplit-<configuration>-<name>.lock`,
+//! named after the instance or a hash of the configuration path.
+use std::collections::hash_map::DefaultHasher;
+use std::env::temp_dir;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Lock held for as long as the splitter runs
+pub(crate) struct TopologyLock {
+    /// Locked files, closing them releases the lock
+    _files: Vec<File>,
+}
+
+/// File locked for every topology of the configuration at `config_path`
+/// and `instance`
+fn config_lock_path(config_path: &Path, instance: Option<&str>) -> PathBuf {
+    match instance {
+        Some(instance) => temp_dir().join(format!("psplit-{instance}.lock")),
+        None => config_path.to_path_buf(),
+    }
+}
+
+/// File locked for the topology `topology` of the configuration at
+/// `config_path` and `instance`
+fn topology_lock_path(config_path: &Path, instance: Option<&str>, topology: &str) -> PathBuf {
+    let config = match instance {
+        Some(instance) => instance.to_owned(),
+        None => {
+            let path = fs::canonicalize(config_path).unwrap_or_else(|_| config_path.into());
+            let mut hasher = DefaultHasher::new();
+            path.hash(&mut hasher);
+            format!("{:016x}", hasher.finish())
+        }
+    };
+    temp_dir().join(format!("psplit-{config}-{topology}.lock"))
+}
+
+/// Lock `path` with the `flock` operation `operation`, failing if another
+/// process holds a conflicting lock
+fn lock_file(path: &Path, operation: libc::c_int) -> io::Result<File> {
+    // flock works on read-only descriptors, the configuration may not be
+    // writable
+    let file = match File::open(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?,
+        file => file?,
+    };
+
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        return Err(match error.kind() {
+            io::ErrorKind::WouldBlock => io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "another psplit is already splitting the topology of {}",
+                    path.display()
+                ),
+            ),
+            _ => error,
+        });
+    }
+    Ok(file)
+}
+
+/// Take the lock of the topology `topology` of the configuration at
+/// `config_path` and `instance`, or of every topology when `None`, failing
+/// if another process splits it
+pub(crate) fn acquire(
+    config_path: &Path,
+    instance: Option<&str>,
+    topology: Option<&str>,
+) -> io::Result<TopologyLock> {
+    let config = config_lock_path(config_path, instance);
+    let Some(topology) = topology else {
+        return Ok(TopologyLock {
+            _files: vec![lock_file(&config, libc::LOCK_EX)?],
+        });
+    };
+    let shared = lock_file(&config, libc::LOCK_SH)?;
+    let own = lock_file(
+        &topology_lock_path(config_path, instance, topology),
+        libc::LOCK_EX,
+    )?;
+    Ok(TopologyLock {
+        _files: vec![shared, own],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn second_lock_fails() {
+        let path = Path::new("/unused");
+        let instance = Some("p_split_lock_test");
+        let lock = acquire(path, instance, None).expect("first lock");
+        let error = acquire(path, instance, None).err().expect("second lock");
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        drop(lock);
+        acquire(path, instance, None).expect("lock after release");
+    }
+
+    #[test]
+    fn topology_locks() {
+        let path = temp_dir().join("p_split_lock_config");
+        fs::write(&path, b"").unwrap();
+        for instance in [None, Some("p_split_lock_topology")] {
+            let gateway = acquire(&path, instance, Some("gateway")).expect("gateway");
+            let sensor = acquire(&path, instance, Some("sensor")).expect("sensor");
+            assert!(acquire(&path, instance, Some("gateway")).is_err());
+            // Every topology includes those already split
+            assert!(acquire(&path, instance, None).is_err());
+            drop((gateway, sensor));
+
+            let all = acquire(&path, instance, None).expect("every topology");
+            assert!(acquire(&path, instance, Some("sensor")).is_err());
+            drop(all);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Log file with size and time based rotation.
+//!
+//! The file at `path` is rotated to `path.1`, `path.1` to `path.2` and so on,
+//! keeping [`LogRotation::keep`] old files. `SIGUSR2` and `SIGHUP` make the
+//! file reopen on the next message, for external tools that move the file
+//! away.
+//!
+//! A daemon points its standard streams at the log file as well, so output
+//! of its own or of libraries lands there, following the file as it is
+//! rotated or reopened.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set by `SIGUSR2` or `SIGHUP`, the log file is reopened on the next message
+static REOPEN: AtomicBool = AtomicBool::new(false);
+
+/// `SIGUSR2` handler
+extern "C" fn request_reopen(_: libc::c_int) {
+    reopen();
+}
+
+/// Reopen the log file on the next message
+pub(crate) fn reopen() {
+    REOPEN.store(true, Ordering::SeqCst);
+}
+
+/// When a log file is rotated
+#[derive(Clone, Debug)]
+pub struct LogRotation {
+    /// Rotate once the file reaches this many bytes
+    pub max_size: Option<u64>,
+    /// Rotate once the file is this old
+    pub interval: Option<Duration>,
+    /// Rotated files kept
+    pub keep: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> LogRotation {
+        LogRotation {
+            max_size: Some(10 << 20),
+            interval: None,
+            keep: 5,
+        }
+    }
+}
+
+/// Log file being written to
+pub(crate) struct LogFile {
+    /// Path of the current file
+    path: PathBuf,
+    /// Rotation policy
+    rotation: LogRotation,
+    /// Current file
+    file: File,
+    /// Size of the current file
+    size: u64,
+    /// When the current file was started
+    opened: Instant,
+    /// Standard output and error are the current file
+    stdio: bool,
+}
+
+impl LogFile {
+    /// Append to the file at `path` and reopen it on `SIGUSR2`
+    pub fn open<P: AsRef<Path>>(path: P, rotation: LogRotation) -> io::Result<LogFile> {
+        let path = path.as_ref().to_path_buf();
+        let file = Self::append(&path)?;
+        let size = file.metadata()?.len();
+        unsafe {
+            libc::signal(
+                libc::SIGUSR2,
+                request_reopen as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+        Ok(LogFile {
+            path,
+            rotation,
+            file,
+            size,
+            opened: Instant::now(),
+            stdio: false,
+        })
+    }
+
+    /// Read standard input from `/dev/null` and write standard output and
+    /// error to the file, now and once it is rotated or reopened
+    pub fn redirect_stdio(&mut self) -> io::Result<()> {
+        let null = File::open("/dev/null")?;
+        if unsafe { libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.stdio = true;
+        self.dup_stdio()
+    }
+
+    /// Make standard output and error the current file
+    fn dup_stdio(&self) -> io::Result<()> {
+        if !self.stdio {
+            return Ok(());
+        }
+        io::stdout().flush()?;
+        for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            if unsafe { libc::dup2(self.file.as_raw_fd(), fd) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Open `path` for appending
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Path of the rotated file `index`
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    /// Whether the current file is due for rotation
+    fn should_rotate(&self) -> bool {
+        self.rotation.max_size.is_some_and(|max| self.size >= max)
+            || self
+                .rotation
+                .interval
+                .is_some_and(|interval| self.opened.elapsed() >= interval)
+    }
+
+    /// Shift the rotated files and start a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.rotation.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.rotation.keep).rev() {
+                match fs::rename(self.rotated(index), self.rotated(index + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.reopen()
+    }
+
+    /// Start writing to the file at `path` again
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = Self::append(&self.path)?;
+        self.size = self.file.metadata()?.len();
+        self.opened = Instant::now();
+        self.dup_stdio()
+    }
+
+    /// Write a line, rotating or reopening the file first when due
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if REOPEN.swap(false, Ordering::SeqCst) {
+            self.reopen()?;
+        }
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\n")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn rotate_on_size() {
+        let dir = temp_dir().join("p_split_log");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("psplit.log");
+
+        let rotation = LogRotation {
+            max_size: Some(6),
+            interval: None,
+            keep: 2,
+        };
+        let mut log = LogFile::open(&path, rotation).expect("open");
+        for line in ["first", "second", "third", "fourth"] {
+            log.write_line(line).expect("write");
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(log.rotated(1)).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(log.rotated(2)).unwrap(), "second\n");
+        assert!(!log.rotated(3).exists());
+    }
+}
@@ -1,13 +1,28 @@
-use psplit::split_pipes;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
-use clap::Parser;
+use psplit::{
+    log_to_file, log_to_stderr, migrate_config, no_new_privs, redirect_stdio, self_test,
+    set_drain_timeout, set_strict, set_tap, soak, split_topology, topology_graph, ExitStatus,
+    GraphFormat, LogRotation, SoakOptions,
+};
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Absolute Path to configuration file
-    #[arg(short, long, value_name = "FILE", default_value_t = String::from("/usr/cvapps/pipes/config_splitter.ini"))]
-    config: String,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        default_value = "/usr/cvapps/pipes/config_splitter.ini"
+    )]
+    config: PathBuf,
 
     /// Log level
     #[arg(short, long, action = clap::ArgAction::Count)]
@@ -16,22 +31,248 @@ struct Args {
     /// Auto reload on config change
     #[arg(short, long)]
     reload: bool,
+
+    /// Topology to run, `[PIPES.<NAME>]`, every topology when omitted
+    #[arg(short, long, value_name = "NAME")]
+    topology: Option<String>,
+
+    /// Write logs to this file instead of standard error, reopened on SIGHUP or SIGUSR2
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Run attached to the terminal, logging to standard error, coloured on
+    /// a terminal, unless a log file is given. The default
+    #[arg(long, conflicts_with = "daemon")]
+    foreground: bool,
+
+    /// Redirect standard streams to the log file, for a service manager
+    /// starting the splitter: standard input reads `/dev/null`, standard
+    /// output and error go to the log file, which SIGUSR2 reopens. The
+    /// process does not fork nor leave its session
+    #[arg(long, requires = "log_file")]
+    daemon: bool,
+
+    /// Rotate the log file once it reaches this many bytes, 0 disables
+    #[arg(long, value_name = "BYTES", default_value_t = 10 << 20)]
+    log_max_size: u64,
+
+    /// Rotate the log file once it is this many seconds old
+    #[arg(long, value_name = "SECS")]
+    log_rotate: Option<u64>,
+
+    /// Rotated log files to keep
+    #[arg(long, value_name = "COUNT", default_value_t = 5)]
+    log_keep: usize,
+
+    /// Seconds writers keep flushing queued records on shutdown, overriding
+    /// `drain_timeout` of the configuration
+    #[arg(long, value_name = "SECS")]
+    drain_timeout: Option<u64>,
+
+    /// Forbid the splitter and the hooks it runs from gaining privileges
+    /// through set-user-ID binaries or file capabilities
+    #[arg(long)]
+    no_new_privs: bool,
+
+    /// Fail on warnings about the configuration, unknown options, sections
+    /// no input reads and names in another case, like `strict=true`
+    #[arg(long)]
+    strict: bool,
+
+    /// Mirror every record read over UDP to this address, for live debugging
+    #[arg(long, value_name = "HOST:PORT")]
+    tap: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the inputs, outputs and transforms of the configuration as a graph
+    Graph {
+        /// Graph language, `dot` (Graphviz) or `mermaid`
+        #[arg(long, value_name = "FORMAT", default_value = "dot", value_parser = ["dot", "mermaid"])]
+        format: String,
+    },
+    /// Push records through a scratch topology and check every output gets them
+    Selftest {
+        /// Records to push through
+        #[arg(long, value_name = "COUNT", default_value_t = 100)]
+        records: usize,
+    },
+    /// Rewrite the configuration file in the current schema version, without
+    /// its comments, keeping the original as <CONFIG>.bak
+    MigrateConfig,
+    /// Print the completion script of a shell
+    Completions {
+        /// Shell the script is for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the manual page, in roff
+    Man,
+    /// Run a scratch topology under chaos and check its invariants, for
+    /// nightly runs against release builds
+    #[command(hide = true)]
+    Soak {
+        /// Seconds the chaos lasts
+        #[arg(long, value_name = "SECS", default_value_t = 60)]
+        duration: u64,
+        /// Outputs of the scratch topology
+        #[arg(long, value_name = "COUNT", default_value_t = 3, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        outputs: usize,
+        /// Longest milliseconds a consumer stays away or the producer pauses
+        #[arg(long, value_name = "MILLIS", default_value_t = 2000)]
+        max_pause: u64,
+        /// Mean milliseconds between two chaos events
+        #[arg(long, value_name = "MILLIS", default_value_t = 200)]
+        interval: u64,
+        /// Resident memory not to exceed, in MiB
+        #[arg(long, value_name = "MIB", default_value_t = 256)]
+        max_rss: u64,
+        /// Seed of the chaos, to replay a run
+        #[arg(long)]
+        seed: Option<u64>,
+    },
 }
 
-fn run_with_reload(_cli: &Args) -> Result<(), std::io::Error> {
+fn run_with_reload(_cli: &Args) -> ExitStatus {
     todo!()
 }
 
-fn run(cli: &Args) -> Result<(), std::io::Error> {
-    split_pipes(&cli.config)
+fn run(cli: &Args) -> ExitStatus {
+    split_topology(&cli.config, cli.topology.as_deref())
+}
+
+/// Exit status of a command that failed with `e`, unreadable configurations
+/// being told apart from other errors
+fn failed(e: io::Error) -> ExitStatus {
+    eprintln!("Error: {e}");
+    match e.kind() {
+        io::ErrorKind::InvalidData => ExitStatus::ConfigError,
+        _ => ExitStatus::RuntimeError,
+    }
+}
+
+/// Exit status of a command returning `result`
+fn finished(result: Result<(), io::Error>) -> ExitStatus {
+    result.map_or_else(failed, |_| ExitStatus::Clean)
+}
+
+fn graph(cli: &Args, format: &str) -> Result<(), std::io::Error> {
+    let format = match format {
+        "mermaid" => GraphFormat::Mermaid,
+        _ => GraphFormat::Dot,
+    };
+    print!(
+        "{}",
+        topology_graph(&cli.config, cli.topology.as_deref(), format)?
+    );
+    Ok(())
+}
+
+fn migrate(cli: &Args) -> Result<(), std::io::Error> {
+    let applied = migrate_config(&cli.config)?;
+    if applied.is_empty() {
+        println!("{} is current", cli.config.display());
+    }
+    for summary in applied {
+        println!("{}: {summary}", cli.config.display());
+    }
+    Ok(())
+}
+
+fn completions(shell: Shell) -> Result<(), std::io::Error> {
+    let mut command = Args::command();
+    let name = command.get_name().to_owned();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, name, &mut script);
+    io::stdout().write_all(&script)
+}
+
+fn man() -> Result<(), std::io::Error> {
+    clap_mangen::Man::new(Args::command()).render(&mut io::stdout())
 }
 
-fn main() -> Result<(), std::io::Error> {
+fn main() -> ExitCode {
     let cli = Args::parse();
-    
+    ExitCode::from(execute(&cli).code())
+}
+
+fn execute(cli: &Args) -> ExitStatus {
+    // Only one of the two can be set, neither means foreground
+    let foreground = cli.foreground || !cli.daemon;
+    if foreground {
+        log_to_stderr();
+    }
+
+    if cli.no_new_privs {
+        if let Err(e) = no_new_privs() {
+            return failed(e);
+        }
+    }
+
+    if cli.strict {
+        set_strict();
+    }
+
+    match &cli.command {
+        Some(Command::Graph { format }) => return finished(graph(cli, format)),
+        Some(Command::Selftest { records }) => return finished(self_test(*records)),
+        Some(Command::MigrateConfig) => return finished(migrate(cli)),
+        Some(Command::Completions { shell }) => return finished(completions(*shell)),
+        Some(Command::Man) => return finished(man()),
+        Some(Command::Soak {
+            duration,
+            outputs,
+            max_pause,
+            interval,
+            max_rss,
+            seed,
+        }) => {
+            let options = SoakOptions {
+                duration: Duration::from_secs(*duration),
+                outputs: *outputs,
+                max_pause: Duration::from_millis(*max_pause),
+                interval: Duration::from_millis(*interval),
+                max_rss: max_rss << 20,
+                seed: *seed,
+            };
+            return finished(soak(&options));
+        }
+        None => {}
+    }
+
+    if let Some(log_file) = &cli.log_file {
+        let rotation = LogRotation {
+            max_size: Some(cli.log_max_size).filter(|size| *size > 0),
+            interval: cli.log_rotate.map(Duration::from_secs),
+            keep: cli.log_keep,
+        };
+        if let Err(e) = log_to_file(log_file, rotation) {
+            return failed(e);
+        }
+        if !foreground {
+            if let Err(e) = redirect_stdio() {
+                return failed(e);
+            }
+        }
+    }
+
+    if let Some(tap) = &cli.tap {
+        if let Err(e) = set_tap(Some(tap)) {
+            return failed(e);
+        }
+    }
+
+    if let Some(drain_timeout) = cli.drain_timeout {
+        set_drain_timeout(Duration::from_secs(drain_timeout));
+    }
+
     if cli.reload {
-        run_with_reload(&cli)
+        run_with_reload(cli)
     } else {
-        run(&cli)
+        run(cli)
     }
 }
@@ -0,0 +1,191 @@
+//! Schema versions of the configuration, `[DEFAULT] version=<n>`, a file
+//! without it being version 1. A file of an older version is migrated when
+//! loaded, one version after the other, and `psplit migrate-config` writes
+//! the migrated document back, so the file is of the current version.
+//!
+//! Migrations:
+//!
+//! - 1 to 2: sections are written `[DEFAULT]`, `[PIPES]`, `[PIPES.<name>]`
+//!   and as the input they describe, settings of `[DEFAULT]` in lowercase.
+//!   Names in another case are still read unless `lenient=no`.
+use ini::Ini;
+
+use crate::ParseError;
+
+/// Version of the schema written by `migrate-config`
+pub(crate) const CURRENT_VERSION: u32 = 2;
+
+/// A change of the schema from one version to the next
+struct Migration {
+    /// What the migration changes
+    summary: &'static str,
+    /// The document of the previous version in the next
+    apply: fn(&Ini) -> Ini,
+}
+
+/// Migrations, the first from version 1 to 2
+const MIGRATIONS: &[Migration] = &[Migration {
+    summary: "section and setting names in canonical case",
+    apply: |conf| canonical_names(conf).0,
+}];
+
+/// Version of the schema of `conf`, `[DEFAULT] version`
+pub(crate) fn version(conf: &Ini) -> Result<u32, ParseError> {
+    let Some(value) = conf.get_from(Some("DEFAULT"), "version") else {
+        return Ok(1);
+    };
+    match value.parse() {
+        Ok(version) if (1..=CURRENT_VERSION).contains(&version) => Ok(version),
+        Ok(version) if version > CURRENT_VERSION => Err(ParseError::Configuration(format!(
+            "configuration version {version} is newer than the supported {CURRENT_VERSION}"
+        ))),
+        _ => Err(ParseError::Configuration(format!(
+            "Invalid value '{value}' for setting 'version'"
+        ))),
+    }
+}
+
+/// `conf` migrated to the current version, with the summaries of the
+/// migrations applied
+pub(crate) fn upgrade(conf: Ini) -> Result<(Ini, Vec<&'static str>), ParseError> {
+    let from = version(&conf)?;
+    let mut conf = conf;
+    let mut applied = Vec::new();
+    for migration in &MIGRATIONS[from as usize - 1..] {
+        conf = (migration.apply)(&conf);
+        applied.push(migration.summary);
+    }
+    if !applied.is_empty() {
+        conf.with_section(Some("DEFAULT"))
+            .set("version", CURRENT_VERSION.to_string());
+    }
+    Ok((conf, applied))
+}
+
+/// Topology of the section `name` of the form `PIPES.<topology>`, the
+/// prefix in any case
+fn topology_of(name: &str) -> Option<&str> {
+    let prefix = "PIPES.";
+    name.get(..prefix.len())
+        .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+        .then(|| &name[prefix.len()..])
+}
+
+/// Canonical name of the section `name`: `DEFAULT`, `PIPES` and
+/// `PIPES.<topology>` in any case, and input sections in the case of the
+/// input they describe
+fn canonical_section(name: &str, inputs: &[String]) -> Option<String> {
+    for fixed in ["DEFAULT", "PIPES"] {
+        if name.eq_ignore_ascii_case(fixed) {
+            return Some(fixed.to_owned());
+        }
+    }
+    if let Some(topology) = topology_of(name) {
+        return Some(format!("PIPES.{topology}"));
+    }
+    if inputs.iter().any(|input| input == name) {
+        return None;
+    }
+    inputs
+        .iter()
+        .find(|input| input.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+/// `conf` with sections and `DEFAULT` settings named as the parser expects,
+/// and the first name that was not, as a message
+pub(crate) fn canonical_names(conf: &Ini) -> (Ini, Option<String>) {
+    let inputs: Vec<String> = conf
+        .iter()
+        .filter(|(name, _)| {
+            name.is_some_and(|name| {
+                name.eq_ignore_ascii_case("PIPES") || topology_of(name).is_some()
+            })
+        })
+        .flat_map(|(_, section)| section.iter().map(|(input, _)| input.to_owned()))
+        .collect();
+
+    let mut normalized = Ini::new();
+    let mut renamed = None;
+    for (name, section) in conf.iter() {
+        let canonical = name.and_then(|name| canonical_section(name, &inputs));
+        let target = canonical.clone().or(name.map(str::to_owned));
+        if let (Some(name), Some(canonical)) = (name, &canonical) {
+            if name != canonical && renamed.is_none() {
+                renamed = Some(format!(
+                    "section '[{name}]' must be written '[{canonical}]'"
+                ));
+            }
+        }
+        let is_default = target.as_deref() == Some("DEFAULT");
+        let properties = normalized
+            .entry(target)
+            .or_insert_with(ini::Properties::new);
+        for (key, value) in section.iter() {
+            if !is_default {
+                properties.append(key, value);
+                continue;
+            }
+            let lower = key.to_lowercase();
+            if lower != key && renamed.is_none() {
+                renamed = Some(format!("setting '{key}' must be written '{lower}'"));
+            }
+            properties.append(lower, value);
+        }
+    }
+    (normalized, renamed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn upgrade_to_current() {
+        let conf = Ini::load_from_str(
+            "
+[Default]
+Root=/tmp
+[pipes]
+cvAnalogsMapperExt=1,rt
+[cvanalogsmapperext]
+cvAnalogsMapperExtFuelApp=1,wt
+",
+        )
+        .unwrap();
+        assert_eq!(version(&conf).unwrap(), 1);
+        let (conf, applied) = upgrade(conf).unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(version(&conf).unwrap(), CURRENT_VERSION);
+        assert_eq!(conf.get_from(Some("DEFAULT"), "root"), Some("/tmp"));
+        assert!(conf.section(Some("cvAnalogsMapperExt")).is_some());
+
+        // A current document is left alone
+        let (_, applied) = upgrade(conf).unwrap();
+        assert!(applied.is_empty());
+
+        let newer = Ini::load_from_str("[DEFAULT]\nversion=3\n").unwrap();
+        assert!(version(&newer).is_err());
+        let invalid = Ini::load_from_str("[DEFAULT]\nversion=two\n").unwrap();
+        assert!(version(&invalid).is_err());
+    }
+
+    #[test]
+    fn non_ascii_sections() {
+        // Unicode case mapping turns these into PIPES.X, the parser does not
+        let conf = Ini::load_from_str(
+            "
+[pipes.gateway]
+fuel=1,rt
+[pıpeſ.x]
+[Pipes.sensör]
+can=1,rt
+",
+        )
+        .unwrap();
+        let (conf, _) = canonical_names(&conf);
+        assert!(conf.section(Some("PIPES.gateway")).is_some());
+        assert!(conf.section(Some("pıpeſ.x")).is_some());
+        assert!(conf.section(Some("PIPES.sensör")).is_some());
+    }
+}
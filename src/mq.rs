@@ -0,0 +1,184 @@
+//! POSIX message queue endpoints, `source=mq://<name>` and `sink=mq://<name>`,
+//! for applications exchanging messages through `mq_send`/`mq_receive`
+//! rather than FIFOs. Every message is one record, so message boundaries
+//! are kept end to end. The queue `/<name>` is created with the system
+//! defaults when missing.
+use std::ffi::CString;
+use std::io;
+use std::os::fd::RawFd;
+
+use crate::sink::Sink;
+use crate::source::Source;
+
+/// Descriptor of an open queue
+struct Queue {
+    /// Name of the queue, with its leading `/`
+    name: CString,
+    /// Descriptor, -1 while closed
+    mqd: libc::mqd_t,
+}
+
+impl Queue {
+    /// Queue configured as `<name>`, a leading `/` being optional
+    fn new(target: &str) -> io::Result<Queue> {
+        let name = target.strip_prefix('/').unwrap_or(target);
+        if name.is_empty() || name.contains('/') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid message queue name '{target}'"),
+            ));
+        }
+        let name = CString::new(format!("/{name}"))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Queue { name, mqd: -1 })
+    }
+
+    /// Open the queue for `access`, without blocking
+    fn open(&mut self, access: libc::c_int) -> io::Result<()> {
+        let flags = access | libc::O_CREAT | libc::O_NONBLOCK | libc::O_CLOEXEC;
+        let mode: libc::c_uint = 0o644;
+        let attributes: *mut libc::mq_attr = std::ptr::null_mut();
+        let mqd = unsafe { libc::mq_open(self.name.as_ptr(), flags, mode, attributes) };
+        if mqd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.mqd = mqd;
+        Ok(())
+    }
+
+    /// Largest message of the queue
+    fn message_size(&self) -> io::Result<usize> {
+        let mut attributes: libc::mq_attr = unsafe { std::mem::zeroed() };
+        match unsafe { libc::mq_getattr(self.mqd, &mut attributes) } {
+            0 => Ok(attributes.mq_msgsize as usize),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn close(&mut self) {
+        if self.mqd >= 0 {
+            unsafe { libc::mq_close(self.mqd) };
+            self.mqd = -1;
+        }
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Sink sending every record as a message
+pub(crate) struct MqSink(Queue);
+
+impl MqSink {
+    pub fn new(target: &str) -> io::Result<MqSink> {
+        Queue::new(target).map(MqSink)
+    }
+}
+
+impl Sink for MqSink {
+    fn open(&mut self) -> io::Result<()> {
+        self.0.open(libc::O_WRONLY)
+    }
+
+    fn write(&mut self, record: &[u8]) -> io::Result<()> {
+        let sent = unsafe {
+            libc::mq_send(
+                self.0.mqd,
+                record.as_ptr() as *const libc::c_char,
+                record.len(),
+                0,
+            )
+        };
+        match sent {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn close(&mut self) {
+        self.0.close()
+    }
+
+    fn ready_fd(&self) -> Option<RawFd> {
+        Some(self.0.mqd)
+    }
+}
+
+/// Source reading every message as a record
+pub(crate) struct MqSource {
+    queue: Queue,
+    /// Receive buffer, as large as the largest message
+    buffer: Vec<u8>,
+}
+
+impl MqSource {
+    pub fn new(target: &str) -> io::Result<MqSource> {
+        Ok(MqSource {
+            queue: Queue::new(target)?,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl Source for MqSource {
+    fn open(&mut self) -> io::Result<()> {
+        self.queue.open(libc::O_RDONLY)?;
+        self.buffer = vec![0; self.queue.message_size()?];
+        Ok(())
+    }
+
+    fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let received = unsafe {
+            libc::mq_receive(
+                self.queue.mqd,
+                self.buffer.as_mut_ptr() as *mut libc::c_char,
+                self.buffer.len(),
+                std::ptr::null_mut(),
+            )
+        };
+        match received {
+            len if len >= 0 => Ok(Some(self.buffer[..len as usize].to_vec())),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn close(&mut self) {
+        self.queue.close()
+    }
+
+    fn ready_fd(&self) -> Option<RawFd> {
+        Some(self.queue.mqd)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sink::SinkTarget;
+    use crate::source::SourceTarget;
+
+    #[test]
+    fn send_and_receive() {
+        assert!(MqSink::new("a/b").is_err());
+        let name = format!("psplit-mq-{}", std::process::id());
+        let uri = format!("mq://{name}");
+        let mut sink = SinkTarget::resolve_sink(&uri).unwrap().open_sink().unwrap();
+        let mut source = SourceTarget::resolve_source(&uri)
+            .unwrap()
+            .open_source()
+            .unwrap();
+
+        let error = source.read().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        sink.write(b"fuel=12").unwrap();
+        sink.write(b"speed=80\nrpm=900").unwrap();
+        assert_eq!(source.read().unwrap().unwrap(), b"fuel=12");
+        assert_eq!(source.read().unwrap().unwrap(), b"speed=80\nrpm=900");
+
+        let name = CString::new(format!("/{name}")).unwrap();
+        unsafe { libc::mq_unlink(name.as_ptr()) };
+    }
+}
@@ -0,0 +1,103 @@
+//! Placeholders of output paths. `{hostname}` and `{input}`, the name of the
+//! input feeding the output, are resolved once at startup. `{date}`, the
+//! local date as `YYYY-MM-DD`, and `strftime` patterns such as `%H` are
+//! resolved whenever the output is opened: the writer closes the output and
+//! moves to the new path once the time crosses a boundary of the pattern,
+//! every hour for `fuel-%Y%m%d-%H` for instance. A literal `%` is written
+//! `%%` in such a path.
+use std::ffi::CString;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the host
+pub(crate) fn hostname() -> String {
+    let mut name = [0u8; 256];
+    match unsafe { libc::gethostname(name.as_mut_ptr() as *mut libc::c_char, name.len()) } {
+        0 => {
+            let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..len]).into_owned()
+        }
+        _ => "localhost".to_owned(),
+    }
+}
+
+/// `path` with `{hostname}` and `{input}` resolved, `input` being the name
+/// of the input feeding the output
+pub(crate) fn expand(path: &str, input: &str) -> String {
+    let mut path = path.replace("{input}", input);
+    if path.contains("{hostname}") {
+        path = path.replace("{hostname}", &hostname());
+    }
+    path
+}
+
+/// `at` in local time as described by the `strftime` `format`
+pub(crate) fn format_local(at: SystemTime, format: &str) -> String {
+    let secs = at
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0) as libc::time_t;
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+    let mut buffer = [0u8; 512];
+    let len = unsafe {
+        let mut local: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut local);
+        libc::strftime(
+            buffer.as_mut_ptr() as *mut libc::c_char,
+            buffer.len(),
+            format.as_ptr(),
+            &local,
+        )
+    };
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
+
+/// Output path depending on the time it is opened at
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TimedPath {
+    template: String,
+}
+
+impl TimedPath {
+    /// Template of `path`, `None` when it does not depend on the time
+    pub fn parse(path: &str) -> Option<TimedPath> {
+        (path.contains("{date}") || path.contains('%')).then(|| TimedPath {
+            template: path.replace("{date}", "%Y-%m-%d"),
+        })
+    }
+
+    /// Path at the time `at`
+    pub fn resolve(&self, at: SystemTime) -> String {
+        format_local(at, &self.template)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn expand_placeholders() {
+        let path = expand("/tmp/{input}-{hostname}", "fuel");
+        assert_eq!(path, format!("/tmp/fuel-{}", hostname()));
+        assert!(TimedPath::parse(&path).is_none());
+
+        let dated = TimedPath::parse("/tmp/fuel-{date}").unwrap();
+        let at = UNIX_EPOCH + Duration::from_secs(86400 * 365);
+        assert_eq!(
+            dated.resolve(at),
+            format!("/tmp/fuel-{}", format_local(at, "%Y-%m-%d"))
+        );
+        let today = dated.resolve(SystemTime::now());
+        assert_eq!(today.len(), "/tmp/fuel-YYYY-MM-DD".len());
+        assert_ne!(today, dated.resolve(at));
+
+        let hourly = TimedPath::parse("/tmp/fuel-{date}-%H").unwrap();
+        let hour = hourly.resolve(at);
+        assert_eq!(hour.len(), "/tmp/fuel-YYYY-MM-DD-HH".len());
+        assert_eq!(hour, hourly.resolve(at + Duration::from_secs(1)));
+        assert_ne!(hour, hourly.resolve(at + Duration::from_secs(3600)));
+    }
+}
@@ -0,0 +1,115 @@
+//! Blocking open of an output, `open=wait`: the FIFO is opened without
+//! `O_NONBLOCK` on a thread of its own, which the kernel wakes as soon as a
+//! consumer opens the other end, rather than the writer retrying a
+//! non-blocking open every `retry` interval. The writer waits for the open
+//! a poll interval at a time, so it still sees exit requests.
+//!
+//! An open still blocked when the writer gives up on it is completed by
+//! opening the read end for an instant, so the thread does not outlive it.
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Open of an output FIFO waiting for a consumer
+pub(crate) struct BlockingOpen {
+    /// Path of the FIFO being opened
+    path: String,
+    /// Result of the open, once a consumer came
+    opened: Receiver<io::Result<File>>,
+    /// The result was received
+    done: bool,
+}
+
+impl BlockingOpen {
+    /// Start opening the FIFO at `path` for writing
+    pub fn start(path: &str) -> io::Result<BlockingOpen> {
+        let (sender, opened) = mpsc::channel();
+        let target = path.to_owned();
+        thread::Builder::new()
+            .name("psplit-open".into())
+            .spawn(move || {
+                let _ = sender.send(OpenOptions::new().append(true).open(&target));
+            })?;
+        Ok(BlockingOpen {
+            path: path.to_owned(),
+            opened,
+            done: false,
+        })
+    }
+
+    /// The FIFO opened by a consumer within `timeout`, non-blocking like a
+    /// FIFO opened with `O_NONBLOCK`, `None` while no consumer came
+    pub fn wait(&mut self, timeout: Duration) -> Option<io::Result<File>> {
+        let opened = match self.opened.recv_timeout(timeout) {
+            Ok(opened) => opened,
+            Err(RecvTimeoutError::Timeout) => return None,
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::other("open thread exited without a result"))
+            }
+        };
+        self.done = true;
+        Some(opened.and_then(|file| {
+            let fd = file.as_raw_fd();
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(file)
+        }))
+    }
+}
+
+impl Drop for BlockingOpen {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        // Be the consumer the open waits for, the file it returns is closed
+        // as nobody receives it
+        let _ = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Writer;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn wait_for_consumer() {
+        let path = temp_dir().join(format!("p_split_open_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        Writer::create(&path, None).unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut open = BlockingOpen::start(path).unwrap();
+        assert!(open.wait(Duration::from_millis(50)).is_none());
+        let consumer = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .unwrap();
+        let file = open
+            .wait(Duration::from_secs(5))
+            .expect("consumer came")
+            .unwrap();
+        let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+        assert_ne!(flags & libc::O_NONBLOCK, 0);
+
+        // An open given up on is completed rather than left blocked
+        drop((consumer, file));
+        let mut abandoned = BlockingOpen::start(path).unwrap();
+        assert!(abandoned.wait(Duration::from_millis(50)).is_none());
+        drop(abandoned);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,214 @@
+//! `key=value` options following `enabled,mode` in a pipe configuration value.
+//!
+//! Values are split on commas. Double quotes keep commas, `=` and leading or
+//! trailing spaces in a value, `prefix=" fuel, "` for instance, a doubled
+//! quote standing for a quote within them. Spaces around unquoted text are
+//! dropped.
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ParseError;
+
+/// Options of a single pipe, in configuration order
+#[derive(Default)]
+pub(crate) struct PipeOptions {
+    entries: Vec<(String, String)>,
+}
+
+/// Split a pipe configuration value on the commas outside quotes
+pub(crate) fn tokenize(value: &str) -> Result<Vec<&str>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                tokens.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quoted {
+        return Err(ParseError::Configuration(format!(
+            "Unterminated quote in '{value}'"
+        )));
+    }
+    tokens.push(&value[start..]);
+    Ok(tokens)
+}
+
+/// `token` split at its first `=` outside quotes
+fn split_assignment(token: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in token.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '=' if !quoted => return Some((&token[..i], &token[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `text` without its quotes, a doubled quote within them being kept once
+fn unquote(text: &str) -> String {
+    let mut unquoted = String::with_capacity(text.len());
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                unquoted.push('"');
+            }
+            '"' => quoted = !quoted,
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+impl PipeOptions {
+    /// Parse option tokens such as `evict_after=30`. A token without `=`
+    /// continues the value of the option before it, for lists such as
+    /// `columns=1,3,7`
+    pub fn parse(tokens: &[&str]) -> Result<PipeOptions, ParseError> {
+        let mut entries = Vec::with_capacity(tokens.len());
+
+        for token in tokens {
+            let token = token.trim();
+            if token.is_empty() {
+                continue;
+            }
+            match (split_assignment(token), entries.last_mut()) {
+                (Some((key, value)), _) => {
+                    entries.push((unquote(key.trim()).to_lowercase(), unquote(value.trim())))
+                }
+                (None, Some((_, value))) => {
+                    value.push(',');
+                    value.push_str(&unquote(token));
+                }
+                (None, None) => {
+                    return Err(ParseError::Configuration(format!(
+                        "Malformed option '{token}', expected key=value"
+                    )))
+                }
+            }
+        }
+
+        Ok(PipeOptions { entries })
+    }
+
+    /// Raw value of option `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Option `key` as a duration in (fractional) seconds
+    pub fn duration(&self, key: &str) -> Result<Option<Duration>, ParseError> {
+        match self.get(key) {
+            Some(value) => match value.parse::<f64>() {
+                Ok(secs) if secs >= 0.0 && secs.is_finite() => {
+                    Ok(Some(Duration::from_secs_f64(secs)))
+                }
+                _ => Err(ParseError::Configuration(format!(
+                    "Invalid duration '{value}' for option '{key}'"
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Option `key` as a number
+    pub fn number<T: FromStr>(&self, key: &str) -> Result<Option<T>, ParseError> {
+        match self.get(key) {
+            Some(value) => match value.parse::<T>() {
+                Ok(number) => Ok(Some(number)),
+                Err(_) => Err(ParseError::Configuration(format!(
+                    "Invalid number '{value}' for option '{key}'"
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Option `key` as a boolean, `true`/`false`, `yes`/`no` or `1`/`0`
+    pub fn flag(&self, key: &str) -> Result<Option<bool>, ParseError> {
+        match self.get(key).map(str::to_lowercase).as_deref() {
+            Some("1" | "true" | "yes") => Ok(Some(true)),
+            Some("0" | "false" | "no") => Ok(Some(false)),
+            Some(value) => Err(ParseError::Configuration(format!(
+                "Invalid boolean '{value}' for option '{key}'"
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Keys not in `known`
+    pub fn unknown<'a>(&'a self, known: &'a [&str]) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .filter(move |k| !known.contains(k))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_options() {
+        let options = PipeOptions::parse(&["evict_after=2.5", " Name = fuel "]).unwrap();
+        assert_eq!(options.get("name"), Some("fuel"));
+        assert_eq!(
+            options.duration("evict_after").unwrap(),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(options.duration("missing").unwrap(), None);
+        assert!(PipeOptions::parse(&["evict_after"]).is_err());
+        assert_eq!(
+            PipeOptions::parse(&["columns=1", "3", " 7", "ttl=2"])
+                .unwrap()
+                .get("columns"),
+            Some("1,3,7")
+        );
+        assert_eq!(
+            PipeOptions::parse(&["queue=64"])
+                .unwrap()
+                .number::<usize>("queue")
+                .unwrap(),
+            Some(64)
+        );
+        assert_eq!(
+            PipeOptions::parse(&["coalesce=Yes"])
+                .unwrap()
+                .flag("coalesce")
+                .unwrap(),
+            Some(true)
+        );
+        assert!(PipeOptions::parse(&["evict_after=soon"])
+            .unwrap()
+            .duration("evict_after")
+            .is_err());
+    }
+
+    #[test]
+    fn quoted_values() {
+        let value = r#"1,wt,prefix=" fuel, ", label="a=b",sink="say ""hi""",columns=1,3"#;
+        let tokens = tokenize(value).unwrap();
+        assert_eq!(tokens[..2], ["1", "wt"]);
+        let options = PipeOptions::parse(&tokens[2..]).unwrap();
+        assert_eq!(options.get("prefix"), Some(" fuel, "));
+        assert_eq!(options.get("label"), Some("a=b"));
+        assert_eq!(options.get("sink"), Some(r#"say "hi""#));
+        assert_eq!(options.get("columns"), Some("1,3"));
+        assert!(tokenize(r#"1,wt,prefix="fuel"#).is_err());
+    }
+}
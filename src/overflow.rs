@@ -0,0 +1,292 @@
+//! Overflow buffer of an output, `overflow=<bytes>`: once its queue is full,
+//! records are kept in a memory mapped temporary file rather than dropped,
+//! so a long consumer outage is bounded by disk rather than RAM. The file is
+//! a ring: when it is full the oldest records are dropped to make room.
+//!
+//! Every entry is a `u32` length, 4 bytes of padding, the `u64` sequence
+//! number and the `u64` nanoseconds between the creation of the buffer and
+//! the reception of the record, followed by its bytes padded to 8 bytes. A
+//! length of `u32::MAX` marks the rest of the ring as unused.
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::Record;
+
+/// Bytes before the data of an entry
+const ENTRY: usize = 24;
+/// Length marking the end of the used part of the ring
+const WRAP: u32 = u32::MAX;
+
+/// `len` rounded up to the 8 byte alignment of entries
+fn align(len: usize) -> usize {
+    (len + 7) & !7
+}
+
+/// Path of a new buffer file, unique within the process
+fn buffer_path() -> PathBuf {
+    static BUFFERS: AtomicU64 = AtomicU64::new(0);
+    let index = BUFFERS.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("psplit-overflow-{}-{index}", std::process::id()))
+}
+
+/// Mapping of an unlinked temporary file
+struct Mapping {
+    map: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    /// Map a new temporary file of `len` bytes, removed once unmapped
+    fn create(len: usize) -> io::Result<Mapping> {
+        let path = buffer_path();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // The mapping keeps the data reachable, nothing is left behind
+        fs::remove_file(&path)?;
+        file.set_len(len as u64)?;
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            map: map as *mut u8,
+            len,
+        })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map as *mut libc::c_void, self.len) };
+    }
+}
+
+/// Ring of records in a memory mapped file, created on first use
+pub(crate) struct Overflow {
+    /// Bytes of the ring
+    capacity: usize,
+    /// Mapping of the ring, once a record overflowed
+    mapping: Option<Mapping>,
+    /// Offset of the next entry, from the start of the ring
+    head: usize,
+    /// Offset of the oldest entry, from the start of the ring
+    tail: usize,
+    /// Entries in the ring
+    count: usize,
+    /// Origin of the reception times of the entries
+    epoch: Instant,
+}
+
+// The mapping is only used under the lock of the owning queue
+unsafe impl Send for Overflow {}
+
+impl Overflow {
+    /// Empty buffer of about `size` bytes
+    pub fn new(size: usize) -> Overflow {
+        Overflow {
+            capacity: align(size),
+            mapping: None,
+            head: 0,
+            tail: 0,
+            count: 0,
+            epoch: Instant::now(),
+        }
+    }
+
+    /// Bytes of the ring
+    #[cfg(test)]
+    pub fn size(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether no record is buffered
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Ring of the mapping
+    fn ring(&mut self) -> io::Result<*mut u8> {
+        if self.mapping.is_none() {
+            self.mapping = Some(Mapping::create(self.capacity)?);
+        }
+        Ok(self.mapping.as_ref().unwrap().map)
+    }
+
+    /// Length stored at ring offset `at`
+    fn len_at(&self, at: usize) -> u32 {
+        let map = self.mapping.as_ref().unwrap().map;
+        unsafe { ptr::read_unaligned(map.add(at) as *const u32) }
+    }
+
+    /// Offset of the oldest entry, skipping the unused end of the ring
+    fn oldest(&mut self) -> usize {
+        if self.len_at(self.tail) == WRAP {
+            self.tail = 0;
+        }
+        self.tail
+    }
+
+    /// Bytes skipped at the end of the ring before an entry of `need` bytes,
+    /// and whether the entry then fits without dropping older ones
+    fn room(&self, need: usize) -> (usize, bool) {
+        // An entry does not wrap, the end of the ring is skipped instead
+        let skip = match self.capacity - self.head < need {
+            true => self.capacity - self.head,
+            false => 0,
+        };
+        // Free room is after the head and before the oldest entry, or
+        // between them once the entries wrapped
+        let fits = match self.count == 0 || self.head > self.tail {
+            true => skip == 0 || need <= self.tail,
+            false => need <= self.tail - self.head,
+        };
+        (skip, fits)
+    }
+
+    /// Whether a record of `len` bytes is buffered without dropping older
+    /// ones
+    pub fn fits(&mut self, len: usize) -> bool {
+        let need = align(ENTRY + len);
+        if need > self.capacity || len >= WRAP as usize || self.ring().is_err() {
+            return false;
+        }
+        self.count == 0 || self.room(need).1
+    }
+
+    /// Buffer `record`, returning the oldest records dropped to make room
+    /// for it
+    pub fn push(&mut self, record: &Record) -> io::Result<Vec<Record>> {
+        let need = align(ENTRY + record.data.len());
+        if need > self.capacity || record.data.len() >= WRAP as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record larger than the overflow buffer",
+            ));
+        }
+        let map = self.ring()?;
+        let mut dropped = Vec::new();
+        loop {
+            if self.count == 0 {
+                self.head = 0;
+                self.tail = 0;
+            }
+            let (skip, fits) = self.room(need);
+            if fits {
+                if skip > 0 {
+                    unsafe { ptr::write_unaligned(map.add(self.head) as *mut u32, WRAP) };
+                    self.head = 0;
+                }
+                break;
+            }
+            dropped.push(self.pop().expect("entries to drop"));
+        }
+        let received = record.received.saturating_duration_since(self.epoch);
+        unsafe {
+            let entry = map.add(self.head);
+            ptr::write_unaligned(entry as *mut u32, record.data.len() as u32);
+            ptr::write_unaligned(entry.add(8) as *mut u64, record.seq);
+            ptr::write_unaligned(entry.add(16) as *mut u64, received.as_nanos() as u64);
+            ptr::copy_nonoverlapping(record.data.as_ptr(), entry.add(ENTRY), record.data.len());
+        }
+        self.head = (self.head + need) % self.capacity;
+        self.count += 1;
+        Ok(dropped)
+    }
+
+    /// Bytes of the oldest record, if any
+    pub fn front_len(&mut self) -> Option<usize> {
+        if self.count == 0 {
+            return None;
+        }
+        let at = self.oldest();
+        Some(self.len_at(at) as usize)
+    }
+
+    /// Take the oldest record, if any
+    pub fn pop(&mut self) -> Option<Record> {
+        let len = self.front_len()?;
+        let map = self.mapping.as_ref().unwrap().map;
+        let record = unsafe {
+            let entry = map.add(self.tail);
+            let seq = ptr::read_unaligned(entry.add(8) as *const u64);
+            let received = ptr::read_unaligned(entry.add(16) as *const u64);
+            let mut data = vec![0; len];
+            ptr::copy_nonoverlapping(entry.add(ENTRY), data.as_mut_ptr(), len);
+            Record {
+                seq,
+                data,
+                received: self.epoch + Duration::from_nanos(received),
+            }
+        };
+        self.tail = (self.tail + align(ENTRY + len)) % self.capacity;
+        self.count -= 1;
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(seq: u64, data: &[u8]) -> Record {
+        Record {
+            seq,
+            data: data.to_vec(),
+            received: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn drop_oldest_when_full() {
+        // Room for 3 entries of 8 bytes of data
+        let mut overflow = Overflow::new(96);
+        assert!(overflow.pop().is_none());
+        assert!(overflow.push(&record(0, &[0; 96])).is_err());
+        assert!(!overflow.fits(96));
+
+        for seq in 1..=3 {
+            assert!(overflow
+                .push(&record(seq, b"fuel=12\n"))
+                .unwrap()
+                .is_empty());
+        }
+        // Full, a record only fits by dropping the oldest one
+        assert!(!overflow.fits(8));
+        let dropped = overflow.push(&record(4, b"speed=8\n")).unwrap();
+        let dropped: Vec<_> = dropped.iter().map(|r| (r.seq, &r.data[..])).collect();
+        assert_eq!(dropped, [(1, &b"fuel=12\n"[..])]);
+        assert_eq!(overflow.pop().unwrap().seq, 2);
+        assert_eq!(overflow.pop().unwrap().seq, 3);
+
+        assert!(overflow.push(&record(5, b"")).unwrap().is_empty());
+        assert!(overflow.push(&record(6, b"gps=1.0\n")).unwrap().is_empty());
+        assert!(!overflow.fits(8));
+        // 8 bytes left at the end of the ring: they are skipped, and the
+        // oldest record dropped to make room at the start
+        let dropped = overflow.push(&record(7, b"rpm=900\n")).unwrap();
+        assert_eq!(dropped.iter().map(|r| r.seq).collect::<Vec<_>>(), [4]);
+        assert_eq!(overflow.front_len(), Some(0));
+        assert_eq!(overflow.pop().unwrap().seq, 5);
+        assert_eq!(overflow.pop().unwrap().seq, 6);
+        let last = overflow.pop().unwrap();
+        assert_eq!((last.seq, last.data.as_slice()), (7, &b"rpm=900\n"[..]));
+        assert!(overflow.is_empty());
+    }
+}
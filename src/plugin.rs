@@ -0,0 +1,504 @@
+//! Sinks, sources and transforms loaded at runtime from shared objects,
+//! `[DEFAULT] plugins=/usr/lib/psplit/*.so`, so site specific extensions
+//! ship as packages without rebuilding the splitter.
+//!
+//! A plugin exports `int psplit_plugin_init(const struct psplit_host *host)`,
+//! returning 0 once it registered its extensions through `host`:
+//!
+//! ```c
+//! struct psplit_sink {
+//!     void *(*open)(const char *target);          /* NULL and errno on failure */
+//!     int (*write)(void *state, const uint8_t *data, size_t len);
+//!     void (*close)(void *state);
+//!     int (*ready_fd)(void *state);               /* optional, -1 for none */
+//! };
+//! struct psplit_source {
+//!     void *(*open)(const char *target);
+//!     int (*read)(void *state, uint8_t *buf, size_t cap, size_t *len);
+//!     void (*close)(void *state);
+//!     int (*ready_fd)(void *state);
+//! };
+//! struct psplit_transform {
+//!     void *(*create)(const char *parameter);     /* parameter may be NULL */
+//!     int (*apply)(void *state, uint8_t *buf, size_t *len, size_t cap);
+//!     void (*destroy)(void *state);               /* optional */
+//! };
+//! struct psplit_host {
+//!     uint32_t abi_version;                       /* 1 */
+//!     int (*register_sink)(const char *scheme, const struct psplit_sink *sink);
+//!     int (*register_source)(const char *scheme, const struct psplit_source *source);
+//!     int (*register_transform)(const char *name, const struct psplit_transform *transform);
+//! };
+//! ```
+//!
+//! `write` returns 0 or a negated errno, `-EAGAIN` when the destination
+//! cannot take the record yet. `read` returns 0 with a record of `*len`
+//! bytes in `buf`, 1 at the end of the stream or a negated errno. `apply`
+//! rewrites the record of `*len` bytes in place, returning 0 to keep it and
+//! 1 to drop it. When `read` or `apply` needs a larger buffer it returns
+//! `-ENOBUFS` with the size needed in `*len` and is called again. Plugin
+//! state is used from the worker threads and must not be tied to the thread
+//! that created it; transforms may be applied from several threads at once.
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Mutex;
+
+use crate::sink::{register_sink, Sink};
+use crate::source::{register_source, Source};
+use crate::transform::{register_transform, Transform, TransformOptions};
+use crate::MAX_PACKET;
+
+/// Version of the interface between the splitter and its plugins
+const ABI_VERSION: u32 = 1;
+
+/// Symbol a plugin exports to register its extensions
+const INIT_SYMBOL: &CStr = c"psplit_plugin_init";
+
+/// `struct psplit_sink`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SinkVtable {
+    open: extern "C" fn(*const c_char) -> *mut c_void,
+    write: extern "C" fn(*mut c_void, *const u8, usize) -> c_int,
+    close: extern "C" fn(*mut c_void),
+    ready_fd: Option<extern "C" fn(*mut c_void) -> c_int>,
+}
+
+/// `struct psplit_source`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SourceVtable {
+    open: extern "C" fn(*const c_char) -> *mut c_void,
+    read: extern "C" fn(*mut c_void, *mut u8, usize, *mut usize) -> c_int,
+    close: extern "C" fn(*mut c_void),
+    ready_fd: Option<extern "C" fn(*mut c_void) -> c_int>,
+}
+
+/// `struct psplit_transform`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct TransformVtable {
+    create: extern "C" fn(*const c_char) -> *mut c_void,
+    apply: extern "C" fn(*mut c_void, *mut u8, *mut usize, usize) -> c_int,
+    destroy: Option<extern "C" fn(*mut c_void)>,
+}
+
+/// `struct psplit_host`
+#[repr(C)]
+pub(crate) struct Host {
+    abi_version: u32,
+    register_sink: extern "C" fn(*const c_char, *const SinkVtable) -> c_int,
+    register_source: extern "C" fn(*const c_char, *const SourceVtable) -> c_int,
+    register_transform: extern "C" fn(*const c_char, *const TransformVtable) -> c_int,
+}
+
+/// Interface handed to every plugin
+pub(crate) static HOST: Host = Host {
+    abi_version: ABI_VERSION,
+    register_sink: host_register_sink,
+    register_source: host_register_source,
+    register_transform: host_register_transform,
+};
+
+/// Plugins loaded so far, never unloaded
+static LOADED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Name passed by a plugin, `None` when missing or not UTF-8
+fn plugin_name(name: *const c_char) -> Option<String> {
+    if name.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(name) };
+    name.to_str().ok().map(str::to_owned)
+}
+
+/// Error of a negated errno returned by a plugin
+fn plugin_error(result: c_int) -> io::Error {
+    io::Error::from_raw_os_error(result.checked_neg().unwrap_or(libc::EIO))
+}
+
+extern "C" fn host_register_sink(scheme: *const c_char, sink: *const SinkVtable) -> c_int {
+    let (Some(scheme), false) = (plugin_name(scheme), sink.is_null()) else {
+        return -libc::EINVAL;
+    };
+    let vtable = unsafe { *sink };
+    register_sink(&scheme, move |target| {
+        Ok(Box::new(PluginSink {
+            vtable,
+            target: CString::new(target)?,
+            state: ptr::null_mut(),
+        }))
+    });
+    0
+}
+
+extern "C" fn host_register_source(scheme: *const c_char, source: *const SourceVtable) -> c_int {
+    let (Some(scheme), false) = (plugin_name(scheme), source.is_null()) else {
+        return -libc::EINVAL;
+    };
+    let vtable = unsafe { *source };
+    register_source(&scheme, move |target| {
+        Ok(Box::new(PluginSource {
+            vtable,
+            target: CString::new(target)?,
+            state: ptr::null_mut(),
+            buffer: vec![0; MAX_PACKET],
+        }))
+    });
+    0
+}
+
+extern "C" fn host_register_transform(
+    name: *const c_char,
+    transform: *const TransformVtable,
+) -> c_int {
+    let (Some(name), false) = (plugin_name(name), transform.is_null()) else {
+        return -libc::EINVAL;
+    };
+    let vtable = unsafe { *transform };
+    let transform_name = name.clone();
+    register_transform(&name, move |options: &TransformOptions| {
+        let parameter = match options.value().map(CString::new) {
+            Some(Ok(parameter)) => Some(parameter),
+            Some(Err(_)) => return Err(format!("Invalid value for option '{transform_name}'")),
+            None => None,
+        };
+        let parameter_ptr = parameter.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+        let state = (vtable.create)(parameter_ptr);
+        if state.is_null() {
+            return Err(format!(
+                "Plugin transform '{transform_name}' rejected its options: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(Box::new(PluginTransform { vtable, state }))
+    });
+    0
+}
+
+/// Sink provided by a plugin
+struct PluginSink {
+    vtable: SinkVtable,
+    target: CString,
+    /// State returned by `open`, null while closed
+    state: *mut c_void,
+}
+
+// Plugins must not tie their state to the thread that created it
+unsafe impl Send for PluginSink {}
+
+impl Sink for PluginSink {
+    fn open(&mut self) -> io::Result<()> {
+        self.close();
+        self.state = (self.vtable.open)(self.target.as_ptr());
+        match self.state.is_null() {
+            true => Err(io::Error::last_os_error()),
+            false => Ok(()),
+        }
+    }
+    fn write(&mut self, record: &[u8]) -> io::Result<()> {
+        match (self.vtable.write)(self.state, record.as_ptr(), record.len()) {
+            0 => Ok(()),
+            result => Err(plugin_error(result)),
+        }
+    }
+    fn close(&mut self) {
+        if !self.state.is_null() {
+            (self.vtable.close)(self.state);
+            self.state = ptr::null_mut();
+        }
+    }
+    fn ready_fd(&self) -> Option<c_int> {
+        let fd = (self.vtable.ready_fd?)(self.state);
+        (fd >= 0).then_some(fd)
+    }
+}
+
+impl Drop for PluginSink {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Source provided by a plugin
+struct PluginSource {
+    vtable: SourceVtable,
+    target: CString,
+    /// State returned by `open`, null while closed
+    state: *mut c_void,
+    /// Buffer records are read into, grown on request of the plugin
+    buffer: Vec<u8>,
+}
+
+// Plugins must not tie their state to the thread that created it
+unsafe impl Send for PluginSource {}
+
+impl Source for PluginSource {
+    fn open(&mut self) -> io::Result<()> {
+        self.close();
+        self.state = (self.vtable.open)(self.target.as_ptr());
+        match self.state.is_null() {
+            true => Err(io::Error::last_os_error()),
+            false => Ok(()),
+        }
+    }
+    fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let mut len = 0;
+            let cap = self.buffer.len();
+            match (self.vtable.read)(self.state, self.buffer.as_mut_ptr(), cap, &mut len) {
+                0 if len <= cap => return Ok(Some(self.buffer[..len].to_vec())),
+                1 => return Ok(None),
+                result if result == -libc::ENOBUFS && len > cap => self.buffer.resize(len, 0),
+                result => return Err(plugin_error(result.min(-libc::EIO))),
+            }
+        }
+    }
+    fn close(&mut self) {
+        if !self.state.is_null() {
+            (self.vtable.close)(self.state);
+            self.state = ptr::null_mut();
+        }
+    }
+    fn ready_fd(&self) -> Option<c_int> {
+        let fd = (self.vtable.ready_fd?)(self.state);
+        (fd >= 0).then_some(fd)
+    }
+}
+
+impl Drop for PluginSource {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Transform provided by a plugin
+struct PluginTransform {
+    vtable: TransformVtable,
+    /// State returned by `create`
+    state: *mut c_void,
+}
+
+// Plugin transforms must be safe to apply from several threads
+unsafe impl Send for PluginTransform {}
+unsafe impl Sync for PluginTransform {}
+
+impl Transform for PluginTransform {
+    fn apply(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        let mut cap = data.len().max(64);
+        loop {
+            let mut buffer = Vec::with_capacity(cap);
+            buffer.extend_from_slice(&data);
+            buffer.resize(cap, 0);
+            let mut len = data.len();
+            match (self.vtable.apply)(self.state, buffer.as_mut_ptr(), &mut len, cap) {
+                0 if len <= cap => {
+                    buffer.truncate(len);
+                    return Some(buffer);
+                }
+                result if result == -libc::ENOBUFS && len > cap => cap = len,
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Drop for PluginTransform {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.vtable.destroy {
+            destroy(self.state);
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of
+/// characters and `?` for any single one
+fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, _) => name.is_empty(),
+        (Some((b'*', rest)), _) => {
+            matches(rest, name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name))) => matches(rest, name),
+        (Some((p, rest)), Some((n, name))) => p == n && matches(rest, name),
+        (Some(_), None) => false,
+    }
+}
+
+/// Paths of the files matching `pattern`, which may have wildcards in its
+/// file name, in name order
+fn expand(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let Some(file_pattern) = path.file_name().filter(|_| pattern.contains(['*', '?'])) else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if matches(file_pattern.as_bytes(), entry.file_name().as_bytes()) {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Load the shared object at `path` and let it register its extensions
+fn load_plugin(path: &Path) -> Result<(), String> {
+    let mut loaded = LOADED.lock().unwrap();
+    if loaded.iter().any(|p| p == path) {
+        return Ok(());
+    }
+    let file = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+    let handle = unsafe { libc::dlopen(file.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if handle.is_null() {
+        let error = unsafe { libc::dlerror() };
+        return Err(match error.is_null() {
+            true => "cannot open shared object".to_owned(),
+            false => unsafe { CStr::from_ptr(error) }
+                .to_string_lossy()
+                .into_owned(),
+        });
+    }
+    let init = unsafe { libc::dlsym(handle, INIT_SYMBOL.as_ptr()) };
+    if init.is_null() {
+        unsafe { libc::dlclose(handle) };
+        return Err(format!("no symbol '{}'", INIT_SYMBOL.to_string_lossy()));
+    }
+    let init: extern "C" fn(*const Host) -> c_int = unsafe { std::mem::transmute(init) };
+    match init(&HOST) {
+        0 => {
+            loaded.push(path.to_path_buf());
+            Ok(())
+        }
+        result => Err(format!("initialisation failed with {result}")),
+    }
+}
+
+/// Load the plugins matching the comma separated `patterns`
+pub(crate) fn load_plugins(patterns: &str) -> Result<(), String> {
+    for pattern in patterns.split(',').map(str::trim) {
+        let paths = expand(pattern).map_err(|e| format!("Cannot list plugins '{pattern}': {e}"))?;
+        for path in paths {
+            load_plugin(&path)
+                .map_err(|e| format!("Cannot load plugin '{}': {e}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::options::PipeOptions;
+    use crate::sink::SinkTarget;
+    use crate::transform::Chain;
+    use std::env::temp_dir;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn repeat_create(parameter: *const c_char) -> *mut c_void {
+        let count: Option<usize> = match parameter.is_null() {
+            true => Some(2),
+            false => unsafe { CStr::from_ptr(parameter) }
+                .to_str()
+                .ok()
+                .and_then(|count| count.parse().ok()),
+        };
+        match count {
+            Some(count) => Box::into_raw(Box::new(count)).cast(),
+            None => ptr::null_mut(),
+        }
+    }
+
+    extern "C" fn repeat_apply(
+        state: *mut c_void,
+        buf: *mut u8,
+        len: *mut usize,
+        cap: usize,
+    ) -> c_int {
+        let count = unsafe { *state.cast::<usize>() };
+        let record = unsafe { *len };
+        unsafe { *len = record * count };
+        if record * count > cap {
+            return -libc::ENOBUFS;
+        }
+        for n in 1..count {
+            unsafe { ptr::copy_nonoverlapping(buf, buf.add(n * record), record) };
+        }
+        0
+    }
+
+    extern "C" fn repeat_destroy(state: *mut c_void) {
+        drop(unsafe { Box::from_raw(state.cast::<usize>()) });
+    }
+
+    extern "C" fn count_open(_target: *const c_char) -> *mut c_void {
+        ptr::NonNull::dangling().as_ptr()
+    }
+
+    extern "C" fn count_write(_state: *mut c_void, _data: *const u8, len: usize) -> c_int {
+        WRITTEN.fetch_add(len, Ordering::SeqCst);
+        0
+    }
+
+    extern "C" fn count_close(_state: *mut c_void) {}
+
+    #[test]
+    fn register_through_host() {
+        let repeat = TransformVtable {
+            create: repeat_create,
+            apply: repeat_apply,
+            destroy: Some(repeat_destroy),
+        };
+        assert_eq!((HOST.register_transform)(c"repeat".as_ptr(), &repeat), 0);
+        let options = PipeOptions::parse(&["transforms=repeat", "repeat=40"]).unwrap();
+        let chain = Chain::build(&options).unwrap();
+        assert_eq!(chain.apply(b"ab"), Some(b"ab".repeat(40)));
+        let options = PipeOptions::parse(&["transforms=repeat", "repeat=many"]).unwrap();
+        assert!(Chain::build(&options).is_err());
+
+        let count = SinkVtable {
+            open: count_open,
+            write: count_write,
+            close: count_close,
+            ready_fd: None,
+        };
+        assert_eq!((HOST.register_sink)(c"count".as_ptr(), &count), 0);
+        let mut sink = SinkTarget::resolve_sink("count://x")
+            .unwrap()
+            .open_sink()
+            .unwrap();
+        sink.write(b"fuel=12\n").unwrap();
+        assert_eq!(WRITTEN.load(Ordering::SeqCst), 8);
+        assert_eq!((HOST.register_sink)(ptr::null(), &count), -libc::EINVAL);
+    }
+
+    #[test]
+    fn load_plugin_files() {
+        assert!(matches(b"*.so", b"libfuel.so"));
+        assert!(matches(b"lib?uel.s*", b"libfuel.so"));
+        assert!(!matches(b"*.so", b"libfuel.so.1"));
+
+        let directory = temp_dir().join("p_split_plugins");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("broken.so"), b"not an ELF object").unwrap();
+        fs::write(directory.join("notes.txt"), b"").unwrap();
+        let pattern = directory.join("*.so");
+        assert_eq!(
+            expand(pattern.to_str().unwrap()).unwrap(),
+            [directory.join("broken.so")]
+        );
+        assert!(load_plugins(pattern.to_str().unwrap()).is_err());
+        assert!(load_plugins(directory.join("none*.so").to_str().unwrap()).is_ok());
+    }
+}
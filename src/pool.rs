@@ -0,0 +1,134 @@
+//! Vectored reads of byte inputs. A single `readv` fills several buffers the
+//! size of the kernel pipe, taken from a pool they return to once framed,
+//! so a busy input drains its pipe in few system calls and the read path
+//! allocates nothing once the pool is warm. Records are cut from slices of
+//! the filled buffers, their bytes being copied once, into the record.
+use std::collections::VecDeque;
+use std::io;
+use std::os::fd::RawFd;
+
+/// Buffers allocated, at most, when the pipe capacity cannot be read
+const DEFAULT_CAPACITY: usize = 1 << 16;
+
+/// Reader of a byte input through pooled buffers
+pub(crate) struct VectoredReader {
+    /// Buffers ready to be read into, all `size` bytes long
+    pool: Vec<Vec<u8>>,
+    /// Filled buffers with the number of bytes read into them, oldest first
+    filled: VecDeque<(Vec<u8>, usize)>,
+    /// Bytes of the oldest filled buffer already consumed
+    offset: usize,
+    /// Size of every buffer, the capacity of the pipe
+    size: usize,
+    /// Buffers filled by a read
+    count: usize,
+}
+
+impl VectoredReader {
+    /// Reader of the pipe `fd` taking up to `chunk` bytes a read, in buffers
+    /// the size of the pipe
+    pub fn for_pipe(fd: RawFd, chunk: usize) -> VectoredReader {
+        let size = match unsafe { libc::fcntl(fd, libc::F_GETPIPE_SZ) } {
+            size if size > 0 => size as usize,
+            _ => DEFAULT_CAPACITY,
+        };
+        VectoredReader::new(size, chunk.div_ceil(size))
+    }
+
+    /// Reader filling up to `count` buffers of `size` bytes a read
+    pub fn new(size: usize, count: usize) -> VectoredReader {
+        VectoredReader {
+            pool: Vec::new(),
+            filled: VecDeque::new(),
+            offset: 0,
+            size,
+            count: count.max(1),
+        }
+    }
+
+    /// Bytes read and not yet consumed, reading `fd` when there are none,
+    /// empty at the end of the stream
+    pub fn fill_buf(&mut self, fd: RawFd) -> io::Result<&[u8]> {
+        if self.filled.is_empty() {
+            self.read(fd)?;
+        }
+        Ok(match self.filled.front() {
+            Some((buffer, len)) => &buffer[self.offset..*len],
+            None => &[],
+        })
+    }
+
+    /// Mark `n` bytes of those returned by [`VectoredReader::fill_buf`] as
+    /// consumed, returning exhausted buffers to the pool
+    pub fn consume(&mut self, n: usize) {
+        self.offset += n;
+        if let Some((_, len)) = self.filled.front() {
+            if self.offset >= *len {
+                let (buffer, _) = self.filled.pop_front().unwrap();
+                self.pool.push(buffer);
+                self.offset = 0;
+            }
+        }
+    }
+
+    /// Read `fd` into as many pooled buffers as a read takes
+    fn read(&mut self, fd: RawFd) -> io::Result<()> {
+        let mut buffers: Vec<Vec<u8>> = (0..self.count)
+            .map(|_| self.pool.pop().unwrap_or_else(|| vec![0; self.size]))
+            .collect();
+        let iovecs: Vec<libc::iovec> = buffers
+            .iter_mut()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            })
+            .collect();
+        let read = unsafe { libc::readv(fd, iovecs.as_ptr(), iovecs.len() as libc::c_int) };
+        if read < 0 {
+            self.pool.append(&mut buffers);
+            return Err(io::Error::last_os_error());
+        }
+        let mut left = read as usize;
+        for buffer in buffers {
+            if left == 0 {
+                self.pool.push(buffer);
+                continue;
+            }
+            let len = left.min(buffer.len());
+            left -= len;
+            self.filled.push_back((buffer, len));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn read_into_pooled_buffers() {
+        let (receiver, mut sender) = std::io::pipe().unwrap();
+        let fd = receiver.as_raw_fd();
+        let mut reader = VectoredReader::new(4, 2);
+        sender.write_all(b"fuel=12\ngps").unwrap();
+
+        // One read fills both buffers, the rest waits for the next
+        assert_eq!(reader.fill_buf(fd).unwrap(), b"fuel");
+        reader.consume(2);
+        assert_eq!(reader.fill_buf(fd).unwrap(), b"el");
+        reader.consume(2);
+        assert_eq!(reader.fill_buf(fd).unwrap(), b"=12\n");
+        reader.consume(4);
+        assert_eq!(reader.pool.len(), 2);
+        assert_eq!(reader.fill_buf(fd).unwrap(), b"gps");
+        reader.consume(3);
+
+        // Buffers are reused rather than allocated again
+        drop(sender);
+        assert_eq!(reader.fill_buf(fd).unwrap(), b"");
+        assert_eq!(reader.pool.len(), 2);
+    }
+}
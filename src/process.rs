@@ -0,0 +1,94 @@
+//! Resources held by the splitter process, read from `/proc/self` when the
+//! status is reported, so a device running short of memory or descriptors
+//! shows it next to the state of the pipes.
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Resources of the process at the time of the call
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProcessView {
+    /// Descriptors open
+    pub fds: usize,
+    /// Threads running
+    pub threads: usize,
+    /// Resident memory, in bytes
+    pub rss: u64,
+    /// Bytes of the records waiting in the output queues
+    pub queued_bytes: u64,
+    /// Time since the process started
+    pub uptime: Duration,
+}
+
+/// Value of the `key:` line of `/proc/self/status`, without its unit
+fn status_field(status: &str, key: &str) -> io::Result<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .and_then(|value| value.split_whitespace().next()?.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("no {key} in status")))
+}
+
+/// Time since the process started, from its start in `stat` and the time
+/// since boot in `uptime`
+fn uptime(stat: &str, uptime: &str) -> io::Result<Duration> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "unexpected /proc format");
+    // Fields after the command, which may hold spaces, start with the state
+    let fields = &stat[stat.rfind(')').ok_or_else(invalid)? + 1..];
+    let started: u64 = fields
+        .split_whitespace()
+        .nth(19)
+        .and_then(|ticks| ticks.parse().ok())
+        .ok_or_else(invalid)?;
+    let booted: f64 = uptime
+        .split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse().ok())
+        .ok_or_else(invalid)?;
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    Ok(Duration::from_secs_f64(
+        (booted - started as f64 / ticks).max(0.0),
+    ))
+}
+
+/// Resources of the process, with `queued_bytes` waiting in its queues
+pub(crate) fn usage(queued_bytes: u64) -> io::Result<ProcessView> {
+    let status = fs::read_to_string("/proc/self/status")?;
+    Ok(ProcessView {
+        fds: crate::fds::open_count()?,
+        threads: status_field(&status, "Threads")? as usize,
+        rss: status_field(&status, "VmRSS")? * 1024,
+        queued_bytes,
+        uptime: uptime(
+            &fs::read_to_string("/proc/self/stat")?,
+            &fs::read_to_string("/proc/uptime")?,
+        )?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_proc() {
+        let status = "Name:\tpsplit\nVmRSS:\t    2048 kB\nThreads:\t7\n";
+        assert_eq!(status_field(status, "Threads").unwrap(), 7);
+        assert_eq!(status_field(status, "VmRSS").unwrap(), 2048);
+        assert!(status_field(status, "VmSwap").is_err());
+
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+        let stat = format!(
+            "42 (p split) S 1 42 42 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 7 0 {} 0",
+            100 * ticks
+        );
+        assert_eq!(
+            uptime(&stat, "160.00 300.00").unwrap(),
+            Duration::from_secs(60)
+        );
+
+        let usage = usage(12).unwrap();
+        assert!(usage.fds > 0 && usage.threads > 0 && usage.rss > 0);
+        assert_eq!(usage.queued_bytes, 12);
+    }
+}
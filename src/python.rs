@@ -0,0 +1,226 @@
+//! `psplit` Python module, built with `maturin build` and the `python`
+//! feature, for test rigs starting splitters programmatically:
+//!
+//! ```python
+//! import psplit
+//!
+//! topology = {
+//!     "DEFAULT": {"root": "/tmp/rig"},
+//!     "PIPES": {"cvAnalogsMapperExt": "1,rt"},
+//!     "cvAnalogsMapperExt": {"cvAnalogsMapperExtFuelApp": "1,wt"},
+//! }
+//! with psplit.Splitter(topology) as splitter:
+//!     print(splitter.stats())
+//!     print(splitter.topology()["inputs"][0]["records"])
+//! ```
+//!
+//! A splitter is built from the path of a configuration file, or from its
+//! sections as a dictionary of dictionaries, and runs on a thread of its
+//! own as with the C interface.
+use std::env;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict};
+
+use crate::{status, InputView, OutputView, PipeMode, PipeState, Splitter, SplitterHandle};
+
+/// Configurations written from dictionaries so far
+static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Configuration file of the sections `sections`, each a dictionary of
+/// settings or pipes
+fn render(sections: &Bound<'_, PyDict>) -> PyResult<String> {
+    let mut ini = String::new();
+    for (section, entries) in sections.iter() {
+        let entries = entries.downcast::<PyDict>()?;
+        ini.push_str(&format!("[{}]\n", section.str()?));
+        for (key, value) in entries.iter() {
+            let value = match value.downcast::<PyBool>() {
+                Ok(flag) => (flag.is_true() as u8).to_string(),
+                Err(_) => value.str()?.to_string(),
+            };
+            ini.push_str(&format!("{}={}\n", key.str()?, value));
+        }
+    }
+    Ok(ini)
+}
+
+fn mode(mode: Option<PipeMode>) -> Option<&'static str> {
+    mode.map(|mode| match mode {
+        PipeMode::ReadText => "rt",
+        PipeMode::ReadBytes => "rb",
+        PipeMode::WriteText => "wt",
+        PipeMode::WriteBytes => "wb",
+    })
+}
+
+fn state(state: PipeState) -> &'static str {
+    match state {
+        PipeState::Disabled => "disabled",
+        PipeState::Running => "running",
+        PipeState::Stalled => "stalled",
+        PipeState::Evicted => "evicted",
+    }
+}
+
+fn output<'py>(py: Python<'py>, output: &OutputView) -> PyResult<Bound<'py, PyDict>> {
+    let view = PyDict::new(py);
+    view.set_item("name", &output.name)?;
+    view.set_item("pipe", &output.pipe)?;
+    view.set_item("mode", mode(output.mode))?;
+    view.set_item("state", state(output.state))?;
+    view.set_item("records", output.records)?;
+    view.set_item("bytes", output.bytes)?;
+    view.set_item("drops", output.drops)?;
+    view.set_item("queued", output.queued)?;
+    view.set_item("capacity", output.capacity)?;
+    view.set_item("errors", output.errors)?;
+    Ok(view)
+}
+
+fn input<'py>(py: Python<'py>, input: &InputView) -> PyResult<Bound<'py, PyDict>> {
+    let view = PyDict::new(py);
+    view.set_item("name", &input.name)?;
+    view.set_item("pipe", &input.pipe)?;
+    view.set_item("mode", mode(input.mode))?;
+    view.set_item("state", state(input.state))?;
+    view.set_item("records", input.records)?;
+    view.set_item("bytes", input.bytes)?;
+    view.set_item("errors", input.errors)?;
+    let outputs = input
+        .outputs
+        .iter()
+        .map(|o| output(py, o))
+        .collect::<PyResult<Vec<_>>>()?;
+    view.set_item("outputs", outputs)?;
+    Ok(view)
+}
+
+/// Splitter of the pipes of a configuration
+#[pyclass(name = "Splitter", module = "psplit")]
+struct PySplitter {
+    /// Splitter until it is started
+    splitter: Option<Splitter>,
+    /// Splitter started, until it is stopped
+    started: bool,
+    /// Handle on the topology and the run
+    handle: SplitterHandle,
+}
+
+#[pymethods]
+impl PySplitter {
+    /// Load the topology `name`, or every topology when `None`, from the
+    /// configuration file at the path `config` or the sections of `config`
+    #[new]
+    #[pyo3(signature = (config, name=None))]
+    fn new(config: &Bound<'_, PyAny>, name: Option<&str>) -> PyResult<PySplitter> {
+        let loaded = match config.downcast::<PyDict>() {
+            Ok(sections) => {
+                let written = WRITTEN.fetch_add(1, Ordering::Relaxed);
+                let path =
+                    env::temp_dir().join(format!("psplit-{}-{}.ini", std::process::id(), written));
+                fs::write(&path, render(sections)?)?;
+                let loaded = Splitter::load(&path, name);
+                let _ = fs::remove_file(&path);
+                loaded
+            }
+            Err(_) => Splitter::load(config.str()?.to_string(), name),
+        };
+        let splitter = loaded.map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PySplitter {
+            handle: splitter.handle(),
+            splitter: Some(splitter),
+            started: false,
+        })
+    }
+
+    /// Split the pipes on a new thread, once
+    fn start(&mut self) -> PyResult<()> {
+        let Some(splitter) = self.splitter.take() else {
+            return Err(PyRuntimeError::new_err("splitter already started"));
+        };
+        let handle = splitter
+            .spawn()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        if !handle.is_running() {
+            handle.wait();
+            return Err(PyRuntimeError::new_err("splitter could not be started"));
+        }
+        self.started = true;
+        Ok(())
+    }
+
+    /// Stop the splitter and wait for its workers to drain, returning the
+    /// exit status of the `psplit` binary, `None` when it is not running
+    fn stop(&mut self, py: Python<'_>) -> Option<u8> {
+        if !std::mem::take(&mut self.started) {
+            return None;
+        }
+        let handle = self.handle.clone();
+        Some(py.allow_threads(move || handle.stop().code()))
+    }
+
+    /// Whether the splitter is running
+    #[getter]
+    fn running(&self) -> bool {
+        self.handle.is_running()
+    }
+
+    /// Status report, one line per pipe as in the status file
+    fn stats(&self) -> String {
+        let topology = &self.handle.topology;
+        status::report(&topology.settings, &topology.inputs)
+    }
+
+    /// Inputs and outputs with the state and counters of their pipes, as
+    /// dictionaries
+    fn topology<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let topology = self.handle.topology();
+        let view = PyDict::new(py);
+        view.set_item("instance", topology.instance)?;
+        view.set_item("root", topology.root)?;
+        if let Some(usage) = topology.process {
+            let process = PyDict::new(py);
+            process.set_item("fds", usage.fds)?;
+            process.set_item("threads", usage.threads)?;
+            process.set_item("rss", usage.rss)?;
+            process.set_item("queued_bytes", usage.queued_bytes)?;
+            process.set_item("uptime", usage.uptime.as_secs_f64())?;
+            view.set_item("process", process)?;
+        }
+        let inputs = topology
+            .inputs
+            .iter()
+            .map(|i| input(py, i))
+            .collect::<PyResult<Vec<_>>>()?;
+        view.set_item("inputs", inputs)?;
+        Ok(view)
+    }
+
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        slf.start()?;
+        Ok(slf)
+    }
+
+    #[pyo3(signature = (*_exc))]
+    fn __exit__(&mut self, py: Python<'_>, _exc: &Bound<'_, PyAny>) {
+        self.stop(py);
+    }
+}
+
+impl Drop for PySplitter {
+    fn drop(&mut self) {
+        // Splitters left running by the interpreter are stopped with it
+        if std::mem::take(&mut self.started) {
+            self.handle.stop();
+        }
+    }
+}
+
+#[pymodule]
+fn psplit(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PySplitter>()
+}
@@ -0,0 +1,380 @@
+//! Bounded queue of records between a reader and one of its writers.
+//!
+//! Unlike a channel, a waiting writer can be woken without a record, so it
+//! blocks until there is either data to write or a signal to act upon. With
+//! an [`Overflow`] buffer, records arriving while the queue is full, or while
+//! earlier ones are still in the buffer, go to the buffer instead. A queue
+//! retaining records for its first consumer is unbounded until it attaches.
+//! The capacity can be changed while the queue is in use, records already
+//! queued beyond a lowered capacity being kept.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::overflow::Overflow;
+use crate::Record;
+
+/// Why a record could not be queued
+#[derive(Debug)]
+pub(crate) enum PushError {
+    /// The queue is at capacity, the record is handed back
+    Full(Record),
+    /// The writer is gone
+    Closed,
+}
+
+/// Outcome of waiting for a record
+pub(crate) enum Popped {
+    /// Next queued record
+    Record(Record),
+    /// Woken without a record, signals should be checked
+    Woken,
+    /// Nothing arrived within the timeout
+    TimedOut,
+    /// The reader is gone and the queue is drained
+    Closed,
+}
+
+/// Records and flags guarded by the queue lock
+#[derive(Default)]
+struct State {
+    /// Queued records, oldest first
+    records: VecDeque<Record>,
+    /// Bytes of the queued records
+    bytes: usize,
+    /// A wake-up is pending
+    woken: bool,
+    /// No record will be pushed or popped anymore
+    closed: bool,
+    /// Records that did not fit, newer than the queued ones
+    overflow: Option<Overflow>,
+    /// Records are kept regardless of capacity until a consumer attaches
+    retain: bool,
+}
+
+impl State {
+    /// Whether no record is queued or buffered
+    fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.overflow.as_ref().is_none_or(Overflow::is_empty)
+    }
+
+    /// Oldest record, queued or buffered
+    fn pop_front(&mut self) -> Option<Record> {
+        match self.records.pop_front() {
+            Some(record) => {
+                self.bytes -= record.data.len();
+                Some(record)
+            }
+            None => self.overflow.as_mut().and_then(Overflow::pop),
+        }
+    }
+
+    /// Bytes of the oldest record, queued or buffered
+    fn front_len(&mut self) -> Option<usize> {
+        match self.records.front() {
+            Some(record) => Some(record.data.len()),
+            None => self.overflow.as_mut().and_then(Overflow::front_len),
+        }
+    }
+}
+
+/// Bounded queue of records with wake-ups
+pub(crate) struct RecordQueue {
+    /// Queue state
+    state: Mutex<State>,
+    /// Signalled on every push, wake-up and close
+    ready: Condvar,
+    /// Maximum number of queued records
+    capacity: AtomicUsize,
+}
+
+impl RecordQueue {
+    /// Empty queue holding up to `capacity` records, and then as many as
+    /// fit in `overflow`
+    pub fn new(capacity: usize, overflow: Option<Overflow>) -> RecordQueue {
+        RecordQueue {
+            state: Mutex::new(State {
+                overflow,
+                ..State::default()
+            }),
+            ready: Condvar::new(),
+            capacity: AtomicUsize::new(capacity),
+        }
+    }
+
+    /// Maximum number of queued records
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Hold up to `capacity` records from the next push on
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    /// Queue `record` unless the queue is full or closed. Returns the
+    /// buffered records dropped to make room for it.
+    pub fn try_push(&self, record: Record) -> Result<Vec<Record>, PushError> {
+        self.lock().push(record)
+    }
+
+    /// Lock the queue for pushing, the writer cannot pop nor close it until
+    /// the [`Pusher`] is dropped
+    pub fn lock(&self) -> Pusher<'_> {
+        Pusher {
+            state: self.state.lock().unwrap(),
+            queue: self,
+        }
+    }
+
+    /// Bytes of the queued records, buffered ones aside
+    pub fn queued_bytes(&self) -> usize {
+        self.state.lock().unwrap().bytes
+    }
+
+    /// Whether no record is queued
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().is_empty()
+    }
+
+    /// Next record, if one is queued
+    pub fn try_pop(&self) -> Option<Record> {
+        self.state.lock().unwrap().pop_front()
+    }
+
+    /// Next record, if one is queued and holds at most `max_len` bytes
+    pub fn try_pop_fitting(&self, max_len: usize) -> Option<Record> {
+        let mut state = self.state.lock().unwrap();
+        match state.front_len() {
+            Some(len) if len <= max_len => state.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Wait for the next record, a wake-up or the end of the queue, for at
+    /// most `timeout` when set
+    pub fn pop_wait(&self, timeout: Option<Duration>) -> Popped {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(record) = state.pop_front() {
+                return Popped::Record(record);
+            }
+            if state.closed {
+                return Popped::Closed;
+            }
+            if state.woken {
+                state.woken = false;
+                return Popped::Woken;
+            }
+            state = match timeout {
+                None => self.ready.wait(state).unwrap(),
+                Some(timeout) => {
+                    let (state, result) = self.ready.wait_timeout(state, timeout).unwrap();
+                    if result.timed_out() && state.is_empty() && !state.woken {
+                        return match state.closed {
+                            true => Popped::Closed,
+                            false => Popped::TimedOut,
+                        };
+                    }
+                    state
+                }
+            };
+        }
+    }
+
+    /// Interrupt the wait of the writer, so it checks its signals
+    pub fn wake(&self) {
+        self.state.lock().unwrap().woken = true;
+        self.ready.notify_one();
+    }
+
+    /// End the queue, the remaining records can still be popped
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_one();
+    }
+
+    /// Keep every record until [`RecordQueue::attach`]
+    pub fn retain_until_attached(&self) {
+        self.state.lock().unwrap().retain = true;
+    }
+
+    /// A consumer attached, the capacity applies from now on
+    pub fn attach(&self) {
+        self.state.lock().unwrap().retain = false;
+    }
+
+    /// Whether records are kept until a consumer attaches
+    pub fn retains(&self) -> bool {
+        self.state.lock().unwrap().retain
+    }
+
+    /// Bytes of the overflow buffer, if any
+    #[cfg(test)]
+    pub fn overflow_size(&self) -> Option<usize> {
+        self.state
+            .lock()
+            .unwrap()
+            .overflow
+            .as_ref()
+            .map(Overflow::size)
+    }
+
+    /// Accept records again after the queue was closed
+    pub fn reopen(&self) {
+        self.state.lock().unwrap().closed = false;
+    }
+}
+
+/// Queue locked for pushing, so records go to several queues only once
+/// all of them have room
+pub(crate) struct Pusher<'a> {
+    /// Queue state, locked
+    state: MutexGuard<'a, State>,
+    /// Locked queue
+    queue: &'a RecordQueue,
+}
+
+impl Pusher<'_> {
+    /// Whether the writer is gone
+    pub fn is_closed(&self) -> bool {
+        self.state.closed
+    }
+
+    /// Whether the queue is at capacity
+    fn is_full(&self) -> bool {
+        self.state.records.len() >= self.queue.capacity() && !self.state.retain
+    }
+
+    /// Whether `record` would be queued or buffered without dropping any
+    /// other record
+    pub fn accepts(&mut self, record: &Record) -> bool {
+        if self.state.closed {
+            return false;
+        }
+        let full = self.is_full();
+        match self.state.overflow.as_mut() {
+            Some(overflow) if full || !overflow.is_empty() => overflow.fits(record.data.len()),
+            _ => !full,
+        }
+    }
+
+    /// Queue `record`, see [`RecordQueue::try_push`]
+    pub fn push(&mut self, record: Record) -> Result<Vec<Record>, PushError> {
+        if self.state.closed {
+            return Err(PushError::Closed);
+        }
+        let full = self.is_full();
+        let state = &mut *self.state;
+        let dropped = match state.overflow.as_mut() {
+            // Buffered records go first, so later ones are buffered as well
+            Some(overflow) if full || !overflow.is_empty() => match overflow.push(&record) {
+                Ok(dropped) => dropped,
+                Err(_) => return Err(PushError::Full(record)),
+            },
+            _ if full => return Err(PushError::Full(record)),
+            _ => {
+                state.bytes += record.data.len();
+                state.records.push_back(record);
+                Vec::new()
+            }
+        };
+        self.queue.ready.notify_one();
+        Ok(dropped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    fn record(seq: u64) -> Record {
+        Record {
+            seq,
+            data: Vec::new(),
+            received: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn push_wake_and_close() {
+        let queue = RecordQueue::new(1, None);
+        let fuel = Record {
+            data: b"fuel=12\n".to_vec(),
+            ..record(1)
+        };
+        assert!(queue.try_push(fuel).is_ok());
+        assert_eq!(queue.queued_bytes(), 8);
+        assert!(!queue.lock().accepts(&record(2)));
+        assert!(matches!(queue.try_push(record(2)), Err(PushError::Full(_))));
+        assert!(matches!(queue.pop_wait(None), Popped::Record(r) if r.seq == 1));
+        assert!(queue.lock().accepts(&record(2)));
+        assert_eq!(queue.queued_bytes(), 0);
+
+        queue.wake();
+        assert!(matches!(queue.pop_wait(None), Popped::Woken));
+        assert!(matches!(
+            queue.pop_wait(Some(Duration::from_millis(1))),
+            Popped::TimedOut
+        ));
+
+        queue.close();
+        assert!(queue.lock().is_closed());
+        assert!(!queue.lock().accepts(&record(3)));
+        assert!(matches!(queue.try_push(record(3)), Err(PushError::Closed)));
+        assert!(matches!(queue.pop_wait(None), Popped::Closed));
+    }
+
+    #[test]
+    fn resize() {
+        let queue = RecordQueue::new(1, None);
+        assert!(queue.try_push(record(1)).is_ok());
+        queue.set_capacity(2);
+        assert!(queue.try_push(record(2)).is_ok());
+        assert!(matches!(queue.try_push(record(3)), Err(PushError::Full(_))));
+
+        // Records beyond a lowered capacity stay queued
+        queue.set_capacity(1);
+        assert!(matches!(queue.try_push(record(3)), Err(PushError::Full(_))));
+        assert!(matches!(queue.pop_wait(None), Popped::Record(r) if r.seq == 1));
+        assert!(matches!(queue.pop_wait(None), Popped::Record(r) if r.seq == 2));
+        assert!(queue.try_push(record(3)).is_ok());
+    }
+
+    #[test]
+    fn overflow_in_order() {
+        let queue = RecordQueue::new(1, Some(Overflow::new(64)));
+        for seq in 1..=3 {
+            assert!(queue.try_push(record(seq)).unwrap().is_empty());
+        }
+        // The buffer holds 2 empty records, the oldest one makes room
+        assert!(!queue.lock().accepts(&record(4)));
+        let dropped = queue.try_push(record(4)).unwrap();
+        assert_eq!(dropped.iter().map(|r| r.seq).collect::<Vec<_>>(), [2]);
+        assert!(matches!(queue.try_pop(), Some(r) if r.seq == 1));
+        assert_eq!(queue.try_pop_fitting(0).map(|r| r.seq), Some(3));
+        // The queue has room, but buffered records come first
+        assert!(queue.try_push(record(5)).unwrap().is_empty());
+        assert!(matches!(queue.pop_wait(None), Popped::Record(r) if r.seq == 4));
+        assert!(matches!(queue.pop_wait(None), Popped::Record(r) if r.seq == 5));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn retain_until_attached() {
+        let queue = RecordQueue::new(1, None);
+        queue.retain_until_attached();
+        for seq in 1..=3 {
+            assert!(queue.try_push(record(seq)).is_ok());
+        }
+        queue.attach();
+        assert!(!queue.retains());
+        assert!(matches!(queue.try_push(record(4)), Err(PushError::Full(_))));
+        for seq in 1..=3 {
+            assert!(matches!(queue.try_pop(), Some(r) if r.seq == seq));
+        }
+        assert!(queue.try_push(record(5)).is_ok());
+    }
+}
@@ -0,0 +1,92 @@
+//! Factories of the custom sinks and sources, by the scheme they are
+//! referenced with in the configuration, `<scheme>://<target>`, and of the
+//! custom transforms, by name.
+use std::fmt;
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::secrets;
+
+/// Factories registered by scheme
+pub(crate) struct Registry<F: ?Sized> {
+    factories: Mutex<Vec<(String, Arc<F>)>>,
+}
+
+impl<F: ?Sized> Registry<F> {
+    pub const fn new() -> Registry<F> {
+        Registry {
+            factories: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Register `factory` for `scheme`, replacing the previous one
+    pub fn register(&self, scheme: &str, factory: Arc<F>) {
+        let mut factories = self.factories.lock().unwrap();
+        factories.retain(|(s, _)| s != scheme);
+        factories.push((scheme.to_owned(), factory));
+    }
+
+    /// Factory registered for `name`
+    pub fn get(&self, name: &str) -> Option<Arc<F>> {
+        let factories = self.factories.lock().unwrap();
+        let (_, factory) = factories.iter().find(|(s, _)| s == name)?;
+        Some(Arc::clone(factory))
+    }
+
+    /// Target of `uri`, `None` when its scheme has no registered factory
+    /// and `builtin` has none for it either
+    pub fn resolve(&self, uri: &str, builtin: fn(&str) -> Option<Arc<F>>) -> Option<Target<F>> {
+        let (scheme, _) = uri.split_once("://")?;
+        let registered = self.get(scheme);
+        Some(Target {
+            uri: uri.to_owned(),
+            target: scheme.len() + 3,
+            factory: registered.or_else(|| builtin(scheme))?,
+        })
+    }
+}
+
+/// Configured `<scheme>://<target>` with the factory of its scheme
+pub(crate) struct Target<F: ?Sized> {
+    /// Configured value
+    uri: String,
+    /// Offset of the target in `uri`
+    target: usize,
+    /// Factory registered for the scheme
+    pub factory: Arc<F>,
+}
+
+impl<F: ?Sized> Target<F> {
+    /// Part of the value after `<scheme>://`
+    pub fn target(&self) -> &str {
+        &self.uri[self.target..]
+    }
+}
+
+impl<F: ?Sized> Clone for Target<F> {
+    fn clone(&self) -> Target<F> {
+        Target {
+            uri: self.uri.clone(),
+            target: self.target,
+            factory: Arc::clone(&self.factory),
+        }
+    }
+}
+
+impl<F: ?Sized> fmt::Display for Target<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Secret references are kept as such, passwords in the clear hidden
+        write!(f, "{}", secrets::redact(&self.uri))
+    }
+}
+
+/// Wait until `fd` is ready for `events`, for at most `timeout`
+pub(crate) fn wait_ready(fd: RawFd, events: libc::c_short, timeout: Duration) {
+    let mut fds = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fds, 1, timeout.as_millis() as libc::c_int) };
+}
@@ -0,0 +1,131 @@
+//! Recovery of worker threads that panicked: the panic is logged with the
+//! pipe the worker serves and the worker is started again as allowed by the
+//! `[DEFAULT] restart` policy, rather than leaving the pipe dead while the
+//! rest of the topology keeps running.
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::Duration;
+
+/// What to do with a worker that panicked, `[DEFAULT] restart`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RestartPolicy {
+    /// Leave the pipe stopped, `never`
+    Never,
+    /// Start the worker again after every panic, `always`
+    Always,
+    /// Start the worker again after its first panics only, a count
+    Limit(u32),
+}
+
+impl RestartPolicy {
+    /// Parse `always`, `never` or a number of restarts
+    pub fn parse(value: &str) -> Option<RestartPolicy> {
+        match value {
+            "always" => Some(RestartPolicy::Always),
+            "never" => Some(RestartPolicy::Never),
+            count => count.parse().ok().map(RestartPolicy::Limit),
+        }
+    }
+
+    /// Whether a worker may be started again after `panics` panics
+    fn allows(self, panics: u32) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::Limit(limit) => panics <= limit,
+        }
+    }
+}
+
+/// Text of a panic payload
+fn message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(text) = payload.downcast_ref::<&str>() {
+        text
+    } else if let Some(text) = payload.downcast_ref::<String>() {
+        text
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Run `body`, the worker of `pipe`, until it returns. After a panic the
+/// worker is started again `delay` later while `policy` allows it, and
+/// `panicked` is called with the panic message to account for it.
+pub(crate) fn supervise<P, B, F>(
+    pipe: &P,
+    policy: RestartPolicy,
+    delay: Duration,
+    mut panicked: F,
+    mut body: B,
+) -> io::Result<()>
+where
+    P: fmt::Display,
+    B: FnMut() -> io::Result<()>,
+    F: FnMut(&str),
+{
+    let mut panics = 0;
+    loop {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(&mut body)) {
+            Ok(result) => return result,
+            Err(payload) => payload,
+        };
+        panics += 1;
+        let message = message(payload.as_ref());
+        log!("Panic -> {} Error {}", pipe, message);
+        panicked(message);
+        if !policy.allows(panics) {
+            log!("Stopping after panic <> {}", pipe);
+            return Err(io::Error::other("worker panicked"));
+        }
+        thread::sleep(delay);
+        log!("Restarting after panic <> {}", pipe);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn restart_after_panic() {
+        assert_eq!(RestartPolicy::parse("2"), Some(RestartPolicy::Limit(2)));
+        assert_eq!(RestartPolicy::parse("sometimes"), None);
+
+        let mut runs = 0;
+        let mut counted = 0;
+        let result = supervise(
+            &"worker",
+            RestartPolicy::Always,
+            Duration::ZERO,
+            |_| counted += 1,
+            || {
+                runs += 1;
+                match runs {
+                    1 => panic!("first run"),
+                    2 => panic!("{}", String::from("second run")),
+                    _ => Ok(()),
+                }
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!((runs, counted), (3, 2));
+
+        let mut runs = 0;
+        let result = supervise(
+            &"worker",
+            RestartPolicy::Limit(1),
+            Duration::ZERO,
+            |_| {},
+            || -> io::Result<()> {
+                runs += 1;
+                panic!("run {runs}")
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(runs, 2);
+        assert!(!RestartPolicy::Never.allows(1));
+    }
+}
@@ -0,0 +1,89 @@
+//! split(1) style distribution of the records of an input across its
+//! outputs, `rotate=<records>` or `rotate_bytes=<bytes>`: a chunk of records
+//! goes to the first output, the next chunk to the second one and so on,
+//! wrapping around after the last output. Records are never cut, a chunk of
+//! bytes ends with the record reaching its size. Outputs that cannot take
+//! records, disconnected or evicted, are passed over.
+
+/// Size of the chunk each output gets in turn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ChunkSize {
+    /// A number of records
+    Records(u64),
+    /// A number of bytes
+    Bytes(u64),
+}
+
+/// Output taking the records of the current chunk
+pub(crate) struct Rotation {
+    size: ChunkSize,
+    /// Index of the output taking the current chunk
+    current: usize,
+    /// Records or bytes of the current chunk
+    filled: u64,
+}
+
+impl Rotation {
+    /// Rotation starting with the first output
+    pub fn new(size: ChunkSize) -> Rotation {
+        Rotation {
+            size,
+            current: 0,
+            filled: 0,
+        }
+    }
+
+    /// Index of the output, among `count`, taking a record of `len` bytes,
+    /// `usable` telling whether an output can take records
+    pub fn select<F: Fn(usize) -> bool>(
+        &mut self,
+        len: usize,
+        count: usize,
+        usable: F,
+    ) -> Option<usize> {
+        let limit = match self.size {
+            ChunkSize::Records(records) => records,
+            ChunkSize::Bytes(bytes) => bytes,
+        };
+        if self.filled >= limit {
+            self.current += 1;
+            self.filled = 0;
+        }
+        let start = self.current % count.max(1);
+        let index = (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|&index| usable(index))?;
+        if index != start {
+            self.filled = 0;
+        }
+        self.current = index;
+        self.filled += match self.size {
+            ChunkSize::Records(_) => 1,
+            ChunkSize::Bytes(_) => len as u64,
+        };
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotate_chunks() {
+        let mut rotation = Rotation::new(ChunkSize::Records(2));
+        let picked: Vec<_> = (0..7)
+            .map(|_| rotation.select(10, 3, |_| true).unwrap())
+            .collect();
+        assert_eq!(picked, [0, 0, 1, 1, 2, 2, 0]);
+
+        // The second output is passed over
+        let mut rotation = Rotation::new(ChunkSize::Bytes(100));
+        let picked: Vec<_> = [60, 60, 30, 80, 10]
+            .iter()
+            .map(|&len| rotation.select(len, 3, |index| index != 1).unwrap())
+            .collect();
+        assert_eq!(picked, [0, 0, 2, 2, 0]);
+        assert_eq!(rotation.select(1, 3, |_| false), None);
+    }
+}
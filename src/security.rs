@@ -0,0 +1,165 @@
+//! Checks guarding against FIFO squatting and symlink attacks in the pipe
+//! root, which is commonly placed in a shared directory such as `/tmp`, and
+//! security labelling of the FIFOs created there.
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+/// What to do when the pipe root is unsafe, `[DEFAULT] root_permissions`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RootPolicy {
+    /// Refuse to start
+    Refuse,
+    /// Start after printing a warning
+    Warn,
+}
+
+/// What to do with a file other than a FIFO at a pipe path,
+/// `[DEFAULT] non_fifo`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum NonFifoPolicy {
+    /// Fail
+    #[default]
+    Error,
+    /// Remove the file so a FIFO is created in its place
+    Replace,
+    /// Leave the file alone and disable the pipe
+    Skip,
+}
+
+/// Apply `policy` when something other than a FIFO, such as a regular file
+/// or a symbolic link, exists at `path`, returning whether the pipe is usable
+pub(crate) fn check_fifo(path: &Path, policy: NonFifoPolicy) -> io::Result<bool> {
+    let file_type = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata.file_type(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e),
+    };
+    if file_type.is_fifo() {
+        return Ok(true);
+    }
+
+    match policy {
+        NonFifoPolicy::Error => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a FIFO", path.display()),
+        )),
+        NonFifoPolicy::Replace => {
+            log!(
+                "Warning: replacing file that is not a FIFO <> {}",
+                path.display()
+            );
+            fs::remove_file(path)?;
+            Ok(true)
+        }
+        NonFifoPolicy::Skip => Ok(false),
+    }
+}
+
+/// Reasons the directory `root` is unsafe to create FIFOs in
+pub(crate) fn root_problems(root: &Path) -> io::Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    if fs::symlink_metadata(root)?.file_type().is_symlink() {
+        problems.push("is a symbolic link".to_owned());
+    }
+    let metadata = fs::metadata(root)?;
+    if metadata.mode() & 0o002 != 0 {
+        problems.push("is world-writable".to_owned());
+    }
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != euid {
+        problems.push(format!(
+            "is owned by uid {} instead of {}",
+            metadata.uid(),
+            euid
+        ));
+    }
+    Ok(problems)
+}
+
+/// Set the security label `label` of the file at `path`, stored in the
+/// extended attribute `attr` such as `security.selinux` or `security.SMACK64`
+pub(crate) fn set_label(path: &Path, attr: &str, label: &str) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let attr = CString::new(attr)?;
+    let result = unsafe {
+        libc::lsetxattr(
+            path.as_ptr(),
+            attr.as_ptr(),
+            label.as_ptr() as *const libc::c_void,
+            label.len(),
+            0,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Apply `policy` to the problems of the directory `root`
+pub(crate) fn check_root(root: &Path, policy: RootPolicy) -> io::Result<()> {
+    let problems = root_problems(root)?;
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!(
+        "pipe root directory {} {}",
+        root.display(),
+        problems.join(", ")
+    );
+    match policy {
+        RootPolicy::Warn => {
+            log!("Warning: {}", message);
+            Ok(())
+        }
+        RootPolicy::Refuse => Err(io::Error::new(io::ErrorKind::PermissionDenied, message)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn world_writable_root() {
+        let root = temp_dir().join("p_split_root_check");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(root_problems(&root).unwrap().is_empty());
+
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o777)).unwrap();
+        assert_eq!(root_problems(&root).unwrap(), vec!["is world-writable"]);
+        assert!(check_root(&root, RootPolicy::Refuse).is_err());
+        assert!(check_root(&root, RootPolicy::Warn).is_ok());
+    }
+
+    #[test]
+    fn label_file() {
+        let path = temp_dir().join("p_split_label");
+        fs::write(&path, b"").unwrap();
+
+        // user attributes stand in for security ones, which need privileges
+        set_label(&path, "user.psplit", "fuel_t").expect("label");
+        let mut value = [0u8; 16];
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let len = unsafe {
+            libc::getxattr(
+                c_path.as_ptr(),
+                c"user.psplit".as_ptr(),
+                value.as_mut_ptr() as *mut libc::c_void,
+                value.len(),
+            )
+        };
+        assert_eq!(&value[..len as usize], b"fuel_t");
+    }
+}
@@ -0,0 +1,60 @@
+//! Smoke test of a device image: a scratch topology under a temporary root,
+//! known records pushed through its input FIFO and every output checked to
+//! receive all of them, in order.
+use std::io;
+use std::time::Duration;
+
+use crate::testing::{Consumer, Harness, Producer};
+
+/// Outputs of the scratch topology
+const OUTPUTS: usize = 2;
+/// Longest wait for the splitter to take or deliver the records
+const DEADLINE: Duration = Duration::from_secs(10);
+
+/// Record number `n` pushed through the topology
+fn record(n: usize) -> String {
+    format!("selftest {n}\n")
+}
+
+/// Push `records` records through a scratch topology, `true` when every
+/// output received them all in order
+pub(crate) fn run(records: usize) -> io::Result<bool> {
+    let mut config = "[DEFAULT]\nroot={root}\n[PIPES]\nin=1,rt\n[in]\n".to_owned();
+    for o in 1..=OUTPUTS {
+        config.push_str(&format!("out{o}=1,wt,queue={records}\n"));
+    }
+    let harness = Harness::spawn(&config)?;
+    let pipe = |name: &str| harness.pipe(name).unwrap_or_default().to_owned();
+
+    let mut consumers = Vec::with_capacity(OUTPUTS);
+    for o in 1..=OUTPUTS {
+        let output = pipe(&format!("out{o}"));
+        consumers.push((Consumer::open(&output)?, output));
+    }
+
+    let expected: String = (1..=records).map(record).collect();
+    let mut producer = Producer::open(pipe("in"), DEADLINE)?;
+    producer.write(expected.as_bytes(), DEADLINE)?;
+    let mut passed = true;
+    for (consumer, output) in consumers.iter_mut() {
+        let received = consumer.collect(expected.len(), DEADLINE)?;
+        let lines = received.split(|b| *b == b'\n').count() - 1;
+        if received == expected.as_bytes() {
+            log!("Self-test -> {output} passed, {lines} records");
+        } else {
+            log!("Self-test -> {output} failed, {lines} of {records} records in order");
+            passed = false;
+        }
+    }
+    Ok(passed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn self_test_passes() {
+        assert!(run(50).expect("self-test"));
+    }
+}
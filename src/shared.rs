@@ -0,0 +1,64 @@
+//! Outputs listed by several inputs. Each input has a writer of its own on
+//! the shared FIFO, and a record longer than `PIPE_BUF` takes several
+//! writes, which the writes of the other inputs could land between. A
+//! writer holds the turn of the FIFO from the first write of a record to
+//! the last, the writers of the other inputs waiting for it before writing,
+//! so records are interleaved whole.
+//!
+//! `{input}` in the options of an output is the name of the input feeding
+//! it, `prefix=[{input}]` telling the consumer where every record came from.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Identifiers of the writers, 0 meaning none
+static WRITERS: AtomicU64 = AtomicU64::new(1);
+
+/// Identifier of a new writer
+pub(crate) fn writer_id() -> u64 {
+    WRITERS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Turn to write a record to a FIFO shared by several inputs
+#[derive(Debug, Default)]
+pub(crate) struct WriteTurn {
+    /// Writer holding the turn, 0 when it is free
+    owner: AtomicU64,
+}
+
+impl WriteTurn {
+    /// Take the turn for `writer`, false while another writer holds it
+    pub fn take(&self, writer: u64) -> bool {
+        match self
+            .owner
+            .compare_exchange(0, writer, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => true,
+            Err(owner) => owner == writer,
+        }
+    }
+
+    /// Give the turn back, if `writer` holds it
+    pub fn release(&self, writer: u64) {
+        let _ = self
+            .owner
+            .compare_exchange(writer, 0, Ordering::Release, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn take_and_release() {
+        let turn = WriteTurn::default();
+        let (fuel, gps) = (writer_id(), writer_id());
+        assert!(turn.take(fuel));
+        assert!(turn.take(fuel));
+        assert!(!turn.take(gps));
+        // Only the holder gives the turn back
+        turn.release(gps);
+        assert!(!turn.take(gps));
+        turn.release(fuel);
+        assert!(turn.take(gps));
+    }
+}
@@ -0,0 +1,248 @@
+//! Shared memory ring buffer output, `sink=shm://<name>[?size=<bytes>]`, so
+//! high rate consumers poll memory instead of paying a system call per read.
+//!
+//! The ring is the POSIX shared memory object `/<name>`, `/dev/shm/<name>`
+//! on Linux, laid out as a 64 byte header followed by `capacity` bytes of
+//! records, all integers in native byte order:
+//!
+//! ```c
+//! struct psplit_ring {
+//!     uint32_t magic;        /* 0x50535242, "PSRB" */
+//!     uint32_t version;      /* 1 */
+//!     uint64_t capacity;     /* bytes of records after the header */
+//!     uint64_t head;         /* bytes ever written, stored with release */
+//!     uint64_t records;      /* records ever written */
+//!     uint8_t reserved[32];
+//! };
+//! ```
+//!
+//! A record at ring offset `head % capacity` is a `uint32_t` length followed
+//! by its bytes, padded to 8 bytes. A length of `0xffffffff` marks the rest
+//! of the ring as unused, the next record is at offset 0. A consumer keeps
+//! its own position, starting at `head`, and reads while it is behind
+//! `head` (loaded with acquire). The splitter never waits for consumers:
+//! once `head` is more than `capacity` ahead of a position the records there
+//! were overwritten, so a consumer checks `head` again after copying a
+//! record and resumes from the current `head` when it was lapped.
+use std::ffi::CString;
+use std::io;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::sink::Sink;
+
+/// `"PSRB"`
+const MAGIC: u32 = 0x5053_5242;
+/// Version of the ring layout
+const VERSION: u32 = 1;
+/// Bytes of the header before the records
+const HEADER: usize = 64;
+/// Offset of `head` in the header
+const HEAD: usize = 16;
+/// Offset of `records` in the header
+const RECORDS: usize = 24;
+/// Length marking the end of the used part of the ring
+const WRAP: u32 = u32::MAX;
+/// Bytes of records of a ring without `size=`
+const DEFAULT_SIZE: usize = 1 << 20;
+
+/// `len` rounded up to the 8 byte alignment of records
+fn align(len: usize) -> usize {
+    (len + 7) & !7
+}
+
+/// Ring buffer in shared memory, written by a single splitter output
+pub(crate) struct ShmRing {
+    /// Name of the shared memory object, with its leading `/`
+    name: CString,
+    /// Bytes of records
+    capacity: usize,
+    /// Mapping of the object, null while closed
+    map: *mut u8,
+}
+
+// The mapping is only used from the writer thread owning the sink
+unsafe impl Send for ShmRing {}
+
+impl ShmRing {
+    /// Ring configured as `<name>[?size=<bytes>]`
+    pub fn new(target: &str) -> io::Result<ShmRing> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let (name, size) = match target.split_once("?size=") {
+            Some((name, size)) => match size.parse::<usize>() {
+                Ok(size) if size >= 16 => (name, align(size)),
+                _ => return Err(invalid(format!("Invalid ring size '{size}'"))),
+            },
+            None => (target, DEFAULT_SIZE),
+        };
+        if name.is_empty() || name.contains('/') {
+            return Err(invalid(format!("Invalid ring name '{name}'")));
+        }
+        Ok(ShmRing {
+            name: CString::new(format!("/{name}")).map_err(|e| invalid(e.to_string()))?,
+            capacity: size,
+            map: ptr::null_mut(),
+        })
+    }
+
+    /// Header field at `offset`
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        unsafe { &*(self.map.add(offset) as *const AtomicU64) }
+    }
+
+    /// Store `value` as a `uint32_t` at ring offset `at`
+    fn put_len(&mut self, at: usize, value: u32) {
+        unsafe { ptr::write_unaligned(self.map.add(HEADER + at) as *mut u32, value) }
+    }
+}
+
+impl Sink for ShmRing {
+    fn open(&mut self) -> io::Result<()> {
+        let len = HEADER + self.capacity;
+        let fd = unsafe { libc::shm_open(self.name.as_ptr(), libc::O_CREAT | libc::O_RDWR, 0o644) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let map = unsafe {
+            let mut stat: libc::stat = std::mem::zeroed();
+            let sized = libc::fstat(fd, &mut stat) == 0
+                && (stat.st_size as usize == len || libc::ftruncate(fd, len as libc::off_t) == 0);
+            let map = match sized {
+                true => libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    0,
+                ),
+                false => libc::MAP_FAILED,
+            };
+            let error = io::Error::last_os_error();
+            libc::close(fd);
+            if map == libc::MAP_FAILED {
+                return Err(error);
+            }
+            map as *mut u8
+        };
+        self.map = map;
+
+        // A ring left by a previous run with the same layout is continued,
+        // its consumers keep their positions
+        let header = unsafe { std::slice::from_raw_parts(map, 16) };
+        let reuse = header[..4] == MAGIC.to_ne_bytes()
+            && header[4..8] == VERSION.to_ne_bytes()
+            && header[8..16] == (self.capacity as u64).to_ne_bytes();
+        if !reuse {
+            unsafe {
+                ptr::write_bytes(map, 0, HEADER);
+                ptr::write_unaligned(map as *mut u32, MAGIC);
+                ptr::write_unaligned(map.add(4) as *mut u32, VERSION);
+                ptr::write_unaligned(map.add(8) as *mut u64, self.capacity as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, record: &[u8]) -> io::Result<()> {
+        let need = align(4 + record.len());
+        if need > self.capacity || record.len() >= WRAP as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record larger than the ring",
+            ));
+        }
+        let mut head = self.counter(HEAD).load(Ordering::Relaxed);
+        let mut at = head as usize % self.capacity;
+        if self.capacity - at < need {
+            self.put_len(at, WRAP);
+            head += (self.capacity - at) as u64;
+            at = 0;
+        }
+        self.put_len(at, record.len() as u32);
+        unsafe {
+            let data = self.map.add(HEADER + at + 4);
+            ptr::copy_nonoverlapping(record.as_ptr(), data, record.len());
+        }
+        self.counter(HEAD)
+            .store(head + need as u64, Ordering::Release);
+        self.counter(RECORDS).fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if !self.map.is_null() {
+            unsafe { libc::munmap(self.map as *mut libc::c_void, HEADER + self.capacity) };
+            self.map = ptr::null_mut();
+        }
+    }
+}
+
+impl Drop for ShmRing {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sink::SinkTarget;
+
+    /// Records of the ring `path` from position `from`, as a consumer reads
+    /// them, and the position after them
+    fn consume(path: &str, from: u64) -> (Vec<Vec<u8>>, u64) {
+        let ring = std::fs::read(path).expect("ring");
+        let word = |at: usize| u64::from_ne_bytes(ring[at..at + 8].try_into().unwrap());
+        let (capacity, head) = (word(8) as usize, word(HEAD));
+        let mut records = Vec::new();
+        let mut position = from;
+        while position < head {
+            let at = position as usize % capacity;
+            let len = u32::from_ne_bytes(ring[HEADER + at..HEADER + at + 4].try_into().unwrap());
+            if len == WRAP {
+                position += (capacity - at) as u64;
+                continue;
+            }
+            let data = &ring[HEADER + at + 4..HEADER + at + 4 + len as usize];
+            records.push(data.to_vec());
+            position += align(4 + len as usize) as u64;
+        }
+        (records, position)
+    }
+
+    #[test]
+    fn write_ring() {
+        assert!(ShmRing::new("fuel?size=8").is_err());
+        assert!(ShmRing::new("a/b").is_err());
+
+        let name = format!("psplit-ring-{}", std::process::id());
+        let path = format!("/dev/shm/{name}");
+        let target = SinkTarget::resolve_sink(&format!("shm://{name}?size=64")).expect("builtin");
+        let mut sink = target.open_sink().expect("open");
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 128);
+
+        sink.write(b"fuel=12\n").unwrap();
+        sink.write(b"speed=80\n").unwrap();
+        sink.write(b"twenty bytes record\n").unwrap();
+        let (records, position) = consume(&path, 0);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1], b"speed=80\n");
+        assert_eq!(position, 56);
+
+        // 8 bytes left: the next record wraps to the start of the ring
+        sink.write(b"gps=1\n").unwrap();
+        let (records, position) = consume(&path, position);
+        assert_eq!(records, [b"gps=1\n".to_vec()]);
+        assert_eq!(position, 80);
+        assert!(sink.write(&[0; 64]).is_err());
+        sink.close();
+
+        // A new run continues the ring
+        let mut sink = target.open_sink().expect("open");
+        sink.write(b"fuel=13\n").unwrap();
+        assert_eq!(consume(&path, position).0, [b"fuel=13\n".to_vec()]);
+        sink.close();
+        let _ = std::fs::remove_file(path);
+    }
+}
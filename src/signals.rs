@@ -0,0 +1,147 @@
+//! Signals of the splitter, received through a `signalfd` polled by the
+//! supervising loop along with its timers rather than by asynchronous
+//! handlers:
+//!
+//! - `SIGTERM` and `SIGINT` stop the workers and return,
+//! - `SIGHUP` reopens the log file, for external tools that move it away,
+//! - `SIGUSR1` logs the status report.
+//!
+//! The signals are blocked before any worker is started, so every thread
+//! inherits the mask and they are only ever read from the descriptor.
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+
+/// Signals handled by the splitter
+const HANDLED: [libc::c_int; 4] = [libc::SIGTERM, libc::SIGINT, libc::SIGHUP, libc::SIGUSR1];
+
+/// Signals of the calling thread and the threads it starts, as a descriptor
+/// readable once one is pending
+pub(crate) struct Signals {
+    /// Non blocking `signalfd` of the handled signals
+    fd: OwnedFd,
+    /// Mask of the thread before the signals were blocked
+    previous: libc::sigset_t,
+}
+
+impl Signals {
+    /// Block the handled signals and receive them through a descriptor
+    pub fn install() -> io::Result<Signals> {
+        let mut set: libc::sigset_t = unsafe { mem::zeroed() };
+        let mut previous: libc::sigset_t = unsafe { mem::zeroed() };
+        unsafe {
+            libc::sigemptyset(&mut set);
+            for signal in HANDLED {
+                libc::sigaddset(&mut set, signal);
+            }
+        }
+        match unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, &mut previous) } {
+            0 => {}
+            e => return Err(io::Error::from_raw_os_error(e)),
+        }
+        let fd = unsafe { libc::signalfd(-1, &set, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC) };
+        if fd < 0 {
+            let error = io::Error::last_os_error();
+            unsafe { libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut()) };
+            return Err(error);
+        }
+        Ok(Signals {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            previous,
+        })
+    }
+
+    /// Signals pending on the descriptor, none when it is not readable
+    pub fn received(&self) -> io::Result<Vec<libc::c_int>> {
+        let mut received = Vec::new();
+        let mut info: libc::signalfd_siginfo = unsafe { mem::zeroed() };
+        let size = mem::size_of::<libc::signalfd_siginfo>();
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    &mut info as *mut libc::signalfd_siginfo as *mut libc::c_void,
+                    size,
+                )
+            };
+            if read == size as isize {
+                received.push(info.ssi_signo as libc::c_int);
+                continue;
+            }
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::WouldBlock => Ok(received),
+                io::ErrorKind::Interrupted => continue,
+                _ => Err(error),
+            };
+        }
+    }
+}
+
+impl Source for Signals {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).deregister(registry)
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        // Signals left pending would take their default action once unblocked
+        let _ = self.received();
+        unsafe { libc::pthread_sigmask(libc::SIG_SETMASK, &self.previous, std::ptr::null_mut()) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mio::{Events, Poll};
+    use std::time::Duration;
+
+    #[test]
+    fn receive_signals() {
+        let mut signals = Signals::install().expect("signalfd");
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1);
+        poll.registry()
+            .register(&mut signals, Token(0), Interest::READABLE)
+            .unwrap();
+        assert!(signals.received().unwrap().is_empty());
+        unsafe {
+            libc::raise(libc::SIGUSR1);
+            libc::raise(libc::SIGHUP);
+        }
+        poll.poll(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert!(!events.is_empty());
+        let mut received = signals.received().unwrap();
+        received.sort();
+        assert_eq!(received, [libc::SIGHUP, libc::SIGUSR1]);
+
+        // Left pending when the signals are no longer handled
+        unsafe { libc::raise(libc::SIGTERM) };
+        drop(signals);
+    }
+}
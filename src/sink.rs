@@ -0,0 +1,76 @@
+//! Custom output types. An embedder implements [`Sink`] and registers a
+//! factory for a scheme with [`register_sink`]; an output configured with
+//! `sink=<scheme>://<target>` then has its records written to the sink built
+//! for `<target>` instead of a FIFO. The `shm` scheme, a ring buffer in
+//! shared memory, and the `mq` scheme, a POSIX message queue, are built in
+//! unless an embedder registers its own.
+use std::io;
+use std::os::fd::RawFd;
+use std::sync::Arc;
+
+use crate::mq::MqSink;
+use crate::registry::{Registry, Target};
+use crate::secrets;
+use crate::shm::ShmRing;
+
+/// Destination of the records of an output
+pub trait Sink: Send {
+    /// Connect to the destination, called again after a failed write
+    fn open(&mut self) -> io::Result<()>;
+    /// Write the whole of `record`. `WouldBlock` means the destination cannot
+    /// take it yet, it is written again once [`Sink::ready_fd`] is writable
+    /// or after the retry delay; other errors close and reopen the sink.
+    fn write(&mut self, record: &[u8]) -> io::Result<()>;
+    /// Disconnect from the destination
+    fn close(&mut self) {}
+    /// Descriptor polled for writability before a write is retried
+    fn ready_fd(&self) -> Option<RawFd> {
+        None
+    }
+}
+
+/// Builds the sink for a target, the part of `sink=` after `<scheme>://`
+pub type SinkFactory = dyn Fn(&str) -> io::Result<Box<dyn Sink>> + Send + Sync;
+
+/// Sink factories by scheme
+static SINKS: Registry<SinkFactory> = Registry::new();
+
+/// Build the sinks of outputs configured with `sink=<scheme>://...` with
+/// `factory`, replacing any factory registered before for `scheme`. Sinks
+/// must be registered before the configuration is loaded.
+pub fn register_sink<F>(scheme: &str, factory: F)
+where
+    F: Fn(&str) -> io::Result<Box<dyn Sink>> + Send + Sync + 'static,
+{
+    SINKS.register(scheme, Arc::new(factory));
+}
+
+/// Factory of the sink built in for `scheme`
+fn builtin(scheme: &str) -> Option<Arc<SinkFactory>> {
+    match scheme {
+        "shm" => Some(Arc::new(|target: &str| {
+            Ok(Box::new(ShmRing::new(target)?) as Box<dyn Sink>)
+        })),
+        "mq" => Some(Arc::new(|target: &str| {
+            Ok(Box::new(MqSink::new(target)?) as Box<dyn Sink>)
+        })),
+        _ => None,
+    }
+}
+
+/// Sink an output is configured to write to
+pub(crate) type SinkTarget = Target<SinkFactory>;
+
+impl SinkTarget {
+    /// Sink configured as `uri`, `None` when its scheme is not registered
+    pub fn resolve_sink(uri: &str) -> Option<SinkTarget> {
+        SINKS.resolve(uri, builtin)
+    }
+
+    /// Build and open the sink
+    pub fn open_sink(&self) -> io::Result<Box<dyn Sink>> {
+        let mut sink = (self.factory)(&secrets::resolve(self.target())?)?;
+        sink.open()?;
+        Ok(sink)
+    }
+}
@@ -0,0 +1,359 @@
+//! Soak test of a release build, `psplit soak`: a scratch topology run for a
+//! while under chaos, consumers killed, output FIFOs deleted and the
+//! producer paused at random for random durations, then checked against the
+//! invariants the splitter must keep whatever happens to its pipes:
+//!
+//! - no deadlock: once the chaos stops, records pushed through reach every
+//!   output and the splitter stops, each within a deadline;
+//! - bounded memory: the resident memory of the process stays below a limit;
+//! - accurate drop accounting: every record read by the input is written to
+//!   each output or counted as dropped, and each consumer receives records
+//!   in order, without duplicates, never more than were written.
+//!
+//! The chaos is drawn from a seed, logged so a failing run can be replayed.
+use std::fs;
+use std::io;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::process;
+use crate::status;
+use crate::testing::{Consumer, Harness, Producer};
+use crate::SplitIn;
+
+/// Longest wait for the splitter to take, deliver the records or stop
+const DEADLINE: Duration = Duration::from_secs(10);
+/// Delay between two rounds of producing and consuming
+const TICK: Duration = Duration::from_millis(5);
+/// Records written by the producer every round
+const BATCH: u64 = 8;
+/// Records of the queue of every output
+const QUEUE: usize = 64;
+/// Interval between two checks of the memory of the process
+const SAMPLE: Duration = Duration::from_secs(1);
+
+/// Settings of a soak run
+#[derive(Clone, Debug)]
+pub struct SoakOptions {
+    /// How long the chaos lasts
+    pub duration: Duration,
+    /// Outputs of the scratch topology, at least one
+    pub outputs: usize,
+    /// Longest a consumer stays away or the producer pauses
+    pub max_pause: Duration,
+    /// Mean interval between two chaos events
+    pub interval: Duration,
+    /// Resident memory of the process not to exceed, in bytes
+    pub max_rss: u64,
+    /// Seed of the chaos, from the clock when `None`
+    pub seed: Option<u64>,
+}
+
+impl Default for SoakOptions {
+    fn default() -> SoakOptions {
+        SoakOptions {
+            duration: Duration::from_secs(60),
+            outputs: 3,
+            max_pause: Duration::from_secs(2),
+            interval: Duration::from_millis(200),
+            max_rss: 256 << 20,
+            seed: None,
+        }
+    }
+}
+
+/// Xorshift generator, enough to draw the chaos from a seed
+struct Chaos(u64);
+
+impl Chaos {
+    fn new(seed: u64) -> Chaos {
+        Chaos(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Number below `bound`
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+
+    /// Duration up to `max`
+    fn duration(&mut self, max: Duration) -> Duration {
+        Duration::from_millis(self.below(max.as_millis() as u64 + 1))
+    }
+}
+
+/// Record number `n` pushed through the topology
+fn record(n: u64) -> String {
+    format!("soak {n}\n")
+}
+
+/// Consumer of an output, away while killed
+struct Output {
+    /// Path of the output FIFO
+    path: String,
+    /// Reading end, `None` while the consumer is killed
+    consumer: Option<Consumer>,
+    /// When a killed consumer comes back
+    back_at: Instant,
+    /// Bytes of a record not received whole yet
+    partial: Vec<u8>,
+    /// Number of the last record received
+    last: u64,
+    /// Records received
+    received: u64,
+}
+
+impl Output {
+    /// Take the records the consumer received so far, the violations of
+    /// their order in `violations`
+    fn receive(&mut self, violations: &mut Vec<String>) -> io::Result<()> {
+        let Some(consumer) = self.consumer.as_mut() else {
+            return Ok(());
+        };
+        self.partial.extend(consumer.collect(1 << 16, TICK)?);
+        let end = self.partial.iter().rposition(|b| *b == b'\n');
+        let Some(end) = end else {
+            return Ok(());
+        };
+        let lines: Vec<u8> = self.partial.drain(..=end).collect();
+        for line in String::from_utf8_lossy(&lines).lines() {
+            match line
+                .strip_prefix("soak ")
+                .and_then(|n| n.parse::<u64>().ok())
+            {
+                Some(n) if n > self.last => {
+                    self.last = n;
+                    self.received += 1;
+                }
+                Some(n) => violations.push(format!(
+                    "{} received record {n} after record {}",
+                    self.path, self.last
+                )),
+                None => violations.push(format!("{} received '{line}'", self.path)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Kill the consumer for `pause`, deleting its FIFO when `delete`,
+    /// unless it is away already
+    fn kill(&mut self, pause: Duration, delete: bool) -> io::Result<()> {
+        // Records in the pipe are lost with the consumer
+        if self.consumer.take().is_none() {
+            return Ok(());
+        }
+        self.partial.clear();
+        self.back_at = Instant::now() + pause;
+        if delete {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Bring a killed consumer back once its pause is over, or right away
+    /// when `now`
+    fn revive(&mut self, now: bool) -> io::Result<()> {
+        if self.consumer.is_none() && (now || Instant::now() >= self.back_at) {
+            self.consumer = Some(Consumer::open(&self.path)?);
+        }
+        Ok(())
+    }
+}
+
+/// Violations of the accounting of the records of `input`, `produced` of
+/// them pushed through and `outputs` consumed
+fn check_accounting(input: &SplitIn, produced: u64, outputs: &[Output]) -> Vec<String> {
+    let mut violations = Vec::new();
+    let read = input.status.records();
+    if read != produced {
+        violations.push(format!("input read {read} of {produced} records"));
+    }
+    for (output, consumed) in input.outputs.iter().zip(outputs) {
+        let status = &output.status;
+        let written = status.records();
+        let dropped = status.drops() + status.expirations() + status.filters();
+        if written + dropped + status.queue_len() != read {
+            violations.push(format!(
+                "{} wrote {written} and dropped {dropped} of {read} records, {} queued",
+                output.name(),
+                status.queue_len()
+            ));
+        }
+        if consumed.received > written {
+            violations.push(format!(
+                "{} received {} records, {written} written",
+                output.name(),
+                consumed.received
+            ));
+        }
+        log!(
+            "Soak -> {} received {}, written {written}, dropped {dropped} of {read} records",
+            output.name(),
+            consumed.received
+        );
+    }
+    violations
+}
+
+/// Stop the splitter of `harness`, false when it did not stop in time
+fn stop(mut harness: Harness) -> bool {
+    let (done, stopped) = mpsc::channel();
+    thread::spawn(move || {
+        harness.stop();
+        let _ = done.send(());
+        drop(harness);
+    });
+    stopped.recv_timeout(DEADLINE).is_ok()
+}
+
+/// Run a scratch topology under chaos as set by `options`, returning the
+/// violations of the invariants found
+pub(crate) fn run(options: &SoakOptions) -> io::Result<Vec<String>> {
+    if options.outputs == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the soak topology needs an output",
+        ));
+    }
+    let seed = options.seed.unwrap_or_else(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH);
+        now.unwrap_or_default().as_nanos() as u64
+    });
+    log!("Soak -> seed {seed}, {}s", options.duration.as_secs());
+    let mut chaos = Chaos::new(seed);
+
+    let mut config = "[DEFAULT]\nroot={root}\n[PIPES]\nin=1,rt\n[in]\n".to_owned();
+    for o in 1..=options.outputs {
+        config.push_str(&format!("out{o}=1,wt,queue={QUEUE}\n"));
+    }
+    let harness = Harness::spawn(&config)?;
+    let input: Arc<SplitIn> = Arc::clone(&harness.inputs()[0]);
+    let mut outputs = Vec::with_capacity(options.outputs);
+    for o in 1..=options.outputs {
+        let path = harness.pipe(&format!("out{o}")).unwrap_or_default();
+        outputs.push(Output {
+            consumer: Some(Consumer::open(path)?),
+            path: path.to_owned(),
+            back_at: Instant::now(),
+            partial: Vec::new(),
+            last: 0,
+            received: 0,
+        });
+    }
+    let mut producer = Producer::open(harness.pipe("in").unwrap_or_default(), DEADLINE)?;
+
+    let mut violations = Vec::new();
+    let mut produced = 0;
+    let mut paused_until = Instant::now();
+    let mut next_event = Instant::now() + chaos.duration(options.interval * 2);
+    let mut next_sample = Instant::now();
+    let mut max_rss = 0;
+    let start = Instant::now();
+    while start.elapsed() < options.duration && violations.is_empty() {
+        let now = Instant::now();
+        if now >= next_event {
+            next_event = now + chaos.duration(options.interval * 2);
+            let pause = chaos.duration(options.max_pause);
+            match chaos.below(3) {
+                0 => paused_until = now + pause,
+                event => {
+                    let output = chaos.below(outputs.len() as u64) as usize;
+                    outputs[output].kill(pause, event == 2)?;
+                }
+            }
+        }
+        if now >= paused_until {
+            let batch: String = (produced + 1..=produced + BATCH).map(record).collect();
+            if producer.write(batch.as_bytes(), DEADLINE).is_err() {
+                violations.push(format!("input took no record for {DEADLINE:?}"));
+                break;
+            }
+            produced += BATCH;
+        }
+        for output in outputs.iter_mut() {
+            output.revive(false)?;
+            output.receive(&mut violations)?;
+        }
+        if now >= next_sample {
+            next_sample = now + SAMPLE;
+            let usage = process::usage(status::queued_bytes(harness.inputs()))?;
+            max_rss = max_rss.max(usage.rss);
+            if usage.rss > options.max_rss {
+                violations.push(format!(
+                    "resident memory {} kB above {} kB",
+                    usage.rss / 1024,
+                    options.max_rss / 1024
+                ));
+            }
+        }
+        thread::sleep(TICK);
+    }
+    log!(
+        "Soak -> {produced} records produced, resident memory up to {} kB",
+        max_rss / 1024
+    );
+
+    // Without chaos, the last record reaches every output
+    for output in outputs.iter_mut() {
+        output.revive(true)?;
+    }
+    let last = produced + BATCH;
+    let batch: String = (produced + 1..=last).map(record).collect();
+    if producer.write(batch.as_bytes(), DEADLINE).is_err() {
+        violations.push(format!("input took no record for {DEADLINE:?}"));
+    }
+    produced = last;
+    let since = Instant::now();
+    while since.elapsed() < DEADLINE && outputs.iter().any(|o| o.last < last) {
+        for output in outputs.iter_mut() {
+            output.receive(&mut violations)?;
+        }
+    }
+    for output in outputs.iter().filter(|o| o.last < last) {
+        violations.push(format!(
+            "{} got no record after {} within {DEADLINE:?}",
+            output.path, output.last
+        ));
+    }
+    if violations.is_empty() {
+        violations.extend(check_accounting(&input, produced, &outputs));
+    }
+    if !stop(harness) {
+        violations.push(format!("splitter did not stop within {DEADLINE:?}"));
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn soak_under_chaos() {
+        let options = SoakOptions {
+            duration: Duration::from_secs(3),
+            max_pause: Duration::from_millis(300),
+            interval: Duration::from_millis(100),
+            seed: Some(42),
+            ..SoakOptions::default()
+        };
+        assert_eq!(run(&options).expect("soak"), Vec::<String>::new());
+    }
+    #[test]
+    fn needs_an_output() {
+        let options = SoakOptions {
+            outputs: 0,
+            ..SoakOptions::default()
+        };
+        let e = run(&options).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,73 @@
+//! Custom input types. An embedder implements [`Source`] and registers a
+//! factory for a scheme with [`register_source`]; an input configured with
+//! `source=<scheme>://<target>` then has the records of the source built for
+//! `<target>` fanned out to its outputs instead of those of a FIFO. The `mq`
+//! scheme, a POSIX message queue, is built in unless an embedder registers
+//! its own.
+use std::io;
+use std::os::fd::RawFd;
+use std::sync::Arc;
+
+use crate::mq::MqSource;
+use crate::registry::{Registry, Target};
+use crate::secrets;
+
+/// Producer of the records of an input
+pub trait Source: Send {
+    /// Connect to the producer, called again after the stream ended or failed
+    fn open(&mut self) -> io::Result<()>;
+    /// Next record, `None` once the stream ended. `WouldBlock` means no
+    /// record is available yet, reading is tried again once
+    /// [`Source::ready_fd`] is readable or after the poll interval; other
+    /// errors close and reopen the source.
+    fn read(&mut self) -> io::Result<Option<Vec<u8>>>;
+    /// Disconnect from the producer
+    fn close(&mut self) {}
+    /// Descriptor polled for readability before reading is tried again
+    fn ready_fd(&self) -> Option<RawFd> {
+        None
+    }
+}
+
+/// Builds the source for a target, the part of `source=` after `<scheme>://`
+pub type SourceFactory = dyn Fn(&str) -> io::Result<Box<dyn Source>> + Send + Sync;
+
+/// Source factories by scheme
+static SOURCES: Registry<SourceFactory> = Registry::new();
+
+/// Build the sources of inputs configured with `source=<scheme>://...` with
+/// `factory`, replacing any factory registered before for `scheme`. Sources
+/// must be registered before the configuration is loaded.
+pub fn register_source<F>(scheme: &str, factory: F)
+where
+    F: Fn(&str) -> io::Result<Box<dyn Source>> + Send + Sync + 'static,
+{
+    SOURCES.register(scheme, Arc::new(factory));
+}
+
+/// Factory of the source built in for `scheme`
+fn builtin(scheme: &str) -> Option<Arc<SourceFactory>> {
+    match scheme {
+        "mq" => Some(Arc::new(|target: &str| {
+            Ok(Box::new(MqSource::new(target)?) as Box<dyn Source>)
+        })),
+        _ => None,
+    }
+}
+
+/// Source an input is configured to read from
+pub(crate) type SourceTarget = Target<SourceFactory>;
+
+impl SourceTarget {
+    /// Source configured as `uri`, `None` when its scheme is not registered
+    pub fn resolve_source(uri: &str) -> Option<SourceTarget> {
+        SOURCES.resolve(uri, builtin)
+    }
+
+    /// Build and open the source
+    pub fn open_source(&self) -> io::Result<Box<dyn Source>> {
+        let mut source = (self.factory)(&secrets::resolve(self.target())?)?;
+        source.open()?;
+        Ok(source)
+    }
+}
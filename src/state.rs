@@ -0,0 +1,196 @@
+//! Resume state of the splitter, `[DEFAULT] state_file=<path>`: the next
+//! sequence number of every input and the sequence number every output has
+//! settled (written or dropped), so a restarted splitter numbers records
+//! after the last one and replays write-ahead logs from where each output
+//! stopped. Lines hold tab separated fields, `next <input> <sequence>`,
+//! `settled <input> <output> <sequence>`, and `clean` last when saved at
+//! exit.
+//!
+//! The file is saved every checkpoint interval and once more at exit.
+//! Saves replace the file atomically, so a crash leaves the previous one,
+//! only the records read since then being numbered again, unless the input
+//! has a write-ahead log.
+use std::collections::HashMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Write};
+use std::os::unix::fs::{fchown, MetadataExt};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::SplitIn;
+
+/// Replace the file at `path` with `contents`, durably: a crash leaves
+/// either the previous file or the new one
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    replace(path, contents, None)
+}
+
+/// Replace the existing file at `path` with `contents` as `write_atomic`
+/// does, the new file keeping the mode and owner of the one it replaces
+pub(crate) fn rewrite_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    replace(path, contents, Some(&fs::metadata(path)?))
+}
+
+/// Write `contents` to a temporary file, given the mode and owner of
+/// `like` if any, and rename it over `path`
+fn replace(path: &Path, contents: &[u8], like: Option<&Metadata>) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    if let Some(like) = like {
+        file.set_permissions(like.permissions())?;
+        fchown(&file, Some(like.uid()), Some(like.gid()))?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    // The rename is durable once the directory is synced
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+/// State saved by a previous run
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ResumeState {
+    /// Next sequence number, by input
+    next: HashMap<String, u64>,
+    /// Settled sequence number, by input and output
+    settled: HashMap<(String, String), u64>,
+    /// Whether the state was saved at exit
+    clean: bool,
+}
+
+impl ResumeState {
+    /// State saved at `path`, empty when there is none
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ResumeState> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(ResumeState {
+                    clean: true,
+                    ..ResumeState::default()
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        let mut state = ResumeState::default();
+        for line in contents.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                ["next", input, seq] => {
+                    if let Ok(seq) = seq.parse() {
+                        state.next.insert(input.to_owned(), seq);
+                    }
+                }
+                ["settled", input, output, seq] => {
+                    if let Ok(seq) = seq.parse() {
+                        let key = (input.to_owned(), output.to_owned());
+                        state.settled.insert(key, seq);
+                    }
+                }
+                ["clean"] => state.clean = true,
+                _ => {}
+            }
+        }
+        Ok(state)
+    }
+
+    /// Whether the state was saved at exit rather than before a crash
+    pub fn is_clean(&self) -> bool {
+        self.clean
+    }
+
+    /// Resume the numbering of `entries` and the positions of their outputs
+    pub fn apply(&self, entries: &[Arc<SplitIn>]) {
+        for input in entries {
+            if let Some(next) = self.next.get(&input.pipe) {
+                input.status.resume(*next);
+            }
+            for output in input.outputs.iter() {
+                let key = (input.pipe.clone(), output.pipe.clone());
+                if let Some(settled) = self.settled.get(&key) {
+                    output.status.restore(*settled);
+                }
+            }
+        }
+    }
+}
+
+/// Save the state of `entries` at `path`, `clean` once the workers stopped
+pub(crate) fn save<P: AsRef<Path>>(
+    path: P,
+    entries: &[Arc<SplitIn>],
+    clean: bool,
+) -> io::Result<()> {
+    let mut contents = String::new();
+    for input in entries {
+        contents.push_str(&format!(
+            "next\t{}\t{}\n",
+            input.pipe,
+            input.status.next_seq()
+        ));
+        for output in input.outputs.iter() {
+            contents.push_str(&format!(
+                "settled\t{}\t{}\t{}\n",
+                input.pipe,
+                output.pipe,
+                output.status.settled()
+            ));
+        }
+    }
+    if clean {
+        contents.push_str("clean\n");
+    }
+    write_atomic(path.as_ref(), contents.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Parser;
+    use std::env::temp_dir;
+
+    #[test]
+    fn save_and_resume() {
+        let file_name = temp_dir().join("p_split_state_config");
+        fs::write(
+            &file_name,
+            "
+[DEFAULT]
+root=/tmp
+[PIPES]
+cvAnalogsMapperExt=1,rt
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt
+cvAnalogsMapperExtGpsApp=1,wt
+",
+        )
+        .unwrap();
+        let state_file = temp_dir().join(format!("p_split_state_{}", std::process::id()));
+        let _ = fs::remove_file(&state_file);
+        assert!(ResumeState::load(&state_file).unwrap().is_clean());
+
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let input = &config.inputs[0];
+        input.status.numbered(43);
+        input.outputs[0].status.restore(42);
+        input.outputs[1].status.restore(40);
+        save(&state_file, &config.inputs, false).unwrap();
+        let state = ResumeState::load(&state_file).unwrap();
+        assert!(!state.is_clean());
+        save(&state_file, &config.inputs, true).unwrap();
+
+        let resumed = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let state = ResumeState::load(&state_file).unwrap();
+        assert!(state.is_clean());
+        state.apply(&resumed.inputs);
+        let input = &resumed.inputs[0];
+        assert_eq!(input.status.next_seq(), 43);
+        assert_eq!(input.outputs[0].status.settled(), 42);
+        assert_eq!(input.outputs[1].status.settled(), 40);
+        let _ = fs::remove_file(state_file);
+    }
+}
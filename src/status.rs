@@ -0,0 +1,602 @@
+//! Runtime state of the splitting pipes, shared between the workers and the
+//! supervising loop in [`split_pipes`](crate::split_pipes).
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{process, Settings, SplitIn};
+
+/// Time an output may stay blocked before it is reported as stalled
+pub(crate) const STALL_AFTER: Duration = Duration::from_secs(5);
+/// Minimum interval between two stall warnings of the same output
+pub(crate) const STALL_WARN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Counters and blocking state of an output pipe
+#[derive(Default)]
+pub(crate) struct OutputStatus {
+    /// Records written to the pipe
+    records: AtomicU64,
+    /// Bytes written to the pipe
+    bytes: AtomicU64,
+    /// Records dropped because the output queue was full
+    drops: AtomicU64,
+    /// Since when the output queue has been full
+    full_since: Mutex<Option<Instant>>,
+    /// Since when the output FIFO has been unwritable
+    blocked_since: Mutex<Option<Instant>>,
+    /// When the last stall warning was emitted
+    last_warning: Mutex<Option<Instant>>,
+    /// Output was disabled by the eviction policy
+    evicted: AtomicBool,
+    /// Times an evicted output got a consumer back
+    reattaches: AtomicU64,
+    /// Unacknowledged records written again
+    retransmits: AtomicU64,
+    /// Records dropped because they waited longer than the output's TTL
+    expired: AtomicU64,
+    /// Records queued or being written
+    queue_len: AtomicU64,
+    /// Records larger than `PIPE_BUF`
+    oversized: AtomicU64,
+    /// Records dropped by a filter of the output
+    filtered: AtomicU64,
+    /// Queue is above its high water mark
+    above_high_water: AtomicBool,
+    /// Times the queue crossed its high water mark
+    high_water_events: AtomicU64,
+    /// Sequence number of the last record written or discarded by the writer
+    last_settled: AtomicU64,
+    /// Sequence number of the last record never queued
+    last_skipped: AtomicU64,
+    /// Errors opening or writing to the output
+    errors: AtomicU64,
+}
+
+impl OutputStatus {
+    /// Account for `records` written to the pipe in `bytes`
+    pub fn written(&self, records: usize, bytes: usize) {
+        self.records.fetch_add(records as u64, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        *self.blocked_since.lock().unwrap() = None;
+    }
+    /// Reserve a queue slot before queuing a record
+    pub fn reserve(&self) {
+        self.queue_len.fetch_add(1, Ordering::SeqCst);
+    }
+    /// Release a slot reserved for a record that could not be queued
+    pub fn release(&self) {
+        self.queue_len.fetch_sub(1, Ordering::SeqCst);
+    }
+    /// Account for the record `seq` dropped on a full queue, releasing its slot
+    pub fn dropped(&self, seq: u64) {
+        self.release();
+        self.skipped(seq);
+        self.drops.fetch_add(1, Ordering::Relaxed);
+        self.full_since
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+    }
+    /// The record `seq` is not delivered to this output
+    pub fn skipped(&self, seq: u64) {
+        self.last_skipped.fetch_max(seq, Ordering::SeqCst);
+    }
+    /// Account for the buffered record `seq` dropped to make room in a full
+    /// overflow buffer
+    pub fn overflowed(&self, seq: u64) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+        self.settle(seq);
+        self.full_since
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+    }
+    /// A record was queued, so the queue is no longer full
+    pub fn queued(&self) {
+        *self.full_since.lock().unwrap() = None;
+    }
+    /// The writer is done with the queued record `seq`, written or discarded
+    pub fn settle(&self, seq: u64) {
+        self.last_settled.fetch_max(seq, Ordering::SeqCst);
+        self.queue_len.fetch_sub(1, Ordering::SeqCst);
+    }
+    /// Account for the queued record `seq` dropped for exceeding its TTL
+    pub fn expired(&self, seq: u64) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+        self.settle(seq);
+    }
+    /// Records dropped for exceeding their TTL
+    pub fn expirations(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+    /// Continue sequence accounting from `seq`, settled in a previous run
+    pub fn restore(&self, seq: u64) {
+        self.last_settled.fetch_max(seq, Ordering::SeqCst);
+        self.last_skipped.fetch_max(seq, Ordering::SeqCst);
+    }
+    /// Sequence number up to which every record is written or dropped
+    pub fn settled(&self) -> u64 {
+        // Records are skipped in order after the earlier ones were queued, so
+        // an empty queue seen after a skip means those were settled too
+        let skipped = self.last_skipped.load(Ordering::SeqCst);
+        let settled = self.last_settled.load(Ordering::SeqCst);
+        match self.queue_len.load(Ordering::SeqCst) {
+            0 => settled.max(skipped),
+            _ => settled,
+        }
+    }
+    /// Account for a record larger than `PIPE_BUF`, returning whether it is
+    /// the first one
+    pub fn oversized(&self) -> bool {
+        self.oversized.fetch_add(1, Ordering::Relaxed) == 0
+    }
+    /// Records larger than `PIPE_BUF`
+    pub fn oversizes(&self) -> u64 {
+        self.oversized.load(Ordering::Relaxed)
+    }
+    /// Account for the record `seq` dropped by a filter before being queued
+    pub fn filtered(&self, seq: u64) {
+        self.filtered.fetch_add(1, Ordering::Relaxed);
+        self.skipped(seq);
+    }
+    /// Records dropped by a filter
+    pub fn filters(&self) -> u64 {
+        self.filtered.load(Ordering::Relaxed)
+    }
+    /// Records queued or being written
+    pub fn queue_len(&self) -> u64 {
+        self.queue_len.load(Ordering::Relaxed)
+    }
+    /// The FIFO could not be opened or written to
+    pub fn blocked(&self) {
+        self.blocked_since
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+    }
+    /// Forget about blocking, the input has no data to deliver
+    pub fn idle(&self) {
+        *self.full_since.lock().unwrap() = None;
+        *self.blocked_since.lock().unwrap() = None;
+    }
+    /// How long the output has been unable to accept records
+    pub fn stalled_for(&self) -> Option<Duration> {
+        let full = self.full_since.lock().unwrap().map(|t| t.elapsed());
+        let blocked = self.blocked_since.lock().unwrap().map(|t| t.elapsed());
+        full.max(blocked)
+    }
+    /// The output has been unable to accept records for at least [`STALL_AFTER`]
+    pub fn is_stalled(&self) -> bool {
+        self.stalled_for().is_some_and(|d| d >= STALL_AFTER)
+    }
+    /// Whether a stall warning is due, at most once every [`STALL_WARN_INTERVAL`]
+    pub fn should_warn(&self) -> bool {
+        if !self.is_stalled() {
+            return false;
+        }
+        let mut last = self.last_warning.lock().unwrap();
+        match *last {
+            Some(t) if t.elapsed() < STALL_WARN_INTERVAL => false,
+            _ => {
+                *last = Some(Instant::now());
+                true
+            }
+        }
+    }
+    /// Disable delivery to the output
+    pub fn evict(&self) {
+        self.evicted.store(true, Ordering::Relaxed);
+        self.idle();
+    }
+    /// Delivery to the output is disabled
+    pub fn is_evicted(&self) -> bool {
+        self.evicted.load(Ordering::Relaxed)
+    }
+    /// Re-enable delivery to an evicted output
+    pub fn reattach(&self) {
+        self.evicted.store(false, Ordering::Relaxed);
+        self.reattaches.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Times an evicted output got a consumer back
+    pub fn reattaches(&self) -> u64 {
+        self.reattaches.load(Ordering::Relaxed)
+    }
+    /// Account for an unacknowledged record written again
+    pub fn retransmitted(&self) {
+        self.retransmits.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Unacknowledged records written again
+    pub fn retransmits(&self) -> u64 {
+        self.retransmits.load(Ordering::Relaxed)
+    }
+    /// Records written to the pipe
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+    /// Bytes written to the pipe
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+    /// Records dropped on a full queue
+    pub fn drops(&self) -> u64 {
+        self.drops.load(Ordering::Relaxed)
+    }
+    /// Account for an error opening or writing to the output
+    pub fn failed(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Errors opening or writing to the output
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for OutputStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[records: {}, bytes: {}, queued: {}, drops: {}, expired: {}, oversized: {}, filtered: {}, high_water: {}, stalled: {}, evicted: {}, reattaches: {}, retransmits: {}, errors: {}]",
+            self.records(),
+            self.bytes(),
+            self.queue_len(),
+            self.drops(),
+            self.expirations(),
+            self.oversizes(),
+            self.filters(),
+            self.high_water_events.load(Ordering::Relaxed),
+            self.is_stalled(),
+            self.is_evicted(),
+            self.reattaches(),
+            self.retransmits(),
+            self.errors()
+        )
+    }
+}
+
+/// Counters of an input pipe
+#[derive(Default)]
+pub(crate) struct InputStatus {
+    /// Records read from the pipe
+    records: AtomicU64,
+    /// Bytes read from the pipe
+    bytes: AtomicU64,
+    /// Errors opening or reading the input
+    errors: AtomicU64,
+    /// Sequence number of the next record, 0 before the first one
+    next_seq: AtomicU64,
+}
+
+impl InputStatus {
+    /// Account for a record read from the pipe
+    pub fn read(&self, bytes: usize) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+    /// Records read from the pipe
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+    /// Bytes read from the pipe
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+    /// Account for an error opening or reading the input
+    pub fn failed(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Errors opening or reading the input
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+    /// Records are numbered from `next` on, as saved by a previous run
+    pub fn resume(&self, next: u64) {
+        self.next_seq.fetch_max(next, Ordering::SeqCst);
+    }
+    /// The reader numbered the records before `next`
+    pub fn numbered(&self, next: u64) {
+        self.next_seq.store(next, Ordering::SeqCst);
+    }
+    /// Sequence number of the next record
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq.load(Ordering::SeqCst).max(1)
+    }
+}
+
+impl fmt::Display for InputStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[records: {}, bytes: {}, errors: {}]",
+            self.records(),
+            self.bytes(),
+            self.errors()
+        )
+    }
+}
+
+/// Emit a warning for every output that stopped accepting records
+pub(crate) fn warn_stalled(entries: &[Arc<SplitIn>]) {
+    for input in entries {
+        for output in input.outputs.iter() {
+            if output.status.should_warn() {
+                let stalled_for = output.status.stalled_for().unwrap_or_default();
+                log!(
+                    "Warning: output stalled for {}s <> {}",
+                    stalled_for.as_secs(),
+                    output
+                );
+            }
+        }
+    }
+}
+
+/// Report outputs whose queue crossed their high water mark, and again once
+/// they drained below half of it
+pub(crate) fn check_high_water(entries: &[Arc<SplitIn>]) {
+    for input in entries {
+        for output in input.outputs.iter() {
+            let Some(percent) = output.high_water else {
+                continue;
+            };
+            let status = &output.status;
+            let len = status.queue_len() as usize;
+            let capacity = output.channel.capacity();
+            let level = len * 100 / capacity;
+            let above = status.above_high_water.load(Ordering::Relaxed);
+
+            if !above && level >= percent {
+                status.above_high_water.store(true, Ordering::Relaxed);
+                status.high_water_events.fetch_add(1, Ordering::Relaxed);
+                log!(
+                    "Warning: queue above high water mark ({}/{}) <> {}",
+                    len,
+                    capacity,
+                    output
+                );
+            } else if above && level * 2 < percent {
+                status.above_high_water.store(false, Ordering::Relaxed);
+                log!(
+                    "Queue drained below high water mark ({}/{}) <> {}",
+                    len,
+                    capacity,
+                    output
+                );
+            }
+        }
+    }
+}
+
+/// Disable every output stalled for longer than its `evict_after`
+pub(crate) fn evict_stalled(entries: &[Arc<SplitIn>]) {
+    for input in entries {
+        for output in input.outputs.iter() {
+            let evict_after = match output.evict_after {
+                Some(d) if !output.status.is_evicted() => d,
+                _ => continue,
+            };
+            if let Some(stalled_for) = output.status.stalled_for() {
+                if stalled_for >= evict_after {
+                    output.status.evict();
+                    // Let a writer waiting for records notice the eviction
+                    output.channel.wake();
+                    log!(
+                        "Evicting output stalled for {}s <> {}",
+                        stalled_for.as_secs(),
+                        output
+                    );
+                    if let Some(group) = &output.group {
+                        log!("Group -> {} left by evicted output <> {}", group, output);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `path: ` field of a labelled pipe, empty when the name is the path
+fn path_field(name: &str, path: &str) -> String {
+    match name == path {
+        true => String::new(),
+        false => format!("path: {path}, "),
+    }
+}
+
+/// Bytes of the records waiting in the output queues of `entries`
+pub(crate) fn queued_bytes(entries: &[Arc<SplitIn>]) -> u64 {
+    entries
+        .iter()
+        .flat_map(|input| input.outputs.iter())
+        .map(|output| output.channel.queued_bytes() as u64)
+        .sum()
+}
+
+/// Render the status of every pipe, one line per pipe, after the instance
+/// and the resources of the process
+pub(crate) fn report(settings: &Settings, entries: &[Arc<SplitIn>]) -> String {
+    let mut report = String::new();
+    if let Some(instance) = &settings.instance {
+        report.push_str(&format!("INSTANCE(name: {instance})\n"));
+    }
+    if let Some(throttle) = &settings.throttle {
+        report.push_str(&format!("THROTTLE(rate: {} bytes/s)\n", throttle.rate()));
+    }
+    match process::usage(queued_bytes(entries)) {
+        Ok(usage) => report.push_str(&format!(
+            "PROCESS(pid: {}, uptime: {}s, fds: {}, threads: {}, rss: {} kB, queued: {} bytes)\n",
+            std::process::id(),
+            usage.uptime.as_secs(),
+            usage.fds,
+            usage.threads,
+            usage.rss / 1024,
+            usage.queued_bytes
+        )),
+        Err(e) => report.push_str(&format!("PROCESS(error: {e})\n")),
+    }
+    for input in entries {
+        report.push_str(&format!(
+            "IN(pipe: {}, {}status: {})\n",
+            input.name(),
+            path_field(input.name(), &input.pipe),
+            input.status
+        ));
+        for output in input.outputs.iter() {
+            report.push_str(&format!(
+                "  OUT(pipe: {}, {}status: {})\n",
+                output.name(),
+                path_field(output.name(), &output.pipe),
+                output.status
+            ));
+        }
+    }
+    report
+}
+
+/// Replace the status file at `path` with the current report
+pub(crate) fn write_report<P: AsRef<Path>>(
+    path: P,
+    settings: &Settings,
+    entries: &[Arc<SplitIn>],
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, report(settings, entries))?;
+    fs::rename(tmp, path)
+}
+
+/// One line per pipe with the records it forwarded, dropped and the errors
+/// it hit, logged when the splitter exits
+pub(crate) fn summary(entries: &[Arc<SplitIn>]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for input in entries {
+        lines.push(format!(
+            "Summary <> IN(pipe: {}, records: {}, errors: {})",
+            input.name(),
+            input.status.records(),
+            input.status.errors()
+        ));
+        for output in input.outputs.iter() {
+            let status = &output.status;
+            lines.push(format!(
+                "Summary <> OUT(pipe: {}, records: {}, drops: {}, errors: {})",
+                output.name(),
+                status.records(),
+                status.drops() + status.expirations(),
+                status.errors()
+            ));
+        }
+    }
+    lines
+}
+
+/// Counters of a pipe when the last throughput line was written
+#[derive(Clone, Copy, Default)]
+struct Sample {
+    records: u64,
+    bytes: u64,
+    drops: u64,
+}
+
+/// Rates of every running pipe since the previous throughput line, logged
+/// every `[DEFAULT] throughput_interval`
+pub(crate) struct Throughput {
+    /// When the samples were taken
+    since: Instant,
+    /// Counters of the inputs, then of their outputs, in order
+    samples: Vec<Sample>,
+}
+
+impl Throughput {
+    /// Start measuring the throughput of `entries`
+    pub fn new(entries: &[Arc<SplitIn>]) -> Throughput {
+        let mut throughput = Throughput {
+            since: Instant::now(),
+            samples: Vec::new(),
+        };
+        throughput.samples = throughput.sample(entries);
+        throughput
+    }
+
+    /// Current counters of `entries`
+    fn sample(&self, entries: &[Arc<SplitIn>]) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for input in entries {
+            samples.push(Sample {
+                records: input.status.records(),
+                bytes: input.status.bytes(),
+                drops: 0,
+            });
+            for output in input.outputs.iter() {
+                let status = &output.status;
+                samples.push(Sample {
+                    records: status.records(),
+                    bytes: status.bytes(),
+                    drops: status.drops() + status.expirations(),
+                });
+            }
+        }
+        samples
+    }
+
+    /// One line per running pipe with its rates since the previous call
+    pub fn lines(&mut self, entries: &[Arc<SplitIn>]) -> Vec<String> {
+        let secs = self.since.elapsed().as_secs_f64().max(f64::EPSILON);
+        let samples = self.sample(entries);
+        let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / secs;
+        let mut pairs = samples.iter().copied().zip(self.samples.iter().copied());
+        let mut lines = Vec::new();
+        for input in entries {
+            let (now, before) = pairs.next().unwrap_or_default();
+            let running = input.runs();
+            if running {
+                lines.push(format!(
+                    "Throughput <> IN(pipe: {}, records/s: {:.1}, bytes/s: {:.1})",
+                    input.name(),
+                    rate(now.records, before.records),
+                    rate(now.bytes, before.bytes)
+                ));
+            }
+            for output in input.outputs.iter() {
+                let (now, before) = pairs.next().unwrap_or_default();
+                if !running || !output.configuration.enabled {
+                    continue;
+                }
+                lines.push(format!(
+                    "Throughput <> OUT(pipe: {}, records/s: {:.1}, bytes/s: {:.1}, drops/s: {:.1}, queue: {}/{})",
+                    output.name(),
+                    rate(now.records, before.records),
+                    rate(now.bytes, before.bytes),
+                    rate(now.drops, before.drops),
+                    output.status.queue_len(),
+                    output.channel.capacity()
+                ));
+            }
+        }
+        self.samples = samples;
+        self.since = Instant::now();
+        lines
+    }
+}
+
+/// Pipes that hit errors, and pipes that ran, inputs and outputs alike
+pub(crate) fn failures(entries: &[Arc<SplitIn>]) -> (usize, usize) {
+    let mut failed = 0;
+    let mut running = 0;
+    for input in entries {
+        if !input.configuration.enabled {
+            continue;
+        }
+        running += 1;
+        failed += usize::from(input.status.errors() > 0);
+        for output in input.outputs.iter() {
+            if output.configuration.enabled {
+                running += 1;
+                failed += usize::from(output.status.errors() > 0);
+            }
+        }
+    }
+    (failed, running)
+}
@@ -0,0 +1,109 @@
+//! Debug tap, `--tap host:port`: every record read by an input is mirrored
+//! in a UDP datagram to a developer workstation, `nc -ul <port>` showing the
+//! live traffic. A datagram is a header line, the time the record was read
+//! as `<seconds>.<microseconds>` since the epoch and the name of its input,
+//! followed by the record, cut to fit a datagram.
+//!
+//! The tap is not part of the configuration, it is switched on and off
+//! while the splitter runs and nothing of it persists. Datagrams are sent
+//! without blocking the reader; those the socket or the network cannot take
+//! are lost.
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Largest payload of a UDP datagram over IPv4
+const MAX_DATAGRAM: usize = 65507;
+
+/// Whether a tap is set, checked before taking the lock
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Socket connected to the tap destination
+static TAP: RwLock<Option<UdpSocket>> = RwLock::new(None);
+
+/// Mirror records to `addr`, `host:port`, or stop mirroring when `None`
+pub(crate) fn set(addr: Option<&str>) -> io::Result<()> {
+    let socket = match addr {
+        Some(addr) => Some(connect(addr)?),
+        None => None,
+    };
+    let mut tap = TAP.write().unwrap();
+    ENABLED.store(socket.is_some(), Ordering::Relaxed);
+    *tap = socket;
+    Ok(())
+}
+
+/// Non-blocking socket sending to `addr`
+fn connect(addr: &str) -> io::Result<UdpSocket> {
+    let target = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {addr}")))?;
+    let local = match target.is_ipv4() {
+        true => "0.0.0.0:0",
+        false => "[::]:0",
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(target)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Datagram mirroring `data`, read from `pipe` at `time`
+fn datagram(pipe: &str, time: SystemTime, data: &[u8]) -> Vec<u8> {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut datagram =
+        format!("{}.{:06} {pipe}\n", since.as_secs(), since.subsec_micros()).into_bytes();
+    let room = MAX_DATAGRAM.saturating_sub(datagram.len());
+    datagram.extend_from_slice(&data[..data.len().min(room)]);
+    datagram
+}
+
+/// Mirror `data`, just read from `pipe`, when a tap is set
+pub(crate) fn mirror(pipe: &str, data: &[u8]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(socket) = TAP.read().unwrap().as_ref() {
+        let _ = socket.send(&datagram(pipe, SystemTime::now(), data));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn mirror_records() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_042);
+        assert_eq!(
+            datagram("fuel", time, b"fuel=12\n"),
+            b"1700000000.000042 fuel\nfuel=12\n"
+        );
+        assert_eq!(datagram("fuel", time, &[0; 70000]).len(), MAX_DATAGRAM);
+
+        let workstation = UdpSocket::bind("127.0.0.1:0").unwrap();
+        workstation
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        set(Some(&workstation.local_addr().unwrap().to_string())).unwrap();
+        mirror("fuel", b"fuel=12\n");
+        set(None).unwrap();
+        mirror("fuel", b"fuel=13\n");
+
+        // Readers of other tests may be mirrored too while the tap is set
+        let mut received = Vec::new();
+        let mut buffer = [0; MAX_DATAGRAM];
+        while let Ok(len) = workstation.recv(&mut buffer) {
+            received.push(buffer[..len].to_vec());
+            workstation
+                .set_read_timeout(Some(Duration::from_millis(50)))
+                .unwrap();
+        }
+        assert!(received.iter().any(|d| d.ends_with(b" fuel\nfuel=12\n")));
+        assert!(!received.iter().any(|d| d.ends_with(b"fuel=13\n")));
+    }
+}
@@ -0,0 +1,257 @@
+//! Helpers for integration tests against the splitter, public with the
+//! `testing` feature: a splitter running a topology under a temporary root,
+//! producers writing records to its inputs and consumers collecting what
+//! reaches its outputs, every wait bounded by a timeout.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{create_splitting_threads, FifoOptions, Parser, SplitIn, Writer, SIG_EXIT, SIG_RUN};
+
+/// Delay between attempts while waiting
+const POLL: Duration = Duration::from_millis(10);
+
+/// Scratch roots created by this process
+static ROOTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Create a FIFO at `path` unless one exists
+pub fn create_fifo<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    Writer::create_fifo(path, &FifoOptions::default())
+}
+
+/// Splitter running a topology under a temporary root, stopped and cleaned
+/// up when dropped
+pub struct Harness {
+    /// Temporary root holding the configuration and the pipes
+    root: PathBuf,
+    /// Configured pipe names and their paths
+    pipes: Vec<(String, String)>,
+    /// Inputs of the topology, with the counters of their pipes
+    inputs: Vec<Arc<SplitIn>>,
+    /// Stops the readers
+    signal: Arc<Mutex<u8>>,
+    /// Reader threads
+    threads: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl Harness {
+    /// Run the topology described by the INI document `config`, where
+    /// `{root}` stands for a new temporary root. The FIFOs of every input
+    /// and output are created before the splitter starts.
+    pub fn spawn(config: &str) -> io::Result<Harness> {
+        let root = std::env::temp_dir().join(format!(
+            "psplit-test-{}-{}",
+            std::process::id(),
+            ROOTS.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&root)?;
+        let mut harness = Harness {
+            pipes: Vec::new(),
+            inputs: Vec::new(),
+            signal: Arc::new(Mutex::new(SIG_RUN)),
+            threads: Vec::new(),
+            root,
+        };
+
+        let config_path = harness.root.join("psplit.ini");
+        fs::write(
+            &config_path,
+            config.replace("{root}", &harness.root.to_string_lossy()),
+        )?;
+        let topology = Parser::load_topology(&config_path, None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        for input in &topology.inputs {
+            create_fifo(&input.pipe)?;
+            harness
+                .pipes
+                .push((input.name().to_owned(), input.pipe.clone()));
+            for output in &input.outputs {
+                create_fifo(&output.pipe)?;
+                harness
+                    .pipes
+                    .push((output.name().to_owned(), output.pipe.clone()));
+            }
+        }
+        harness.threads = create_splitting_threads(&topology.inputs, &harness.signal);
+        harness.inputs = topology.inputs;
+        Ok(harness)
+    }
+
+    /// Temporary root of the topology
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of the pipe named `name`, its label or configured path
+    pub fn pipe(&self, name: &str) -> Option<&str> {
+        self.pipes
+            .iter()
+            .find(|(pipe, path)| pipe == name || Path::new(path).file_name() == Some(name.as_ref()))
+            .map(|(_, path)| path.as_str())
+    }
+
+    /// Paths of every configured pipe, by name
+    pub fn pipes(&self) -> &[(String, String)] {
+        &self.pipes
+    }
+
+    /// Inputs of the topology
+    pub(crate) fn inputs(&self) -> &[Arc<SplitIn>] {
+        &self.inputs
+    }
+
+    /// Stop the splitter and wait for its readers to exit
+    pub fn stop(&mut self) {
+        *self.signal.lock().unwrap() = SIG_EXIT;
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Writing end of an input FIFO, kept open so the splitter does not see the
+/// end of the stream between records
+pub struct Producer(File);
+
+impl Producer {
+    /// Open the FIFO at `path` for writing once its reader opened it
+    pub fn open<P: AsRef<Path>>(path: P, timeout: Duration) -> io::Result<Producer> {
+        let start = Instant::now();
+        loop {
+            let opened = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path.as_ref());
+            match opened {
+                Ok(file) => return Ok(Producer(file)),
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) && start.elapsed() < timeout => {
+                    thread::sleep(POLL)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Write all of `data`, waiting while the FIFO is full
+    pub fn write(&mut self, mut data: &[u8], timeout: Duration) -> io::Result<()> {
+        let start = Instant::now();
+        while !data.is_empty() {
+            match self.0.write(data) {
+                Ok(written) => data = &data[written..],
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock && start.elapsed() < timeout => {
+                    thread::sleep(POLL)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reading end of an output FIFO
+pub struct Consumer(File);
+
+impl Consumer {
+    /// Open the FIFO at `path` for reading, creating it when missing
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Consumer> {
+        create_fifo(&path)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        Ok(Consumer(file))
+    }
+
+    /// Data received until `len` bytes arrived or `timeout` passed
+    pub fn collect(&mut self, len: usize, timeout: Duration) -> io::Result<Vec<u8>> {
+        let start = Instant::now();
+        let mut received = Vec::with_capacity(len);
+        let mut buffer = [0u8; 4096];
+        while received.len() < len && start.elapsed() < timeout {
+            match self.0.read(&mut buffer) {
+                Ok(0) => thread::sleep(POLL),
+                Ok(read) => received.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(received)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_through_harness() {
+        let config = "
+[DEFAULT]
+root={root}
+[PIPES]
+canBus=1,rt
+[canBus]
+canBusLogApp=1,wt,queue=8,label=log
+canBusFuelApp=1,wt,queue=8,require_json=yes
+";
+        let harness = Harness::spawn(config).expect("spawn");
+        assert_eq!(harness.pipes().len(), 3);
+        let timeout = Duration::from_secs(5);
+        let mut log = Consumer::open(harness.pipe("log").unwrap()).unwrap();
+        let mut fuel = Consumer::open(harness.pipe("canBusFuelApp").unwrap()).unwrap();
+
+        let mut producer = Producer::open(harness.pipe("canBus").unwrap(), timeout).unwrap();
+        producer
+            .write(b"fuel=12\n{\"fuel\":12}\n", timeout)
+            .unwrap();
+        assert_eq!(
+            log.collect(20, timeout).unwrap(),
+            b"fuel=12\n{\"fuel\":12}\n"
+        );
+        assert_eq!(fuel.collect(12, timeout).unwrap(), b"{\"fuel\":12}\n");
+
+        let root = harness.root().to_path_buf();
+        drop(producer);
+        drop(harness);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn stop_with_idle_producer() {
+        let config = "
+[DEFAULT]
+root={root}
+[PIPES]
+canBus=1,rt
+[canBus]
+canBusLogApp=1,wt,queue=8
+";
+        let mut harness = Harness::spawn(config).expect("spawn");
+        let timeout = Duration::from_secs(5);
+        let mut log = Consumer::open(harness.pipe("canBusLogApp").unwrap()).unwrap();
+        let mut producer = Producer::open(harness.pipe("canBus").unwrap(), timeout).unwrap();
+        producer.write(b"fuel=12\n", timeout).unwrap();
+        assert_eq!(log.collect(8, timeout).unwrap(), b"fuel=12\n");
+
+        // The producer stays connected without sending anything
+        let (stopped, done) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            harness.stop();
+            let _ = stopped.send(());
+        });
+        assert!(done.recv_timeout(timeout).is_ok());
+        drop(producer);
+    }
+}
@@ -0,0 +1,64 @@
+//! Process wide throughput ceiling, `[DEFAULT] max_throughput=<bytes/s>`: a
+//! token bucket shared by every reader. A reader takes the bytes of each
+//! record it reads and, once the bucket is in debt, waits for it to refill
+//! before reading on, so producers are held back by their FIFOs rather than
+//! records being dropped. The bucket holds at most one second of bytes.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket refilled at `rate` bytes per second
+pub(crate) struct Throttle {
+    /// Bytes per second
+    rate: u64,
+    /// Bytes available, negative once records were let through on credit,
+    /// and when they were last counted
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl Throttle {
+    /// Full bucket of `rate` bytes per second
+    pub fn new(rate: u64) -> Throttle {
+        Throttle {
+            rate,
+            bucket: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    /// Bytes per second
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait before the
+    /// bucket is out of debt
+    pub fn take(&self, bytes: usize) -> Duration {
+        let rate = self.rate as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let (available, counted) = *bucket;
+        let refilled = available + now.duration_since(counted).as_secs_f64() * rate;
+        let available = refilled.min(rate) - bytes as f64;
+        *bucket = (available, now);
+        match available < 0.0 {
+            true => Duration::from_secs_f64(-available / rate),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wait_for_refill() {
+        let throttle = Throttle::new(1000);
+        assert_eq!(throttle.take(600), Duration::ZERO);
+        assert_eq!(throttle.take(400), Duration::ZERO);
+        // In debt of about 500 bytes, half a second of refill
+        let wait = throttle.take(500);
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+        std::thread::sleep(wait);
+        assert!(throttle.take(0) < Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,127 @@
+//! Periodic timers of the supervising loop, as `timerfd` descriptors polled
+//! alongside the signals, so every periodic task runs from the same event
+//! loop at its own interval rather than when a common sleep happens to end.
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+
+/// Timer expiring every `period`, readable once it expired
+pub(crate) struct Timer {
+    /// Non blocking monotonic `timerfd`
+    fd: OwnedFd,
+}
+
+impl Timer {
+    /// Timer first expiring `period` from now, then every `period`
+    pub fn every(period: Duration) -> io::Result<Timer> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let timer = Timer {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+        // A zero value would disarm the timer
+        let period = period.max(Duration::from_nanos(1));
+        let spec = libc::timespec {
+            tv_sec: period.as_secs() as libc::time_t,
+            tv_nsec: period.subsec_nanos() as libc::c_long,
+        };
+        let value = libc::itimerspec {
+            it_interval: spec,
+            it_value: spec,
+        };
+        match unsafe { libc::timerfd_settime(fd, 0, &value, std::ptr::null_mut()) } {
+            0 => Ok(timer),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Expirations since the previous call, 0 when the timer did not expire
+    pub fn expirations(&self) -> io::Result<u64> {
+        let mut count: u64 = 0;
+        let read = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                &mut count as *mut u64 as *mut libc::c_void,
+                mem::size_of::<u64>(),
+            )
+        };
+        if read == mem::size_of::<u64>() as isize {
+            return Ok(count);
+        }
+        let error = io::Error::last_os_error();
+        match error.kind() {
+            io::ErrorKind::WouldBlock => Ok(0),
+            _ => Err(error),
+        }
+    }
+}
+
+impl Source for Timer {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mio::{Events, Poll};
+    use std::time::Instant;
+
+    #[test]
+    fn expire_periodically() {
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(2);
+        let mut fast = Timer::every(Duration::from_millis(20)).unwrap();
+        let mut slow = Timer::every(Duration::from_secs(60)).unwrap();
+        poll.registry()
+            .register(&mut fast, Token(0), Interest::READABLE)
+            .unwrap();
+        poll.registry()
+            .register(&mut slow, Token(1), Interest::READABLE)
+            .unwrap();
+        assert_eq!(fast.expirations().unwrap(), 0);
+
+        let start = Instant::now();
+        poll.poll(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        let tokens: Vec<Token> = events.iter().map(|event| event.token()).collect();
+        assert_eq!(tokens, [Token(0)]);
+        assert!(start.elapsed() >= Duration::from_millis(15));
+        assert_eq!(fast.expirations().unwrap(), 1);
+
+        std::thread::sleep(Duration::from_millis(70));
+        assert!(fast.expirations().unwrap() >= 3);
+        assert_eq!(slow.expirations().unwrap(), 0);
+    }
+}
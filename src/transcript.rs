@@ -0,0 +1,172 @@
+//! Transcripts of pipes, `transcript=<file>`: the records an input read, or
+//! were routed to an output, each on a line after the time they came at, as
+//! `<seconds>.<microseconds>` since the epoch. Records of byte pipes are
+//! written in base64.
+//!
+//! A transcript keeps the last `transcript_window` of records, within
+//! `transcript_size` bytes. Records are appended to `<file>` until it holds
+//! half either bound, when it replaces `<file>.1` and a new one starts, so
+//! the two files together always hold the most recent records.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::base64;
+
+/// Bytes kept by a transcript unless `transcript_size` is set
+pub(crate) const DEFAULT_SIZE: u64 = 1 << 20;
+
+/// Time kept by a transcript unless `transcript_window` is set
+pub(crate) const DEFAULT_WINDOW: Duration = Duration::from_secs(600);
+
+/// File being appended to
+struct Segment {
+    /// Current file, opened on the first record
+    file: Option<File>,
+    /// Bytes in the current file
+    size: u64,
+    /// When the current file was started
+    started: Instant,
+    /// The last write failed and was logged
+    failed: bool,
+}
+
+/// Rolling transcript of a pipe
+pub(crate) struct Transcript {
+    /// Path of the current file
+    path: PathBuf,
+    /// Bytes kept, in both files
+    size: u64,
+    /// Time kept, in both files
+    window: Duration,
+    /// Records are written in base64
+    binary: bool,
+    /// Current file
+    segment: Mutex<Segment>,
+}
+
+impl Transcript {
+    /// Transcript at `path` keeping `size` bytes and `window` of records,
+    /// in base64 when `binary`
+    pub fn new<P: AsRef<Path>>(path: P, size: u64, window: Duration, binary: bool) -> Transcript {
+        Transcript {
+            path: path.as_ref().to_path_buf(),
+            size,
+            window,
+            binary,
+            segment: Mutex::new(Segment {
+                file: None,
+                size: 0,
+                started: Instant::now(),
+                failed: false,
+            }),
+        }
+    }
+
+    /// Path of the previous file
+    fn previous(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".1");
+        path.into()
+    }
+
+    /// Line transcribing `data`, received at `time`
+    fn line(&self, time: SystemTime, data: &[u8]) -> Vec<u8> {
+        let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut line = format!("{}.{:06} ", since.as_secs(), since.subsec_micros()).into_bytes();
+        match self.binary {
+            true => line.extend_from_slice(&base64::encode(data)),
+            false => line.extend_from_slice(data.strip_suffix(b"\n").unwrap_or(data)),
+        }
+        line.push(b'\n');
+        line
+    }
+
+    /// Append `line`, starting a new file first when the current one holds
+    /// half the size or the window
+    fn append(&self, segment: &mut Segment, line: &[u8]) -> io::Result<()> {
+        let full = segment.size + line.len() as u64 > self.size / 2
+            || segment.started.elapsed() >= self.window / 2;
+        if segment.file.is_none() || (full && segment.size > 0) {
+            if segment.file.take().is_some() {
+                fs::rename(&self.path, self.previous())?;
+            }
+            segment.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&self.path)?,
+            );
+            segment.size = 0;
+            segment.started = Instant::now();
+        }
+        if let Some(file) = segment.file.as_mut() {
+            file.write_all(line)?;
+        }
+        segment.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Transcribe `data`, received now
+    pub fn record(&self, data: &[u8]) {
+        let line = self.line(SystemTime::now(), data);
+        let mut segment = self.segment.lock().unwrap();
+        match self.append(&mut segment, &line) {
+            Ok(()) => segment.failed = false,
+            Err(e) => {
+                if !segment.failed {
+                    log!("Transcript -> {} Error {:?}", self.path.display(), e);
+                }
+                segment.failed = true;
+                segment.file = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn keep_recent_records() {
+        let path = temp_dir().join(format!("p_split_transcript_{}", std::process::id()));
+        let transcript = Transcript::new(&path, 128, DEFAULT_WINDOW, false);
+        let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_042);
+        assert_eq!(
+            transcript.line(time, b"fuel=12\n"),
+            b"1700000000.000042 fuel=12\n"
+        );
+        let binary = Transcript::new(&path, 128, DEFAULT_WINDOW, true);
+        assert_eq!(binary.line(time, b"\x00\x01"), b"1700000000.000042 AAE=\n");
+
+        for i in 0..20 {
+            transcript.record(format!("fuel={i}\n").as_bytes());
+        }
+        let current = fs::read_to_string(&path).unwrap();
+        let previous = fs::read_to_string(transcript.previous()).unwrap();
+        assert!(current.len() + previous.len() <= 128);
+        assert!(current.ends_with(" fuel=19\n"));
+        assert!(!previous.contains(" fuel=0\n"));
+
+        // A new window starts a new file
+        let path = temp_dir().join(format!("p_split_transcript_w_{}", std::process::id()));
+        let windowed = Transcript::new(&path, DEFAULT_SIZE, Duration::from_millis(20), false);
+        windowed.record(b"fuel=1\n");
+        std::thread::sleep(Duration::from_millis(15));
+        windowed.record(b"fuel=2\n");
+        assert!(fs::read_to_string(&path).unwrap().ends_with(" fuel=2\n"));
+        assert!(fs::read_to_string(windowed.previous())
+            .unwrap()
+            .ends_with(" fuel=1\n"));
+
+        for transcript in [transcript, windowed] {
+            let _ = fs::remove_file(&transcript.path);
+            let _ = fs::remove_file(transcript.previous());
+        }
+    }
+}
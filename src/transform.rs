@@ -0,0 +1,638 @@
+//! Per-output filtering and rewriting of records, applied by the reader before a
+//! record is queued on an output so every output gets its own shape of the data.
+//!
+//! The records of an output go through a chain of [`Transform`]s: the one
+//! listed in its `transforms=` option, the pipeline declared stage by stage
+//! with `filter=`, `transform=` and `encode=` (see [`STAGES`]), or else the
+//! built-ins enabled by their own options, in [`BUILTINS`] order. Custom
+//! transforms are made available to chains with [`register_transform`].
+use std::sync::Arc;
+
+use crate::crypt::Key;
+use crate::options::PipeOptions;
+use crate::registry::Registry;
+use crate::{base64, json, ParseError};
+
+/// A stage of the transform chain of an output
+pub trait Transform: Send + Sync {
+    /// Rewrite `data`, `None` when the record is dropped
+    fn apply(&self, data: Vec<u8>) -> Option<Vec<u8>>;
+}
+
+/// Options of the output a transform is built for
+pub struct TransformOptions<'a> {
+    /// Name the transform is listed with
+    name: &'a str,
+    /// Options of the output
+    options: &'a PipeOptions,
+}
+
+impl TransformOptions<'_> {
+    /// Value of the option named after the transform, its parameter
+    pub fn value(&self) -> Option<&str> {
+        self.options.get(self.name)
+    }
+    /// Value of the output's option `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.get(key)
+    }
+}
+
+/// Builds a transform from the options of an output, or explains why they
+/// are invalid
+pub type TransformFactory =
+    dyn Fn(&TransformOptions) -> Result<Box<dyn Transform>, String> + Send + Sync;
+
+/// Custom transform factories by name
+static TRANSFORMS: Registry<TransformFactory> = Registry::new();
+
+/// Make `name` a transform that `transforms=` can list, built by `factory`
+/// from the options of the output. A custom transform replaces a built-in of
+/// the same name. Transforms must be registered before the configuration is
+/// loaded.
+pub fn register_transform<F>(name: &str, factory: F)
+where
+    F: Fn(&TransformOptions) -> Result<Box<dyn Transform>, String> + Send + Sync + 'static,
+{
+    TRANSFORMS.register(name, Arc::new(factory));
+}
+
+/// Built-in transforms, in the order they are chained when enabled by their
+/// options without `transforms=`
+pub(crate) const BUILTINS: &[&str] = &[
+    "strip_ansi",
+    "columns",
+    "prefix",
+    "csv_json",
+    "require_json",
+    "select",
+    "base64",
+    "encrypt",
+    "newline",
+];
+
+/// Built-ins enabled with a boolean option, the others take a parameter
+const FLAGS: &[&str] = &["strip_ansi", "require_json", "base64", "newline"];
+
+/// Stages of the pipeline of an output, in the order records go through
+/// them before reaching the pipe or sink, each declared with the option of
+/// the same name listing its transforms
+pub(crate) const STAGES: &[&str] = &["filter", "transform", "encode"];
+
+/// Stage the built-in `name` belongs to, custom transforms fit in any
+fn stage_of(name: &str) -> Option<&'static str> {
+    match name {
+        "require_json" => Some("filter"),
+        "strip_ansi" | "columns" | "prefix" | "csv_json" | "select" | "newline" => {
+            Some("transform")
+        }
+        "base64" | "encrypt" => Some("encode"),
+        _ => None,
+    }
+}
+
+/// Built-in transforms
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Builtin {
+    /// Turn CSV lines into JSON objects with the given member names,
+    /// `csv_json=ts,speed,fuel`
+    CsvJson(Vec<String>),
+    /// Drop records that are not a JSON document, `require_json=true`
+    RequireJson,
+    /// Remove ANSI escape sequences, `strip_ansi=true`
+    StripAnsi,
+    /// Keep the listed fields of CSV lines, by index from 0, configured as
+    /// `columns=1,3,7` counting from 1
+    Columns(Vec<usize>),
+    /// Keep the listed members of JSON objects, `select=.ts,.speed`
+    Select(Vec<String>),
+    /// Encode records as base64 lines, `base64=true`
+    Base64,
+    /// Encrypt records, written as base64 lines, `encrypt=<key file>`
+    Encrypt(Key),
+    /// Append a newline to records not ending with one, `newline=true`
+    Newline,
+    /// Put text in front of records, `prefix=<text>`
+    Prefix(Vec<u8>),
+}
+
+impl Builtin {
+    /// Built-in `name` configured from its option, `None` when there is no
+    /// such built-in
+    fn configure(name: &str, options: &TransformOptions) -> Option<Result<Builtin, String>> {
+        let value = options.value().unwrap_or_default();
+        let invalid = || format!("Invalid value '{value}' for option '{name}'");
+        let builtin = match name {
+            "strip_ansi" => Ok(Builtin::StripAnsi),
+            "require_json" => Ok(Builtin::RequireJson),
+            "base64" => Ok(Builtin::Base64),
+            "newline" => Ok(Builtin::Newline),
+            "prefix" => Ok(Builtin::Prefix(value.as_bytes().to_vec())),
+            "columns" => value
+                .split(',')
+                .map(|c| c.trim().parse::<usize>().ok()?.checked_sub(1))
+                .collect::<Option<Vec<usize>>>()
+                .map(Builtin::Columns)
+                .ok_or_else(invalid),
+            "csv_json" => {
+                let header: Vec<String> = value.split(',').map(|h| h.trim().to_owned()).collect();
+                match header.iter().any(|h| h.is_empty() || h.contains('"')) {
+                    true => Err(invalid()),
+                    false => Ok(Builtin::CsvJson(header)),
+                }
+            }
+            "select" => value
+                .split(',')
+                .map(|f| f.trim().strip_prefix('.'))
+                .map(|f| f.filter(|f| !f.is_empty() && !f.contains('.')))
+                .map(|f| f.map(str::to_owned))
+                .collect::<Option<Vec<String>>>()
+                .map(Builtin::Select)
+                .ok_or_else(invalid),
+            "encrypt" => Key::load(value)
+                .map(Builtin::Encrypt)
+                .map_err(|e| format!("Cannot load key '{value}' for option 'encrypt': {e}")),
+            _ => return None,
+        };
+        Some(builtin)
+    }
+}
+
+impl Transform for Builtin {
+    fn apply(&self, mut data: Vec<u8>) -> Option<Vec<u8>> {
+        match self {
+            Builtin::RequireJson => {
+                return json::is_valid(split_line_ending(&data).0).then_some(data)
+            }
+            Builtin::StripAnsi => data = strip_ansi(&data),
+            Builtin::Columns(columns) => data = project_columns(&data, columns),
+            Builtin::CsvJson(header) => data = csv_to_json(&data, header),
+            Builtin::Select(fields) => data = select_fields(data, fields),
+            Builtin::Base64 => {
+                data = base64::encode(&data);
+                data.push(b'\n');
+            }
+            Builtin::Encrypt(key) => {
+                data = base64::encode(&key.seal(&data)?);
+                data.push(b'\n');
+            }
+            Builtin::Newline => {
+                if data.last() != Some(&b'\n') {
+                    data.push(b'\n');
+                }
+            }
+            Builtin::Prefix(prefix) => data = [prefix.as_slice(), &data].concat(),
+        }
+        Some(data)
+    }
+}
+
+/// `data` without its ANSI escape sequences: CSI sequences such as colours
+/// and cursor movements, OSC strings such as window titles, and two byte
+/// escapes
+fn strip_ansi(data: &[u8]) -> Vec<u8> {
+    const ESC: u8 = 0x1b;
+    const BEL: u8 = 0x07;
+
+    let mut stripped = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != ESC {
+            stripped.push(data[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match data.get(i) {
+            // CSI: parameter and intermediate bytes up to a final byte
+            Some(b'[') => {
+                i += 1;
+                while i < data.len() && !(0x40..=0x7e).contains(&data[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            // OSC: up to BEL or the string terminator ESC \
+            Some(b']') => {
+                i += 1;
+                while i < data.len() && data[i] != BEL && data[i] != ESC {
+                    i += 1;
+                }
+                match data.get(i) {
+                    Some(&ESC) => i += 2,
+                    _ => i += 1,
+                }
+            }
+            Some(_) => i += 1,
+            None => {}
+        }
+    }
+    stripped
+}
+
+/// `line` without its line ending, and the line ending
+fn split_line_ending(line: &[u8]) -> (&[u8], &[u8]) {
+    let content = line.strip_suffix(b"\n").unwrap_or(line);
+    let content = content.strip_suffix(b"\r").unwrap_or(content);
+    line.split_at(content.len())
+}
+
+/// Fields of a CSV line, as written including any quotes
+pub(crate) fn csv_fields(line: &[u8]) -> Vec<&[u8]> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, byte) in line.iter().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b',' if !quoted => {
+                fields.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+}
+
+/// The `columns` of the CSV line `data`, missing ones left empty
+fn project_columns(data: &[u8], columns: &[usize]) -> Vec<u8> {
+    let (line, ending) = split_line_ending(data);
+    let fields = csv_fields(line);
+
+    let mut projected = Vec::with_capacity(data.len());
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            projected.push(b',');
+        }
+        projected.extend_from_slice(fields.get(*column).copied().unwrap_or_default());
+    }
+    projected.extend_from_slice(ending);
+    projected
+}
+
+/// Contents of a CSV field, unquoted
+fn csv_unquote(field: &[u8]) -> Vec<u8> {
+    match field
+        .strip_prefix(b"\"")
+        .and_then(|f| f.strip_suffix(b"\""))
+    {
+        Some(quoted) => {
+            let mut unquoted = Vec::with_capacity(quoted.len());
+            let mut bytes = quoted.iter().peekable();
+            while let Some(byte) = bytes.next() {
+                unquoted.push(*byte);
+                if *byte == b'"' && bytes.peek() == Some(&&b'"') {
+                    bytes.next();
+                }
+            }
+            unquoted
+        }
+        None => field.to_vec(),
+    }
+}
+
+/// The CSV line `data` as a JSON object with a member per `header` name.
+/// Unquoted numeric fields become numbers and the others strings; fields
+/// beyond the header are left out, as are members for missing fields.
+fn csv_to_json(data: &[u8], header: &[String]) -> Vec<u8> {
+    let (line, ending) = split_line_ending(data);
+
+    let mut object = vec![b'{'];
+    for (i, (name, field)) in header.iter().zip(csv_fields(line)).enumerate() {
+        if i > 0 {
+            object.push(b',');
+        }
+        json::write_string(&mut object, name.as_bytes());
+        object.push(b':');
+        if json::is_number(field) {
+            object.extend_from_slice(field);
+        } else {
+            json::write_string(&mut object, &csv_unquote(field));
+        }
+    }
+    object.push(b'}');
+    object.extend_from_slice(ending);
+    object
+}
+
+/// The object `data` with only the members named in `fields`, in that
+/// order; records that are not JSON objects are left as they are
+fn select_fields(data: Vec<u8>, fields: &[String]) -> Vec<u8> {
+    let (document, ending) = split_line_ending(&data);
+    let Some(members) = json::object_members(document) else {
+        return data;
+    };
+
+    let mut selected = vec![b'{'];
+    for field in fields {
+        let Some((key, value)) = members.iter().find(|(k, _)| *k == field.as_bytes()) else {
+            continue;
+        };
+        if selected.len() > 1 {
+            selected.push(b',');
+        }
+        selected.push(b'"');
+        selected.extend_from_slice(key);
+        selected.extend_from_slice(b"\":");
+        selected.extend_from_slice(value);
+    }
+    selected.push(b'}');
+    selected.extend_from_slice(ending);
+    selected
+}
+
+/// The record carried by the base64 line `line`, `None` when it is not
+/// valid base64
+pub(crate) fn decode_base64(line: &[u8]) -> Option<Vec<u8>> {
+    base64::decode(split_line_ending(line).0)
+}
+
+/// Transform chain of an output
+#[derive(Clone, Default)]
+pub(crate) struct Chain {
+    /// Transforms with the names they are listed with, in order
+    stages: Vec<(String, Arc<dyn Transform>)>,
+}
+
+impl Chain {
+    /// Names listed in the `transforms=` option and the stage options
+    pub fn listed(options: &PipeOptions) -> Vec<&str> {
+        ["transforms"]
+            .iter()
+            .chain(STAGES)
+            .filter_map(|key| options.get(key))
+            .flat_map(|list| list.split(',').map(str::trim))
+            .collect()
+    }
+
+    /// Transforms listed in `transforms=` or declared stage by stage, `None`
+    /// when the chain is not listed
+    fn declared(options: &PipeOptions) -> Result<Option<Vec<&str>>, ParseError> {
+        let stages: Vec<&str> = STAGES
+            .iter()
+            .copied()
+            .filter(|stage| options.get(stage).is_some())
+            .collect();
+        match (options.get("transforms"), stages.first()) {
+            (Some(_), Some(stage)) => Err(ParseError::Configuration(format!(
+                "Option 'transforms' cannot be used with option '{stage}'"
+            ))),
+            (Some(list), None) => Ok(Some(list.split(',').map(str::trim).collect())),
+            (None, Some(_)) => {
+                for stage in stages {
+                    for name in options.get(stage).unwrap_or_default().split(',') {
+                        Self::check_stage(stage, name.trim())?;
+                    }
+                }
+                Ok(Some(Self::listed(options)))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Check the transform `name` may be declared in `stage`
+    fn check_stage(stage: &str, name: &str) -> Result<(), ParseError> {
+        match stage_of(name) {
+            Some(expected) if expected != stage && TRANSFORMS.get(name).is_none() => {
+                Err(ParseError::Configuration(format!(
+                    "Transform '{name}' cannot be used in option '{stage}', it is a '{expected}' stage"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the option of the built-in `name` is set
+    fn enabled(options: &PipeOptions, name: &str) -> Result<bool, ParseError> {
+        match FLAGS.contains(&name) {
+            true => Ok(options.flag(name)?.unwrap_or(false)),
+            false => Ok(options.get(name).is_some()),
+        }
+    }
+
+    /// Chain of the output with `options`
+    pub fn build(options: &PipeOptions) -> Result<Chain, ParseError> {
+        let declared = Self::declared(options)?;
+        let mut names = Vec::new();
+        for name in BUILTINS {
+            if !Self::enabled(options, name)? {
+                continue;
+            }
+            if declared
+                .as_ref()
+                .is_some_and(|listed| !listed.contains(name))
+            {
+                return Err(ParseError::Configuration(format!(
+                    "Option '{name}' has no effect unless listed in the pipeline of the output"
+                )));
+            }
+            names.push(*name);
+        }
+        if let Some(listed) = declared {
+            names = listed;
+        }
+
+        let mut stages = Vec::with_capacity(names.len());
+        for name in names {
+            let options = TransformOptions { name, options };
+            let transform: Result<Arc<dyn Transform>, String> = match TRANSFORMS.get(name) {
+                Some(factory) => factory(&options).map(Arc::from),
+                None => match Builtin::configure(name, &options) {
+                    Some(builtin) => builtin.map(|b| Arc::new(b) as Arc<dyn Transform>),
+                    None => Err(format!("Unknown transform '{name}'")),
+                },
+            };
+            stages.push((
+                name.to_owned(),
+                transform.map_err(ParseError::Configuration)?,
+            ));
+        }
+        Ok(Chain { stages })
+    }
+
+    /// Names of the transforms, in order
+    pub fn names(&self) -> Vec<&str> {
+        self.stages.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Whether the chain has the transform `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.stages.iter().any(|(n, _)| n == name)
+    }
+
+    /// `data` through every transform, `None` when one drops the record
+    pub fn apply(&self, data: &[u8]) -> Option<Vec<u8>> {
+        self.stages
+            .iter()
+            .try_fold(data.to_vec(), |data, (_, transform)| transform.apply(data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// `data` through `transforms`
+    fn run(transforms: &[Builtin], data: &[u8]) -> Option<Vec<u8>> {
+        transforms
+            .iter()
+            .try_fold(data.to_vec(), |data, transform| transform.apply(data))
+    }
+
+    /// `data` through `transforms`, which must keep it
+    fn kept(transforms: &[Builtin], data: &[u8]) -> Vec<u8> {
+        run(transforms, data).expect("record kept")
+    }
+
+    #[test]
+    fn require_json_documents() {
+        let transforms = [Builtin::RequireJson];
+        assert_eq!(kept(&transforms, b"{\"ts\": 17}\n"), b"{\"ts\": 17}\n");
+        assert_eq!(kept(&transforms, b" [1, \"a\"] "), b" [1, \"a\"] ");
+        assert_eq!(run(&transforms, b"{\"ts\": 17\n"), None);
+        assert_eq!(run(&transforms, b"fuel=12\n"), None);
+        assert_eq!(run(&transforms, b"\n"), None);
+    }
+
+    #[test]
+    fn strip_escape_codes() {
+        let transforms = [Builtin::StripAnsi];
+        assert_eq!(
+            kept(&transforms, b"\x1b[1;31mfuel\x1b[0m=12\x1b[K\n"),
+            b"fuel=12\n"
+        );
+        assert_eq!(kept(&transforms, b"\x1b]0;title\x07a\x1bMb"), b"ab");
+        assert_eq!(kept(&transforms, b"a\x1b]8;;\x1b\\b"), b"ab");
+        assert_eq!(kept(&transforms, b"cut\x1b[3"), b"cut");
+    }
+
+    #[test]
+    fn project_csv_columns() {
+        let transforms = [Builtin::Columns(vec![0, 2, 6])];
+        assert_eq!(
+            kept(&transforms, b"12:00,cv1,\"8,5\",x,y,z,fuel\r\n"),
+            b"12:00,\"8,5\",fuel\r\n"
+        );
+        assert_eq!(kept(&transforms, b"a,b,c"), b"a,c,");
+    }
+
+    #[test]
+    fn convert_csv_to_json() {
+        let transforms = [Builtin::CsvJson(vec![
+            "ts".into(),
+            "unit".into(),
+            "speed".into(),
+        ])];
+        assert_eq!(
+            kept(&transforms, b"12:00,\"km/h, \"\"avg\"\"\",92.5,extra\n"),
+            b"{\"ts\":\"12:00\",\"unit\":\"km/h, \\\"avg\\\"\",\"speed\":92.5}\n"
+        );
+        assert_eq!(kept(&transforms, b"12:00"), b"{\"ts\":\"12:00\"}");
+    }
+
+    #[test]
+    fn select_json_fields() {
+        let transforms = [Builtin::Select(vec!["speed".into(), "ts".into()])];
+        assert_eq!(
+            kept(
+                &transforms,
+                b"{\"ts\": 17, \"fuel\": 8.5, \"speed\": {\"kmh\": 92}}\n"
+            ),
+            b"{\"speed\":{\"kmh\": 92},\"ts\":17}\n"
+        );
+        assert_eq!(kept(&transforms, b"{\"fuel\": 8.5}"), b"{}");
+        assert_eq!(kept(&transforms, b"fuel=8.5\n"), b"fuel=8.5\n");
+    }
+
+    #[test]
+    fn base64_lines() {
+        let line = kept(&[Builtin::Base64], b"\x08\x96\x01\n");
+        assert_eq!(line, b"CJYBCg==\n");
+        assert_eq!(
+            decode_base64(&line).as_deref(),
+            Some(&b"\x08\x96\x01\n"[..])
+        );
+        assert_eq!(decode_base64(b"not base64\n"), None);
+    }
+
+    #[test]
+    fn encrypt_records() {
+        let path = std::env::temp_dir().join("p_split_transform_key");
+        std::fs::write(&path, [7u8; 32]).unwrap();
+        let key = Key::load(&path).unwrap();
+
+        let line = kept(&[Builtin::Encrypt(key.clone())], b"fuel=12\n");
+        assert_eq!(line.last(), Some(&b'\n'));
+        let sealed = decode_base64(&line).expect("base64 line");
+        assert_eq!(key.open(&sealed).as_deref(), Some(&b"fuel=12\n"[..]));
+    }
+
+    #[test]
+    fn build_chains() {
+        struct Upper;
+        impl Transform for Upper {
+            fn apply(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+                Some(data.to_ascii_uppercase())
+            }
+        }
+        register_transform("upper", |_| Ok(Box::new(Upper)));
+
+        let options = PipeOptions::parse(&["newline=yes", "strip_ansi=1"]).unwrap();
+        let chain = Chain::build(&options).unwrap();
+        assert_eq!(chain.names(), ["strip_ansi", "newline"]);
+
+        let options =
+            PipeOptions::parse(&["transforms=upper", "prefix", "newline", "prefix=fuel:"]).unwrap();
+        let chain = Chain::build(&options).unwrap();
+        assert_eq!(chain.names(), ["upper", "prefix", "newline"]);
+        assert_eq!(chain.apply(b"fuel").as_deref(), Some(&b"fuel:FUEL\n"[..]));
+
+        let options = PipeOptions::parse(&["transforms=upper", "newline=yes"]).unwrap();
+        assert!(Chain::build(&options).is_err());
+        let options = PipeOptions::parse(&["transforms=gzip"]).unwrap();
+        assert!(Chain::build(&options).is_err());
+    }
+
+    #[test]
+    fn build_pipelines() {
+        let options = PipeOptions::parse(&[
+            "encode=base64",
+            "filter=require_json",
+            "transform=select",
+            "prefix",
+            "select=.ts",
+            "prefix=fuel:",
+        ])
+        .unwrap();
+        let chain = Chain::build(&options).unwrap();
+        assert_eq!(
+            chain.names(),
+            ["require_json", "select", "prefix", "base64"]
+        );
+        assert_eq!(
+            chain.apply(b"{\"ts\":1,\"a\":2}"),
+            Some(b"ZnVlbDp7InRzIjoxfQ==\n".to_vec())
+        );
+        assert_eq!(chain.apply(b"fuel=12"), None);
+
+        for tokens in [
+            &["filter=base64"][..],
+            &["transform=select", "select=.ts", "require_json=yes"],
+            &["transforms=select", "encode=base64", "select=.ts"],
+        ] {
+            let options = PipeOptions::parse(tokens).unwrap();
+            assert!(Chain::build(&options).is_err(), "{tokens:?}");
+        }
+    }
+
+    #[test]
+    fn append_newline() {
+        let transforms = [Builtin::Newline];
+        assert_eq!(kept(&transforms, b"fuel=12"), b"fuel=12\n");
+        assert_eq!(kept(&transforms, b"fuel=12\n"), b"fuel=12\n");
+        assert_eq!(kept(&transforms, b""), b"\n");
+        assert_eq!(kept(&[], b"fuel=12"), b"fuel=12");
+    }
+}
@@ -0,0 +1,57 @@
+//! Framing of length-delimited streams, where every message is preceded by
+//! its length as a base 128 varint, as written by protobuf's
+//! `writeDelimitedTo`.
+use std::io;
+
+/// Longest encoding of a 64 bit varint
+const MAX_VARINT_LEN: usize = 10;
+/// Largest accepted message, a larger length means the stream is corrupt
+pub(crate) const MAX_MESSAGE: u64 = 64 << 20;
+
+/// Length of the first message of `data`, its length prefix included, or
+/// `None` if `data` does not hold all of it yet
+pub(crate) fn message_len(data: &[u8]) -> io::Result<Option<usize>> {
+    let mut len: u64 = 0;
+    for (i, byte) in data.iter().enumerate().take(MAX_VARINT_LEN) {
+        len |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 != 0 {
+            continue;
+        }
+        if len > MAX_MESSAGE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message of {len} bytes exceeds the limit of {MAX_MESSAGE}"),
+            ));
+        }
+        let total = i + 1 + len as usize;
+        return Ok((data.len() >= total).then_some(total));
+    }
+    match data.len() < MAX_VARINT_LEN {
+        true => Ok(None),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed message length",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_messages() {
+        assert_eq!(message_len(b"").unwrap(), None);
+        assert_eq!(message_len(b"\x03abc\x01").unwrap(), Some(4));
+        assert_eq!(message_len(b"\x03ab").unwrap(), None);
+        assert_eq!(message_len(b"\x00").unwrap(), Some(1));
+
+        let mut long = vec![0xac, 0x02];
+        assert_eq!(message_len(&long).unwrap(), None);
+        long.resize(2 + 300, b'x');
+        assert_eq!(message_len(&long).unwrap(), Some(302));
+
+        assert!(message_len(&[0xff; 10]).is_err());
+        assert!(message_len(b"\x80\x80\x80\x80\x01").is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Write-ahead log of an input pipe.
+//!
+//! Records are appended to segment files named `<path>.<first sequence>`
+//! before they are dispatched to the outputs. The checkpoint file
+//! `<path>.checkpoint` holds the next sequence number and, for every output,
+//! the last sequence number it has settled (written or dropped). Segments
+//! whose records are settled by every output are removed, the rest is
+//! replayed when the input is started again.
+//!
+//! Segment records are `sequence (u64 LE) | length (u32 LE) | contents`.
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::state;
+
+/// Segment size after which a new segment is started
+pub(crate) const WAL_SEGMENT_SIZE: u64 = 1 << 20;
+
+/// Write-ahead log of an input pipe
+pub(crate) struct Wal {
+    /// Base path of the segment and checkpoint files
+    path: PathBuf,
+    /// Segments as `(first sequence, path)`, oldest first
+    segments: Vec<(u64, PathBuf)>,
+    /// Segment records are appended to
+    file: Option<File>,
+    /// Size of the current segment
+    size: u64,
+    /// Sequence number of the next record
+    next_seq: u64,
+    /// Settled sequence number of every output, by pipe
+    positions: HashMap<String, u64>,
+    /// Next sequence number recorded in the checkpoint
+    checkpoint_next: Option<u64>,
+}
+
+impl Wal {
+    /// Open the log at `path`, recovering segments and checkpoint of a
+    /// previous run
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Wal> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut wal = Wal {
+            segments: Self::find_segments(&path)?,
+            path,
+            file: None,
+            size: 0,
+            next_seq: 1,
+            positions: HashMap::new(),
+            checkpoint_next: None,
+        };
+        wal.read_checkpoint()?;
+
+        // Sequence numbers continue after the last logged record
+        if let Some((_, segment)) = wal.segments.last() {
+            if let Some(last) = Self::read_segment(segment)?.last() {
+                wal.next_seq = wal.next_seq.max(last.0 + 1);
+            }
+        }
+        Ok(wal)
+    }
+
+    /// Segment files of the log at `path`, oldest first
+    fn find_segments(path: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+        let parent = path.parent().unwrap_or(Path::new("."));
+        let mut prefix = path.file_name().unwrap_or_default().as_bytes().to_vec();
+        prefix.push(b'.');
+
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(parent)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let first = name
+                .as_bytes()
+                .strip_prefix(prefix.as_slice())
+                .and_then(|n| std::str::from_utf8(n).ok())
+                .and_then(|n| n.parse::<u64>().ok());
+            if let Some(first) = first {
+                segments.push((first, entry.path()));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    /// Records of the segment at `path`, a truncated last record is ignored
+    fn read_segment(path: &Path) -> io::Result<Vec<(u64, Vec<u8>)>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut records = Vec::new();
+        let mut header = [0u8; 12];
+
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let seq = u64::from_le_bytes(header[..8].try_into().unwrap());
+            let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+            let mut data = vec![0u8; len];
+            match reader.read_exact(&mut data) {
+                Ok(_) => records.push((seq, data)),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(records)
+    }
+
+    /// Path of the checkpoint file
+    fn checkpoint_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".checkpoint");
+        path.into()
+    }
+
+    /// Load sequence number and output positions of a previous run
+    fn read_checkpoint(&mut self) -> io::Result<()> {
+        let contents = match fs::read_to_string(self.checkpoint_path()) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for line in contents.lines() {
+            let Some((key, seq)) = line.rsplit_once(' ') else {
+                continue;
+            };
+            let Ok(seq) = seq.parse::<u64>() else {
+                continue;
+            };
+            if key == "next" {
+                self.next_seq = self.next_seq.max(seq);
+                self.checkpoint_next = Some(seq);
+            } else {
+                self.positions.insert(key.to_owned(), seq);
+            }
+        }
+        Ok(())
+    }
+
+    /// Last sequence number settled by the output `pipe` in a previous run,
+    /// outputs added since the last checkpoint start with the next record
+    pub fn position(&self, pipe: &str) -> u64 {
+        match self.positions.get(pipe) {
+            Some(seq) => *seq,
+            None => self.checkpoint_next.unwrap_or(1) - 1,
+        }
+    }
+
+    /// Sequence number of the next record
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Logged records with a sequence number above `after`
+    pub fn replay(&self, after: u64) -> io::Result<Vec<(u64, Vec<u8>)>> {
+        let mut records = Vec::new();
+        for (index, (_, segment)) in self.segments.iter().enumerate() {
+            // Skip segments entirely before `after`
+            if let Some((next_first, _)) = self.segments.get(index + 1) {
+                if *next_first <= after + 1 {
+                    continue;
+                }
+            }
+            records.extend(
+                Self::read_segment(segment)?
+                    .into_iter()
+                    .filter(|(seq, _)| *seq > after),
+            );
+        }
+        Ok(records)
+    }
+
+    /// Append a record, returning its sequence number
+    pub fn append(&mut self, data: &[u8]) -> io::Result<u64> {
+        if self.file.is_none() || self.size >= WAL_SEGMENT_SIZE {
+            let mut segment = self.path.clone().into_os_string();
+            segment.push(format!(".{:020}", self.next_seq));
+            let segment = PathBuf::from(segment);
+
+            self.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&segment)?,
+            );
+            self.size = 0;
+            self.segments.push((self.next_seq, segment));
+        }
+
+        let seq = self.next_seq;
+        let mut entry = Vec::with_capacity(12 + data.len());
+        entry.extend_from_slice(&seq.to_le_bytes());
+        entry.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        entry.extend_from_slice(data);
+
+        self.file.as_mut().unwrap().write_all(&entry)?;
+        self.size += entry.len() as u64;
+        self.next_seq += 1;
+        Ok(seq)
+    }
+
+    /// Record output positions and remove segments settled by every output
+    pub fn checkpoint(&mut self, positions: &[(&str, u64)]) -> io::Result<()> {
+        let mut contents = format!("next {}\n", self.next_seq);
+        for (pipe, seq) in positions {
+            contents.push_str(&format!("{pipe} {seq}\n"));
+        }
+        state::write_atomic(&self.checkpoint_path(), contents.as_bytes())?;
+
+        let settled = positions.iter().map(|(_, seq)| *seq).min().unwrap_or(0);
+        // A segment is settled once the next one starts after `settled`,
+        // the segment being written to is always kept
+        while self.segments.len() > 1 && self.segments[1].0 <= settled + 1 {
+            let (_, segment) = self.segments.remove(0);
+            fs::remove_file(segment)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn replay_after_restart() {
+        let dir = temp_dir().join("p_split_wal");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("input");
+
+        {
+            let mut wal = Wal::open(&path).expect("open");
+            assert_eq!(wal.append(b"a\n").unwrap(), 1);
+            assert_eq!(wal.append(b"b\n").unwrap(), 2);
+            assert_eq!(wal.append(b"c\n").unwrap(), 3);
+            wal.checkpoint(&[("/tmp/out1", 1), ("/tmp/out2", 2)])
+                .expect("checkpoint");
+        }
+
+        let mut wal = Wal::open(&path).expect("reopen");
+        assert_eq!(wal.position("/tmp/out1"), 1);
+        assert_eq!(wal.position("/tmp/out2"), 2);
+        assert_eq!(wal.position("/tmp/out3"), 3);
+        assert_eq!(
+            wal.replay(1).unwrap(),
+            vec![(2, b"b\n".to_vec()), (3, b"c\n".to_vec())]
+        );
+        assert_eq!(wal.append(b"d\n").unwrap(), 4);
+    }
+
+    #[test]
+    fn non_utf8_path() {
+        use std::ffi::OsStr;
+
+        let dir = temp_dir().join("p_split_wal_bytes");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(OsStr::from_bytes(b"input\xff"));
+        fs::create_dir_all(&dir).unwrap();
+        Wal::open(&path).expect("open").append(b"a\n").unwrap();
+
+        let wal = Wal::open(&path).expect("reopen");
+        assert_eq!(wal.next_seq(), 2);
+        assert_eq!(wal.replay(0).unwrap(), vec![(1, b"a\n".to_vec())]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Watchdog of the worker threads, enabled with `[DEFAULT] watchdog`.
+//!
+//! Every reader and writer reports progress each time its loop turns. A
+//! worker silent for longer than the watchdog timeout while it has work to
+//! do, stuck in a blocking call of a custom endpoint for instance, is
+//! considered wedged: an incident is logged and a new worker takes over the
+//! pipe. A thread cannot be killed, so the wedged one is left behind and
+//! exits on its own, without touching the pipe, once it returns.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{spawn_reader, RestartReason, SplitIn, SIG_EXIT};
+
+/// Progress marker of a worker waiting for work
+const IDLE: u64 = u64::MAX;
+
+/// Origin of the progress timestamps
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Milliseconds since [`epoch`]
+fn now() -> u64 {
+    epoch().elapsed().as_millis() as u64
+}
+
+/// Progress of the worker thread of a pipe, and which worker owns the pipe
+pub(crate) struct Liveness {
+    /// When the worker last made progress, [`IDLE`] while it has no work
+    progress: AtomicU64,
+    /// Generation of the worker owning the pipe, bumped on a replacement
+    generation: AtomicU64,
+    /// The owner of the worker has to start a new one
+    respawn: AtomicBool,
+}
+
+impl Default for Liveness {
+    fn default() -> Liveness {
+        Liveness {
+            progress: AtomicU64::new(now()),
+            generation: AtomicU64::new(0),
+            respawn: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Liveness {
+    /// Generation of a worker taking the pipe over
+    pub fn start(&self) -> u64 {
+        self.beat();
+        self.generation.load(Ordering::SeqCst)
+    }
+    /// The worker made progress
+    pub fn beat(&self) {
+        self.progress.store(now(), Ordering::Relaxed);
+    }
+    /// The worker waits for work and is not expected to make progress
+    pub fn idle(&self) {
+        self.progress.store(IDLE, Ordering::Relaxed);
+    }
+    /// Whether the worker of `generation` still owns the pipe
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+    /// Whether a new worker is due, clearing the request
+    pub fn take_respawn(&self) -> bool {
+        self.respawn.swap(false, Ordering::SeqCst)
+    }
+    /// How long the worker has had work without making progress
+    fn wedged_for(&self) -> Duration {
+        match self.progress.load(Ordering::Relaxed) {
+            IDLE => Duration::ZERO,
+            progress => Duration::from_millis(now().saturating_sub(progress)),
+        }
+    }
+    /// Hand the pipe over to a new worker
+    fn replace(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.beat();
+    }
+}
+
+/// Watch the workers of `inputs`, run by `readers` in order, every `period`
+/// until exit is requested through `signal`, and replace those wedged for
+/// `timeout`. Returns the reader threads running at exit.
+pub(crate) fn spawn(
+    inputs: Vec<Arc<SplitIn>>,
+    signal: Arc<Mutex<u8>>,
+    mut readers: Vec<JoinHandle<std::io::Result<()>>>,
+    timeout: Duration,
+    period: Duration,
+) -> JoinHandle<Vec<JoinHandle<std::io::Result<()>>>> {
+    thread::spawn(move || {
+        let running: Vec<Arc<SplitIn>> = inputs.into_iter().filter(|i| i.runs()).collect();
+        loop {
+            thread::sleep(period);
+            if *signal.lock().unwrap() == SIG_EXIT {
+                return readers;
+            }
+            for (input, reader) in running.iter().zip(readers.iter_mut()) {
+                check(input, reader, &signal, timeout);
+            }
+        }
+    })
+}
+
+/// Replace the reader of `input` or its writers when wedged for `timeout`
+fn check(
+    input: &Arc<SplitIn>,
+    reader: &mut JoinHandle<std::io::Result<()>>,
+    signal: &Arc<Mutex<u8>>,
+    timeout: Duration,
+) {
+    let wedged_for = input.worker.wedged_for();
+    if wedged_for >= timeout {
+        log!(
+            "Incident: reader wedged for {}ms, restarting <> {}",
+            wedged_for.as_millis(),
+            input
+        );
+        input.failed(&format!("reader wedged for {}ms", wedged_for.as_millis()));
+        // The new reader starts writers of its own
+        for output in input.outputs.iter() {
+            output.worker.replace();
+        }
+        input.worker.replace();
+        // The wedged reader is left to exit once it returns
+        *reader = spawn_reader(signal, input);
+        input.restarted(RestartReason::Wedged);
+        return;
+    }
+    for output in input.outputs.iter() {
+        let wedged_for = output.worker.wedged_for();
+        if !output.configuration.enabled || wedged_for < timeout {
+            continue;
+        }
+        log!(
+            "Incident: writer wedged for {}ms, restarting <> {}",
+            wedged_for.as_millis(),
+            output
+        );
+        output.failed(&format!("writer wedged for {}ms", wedged_for.as_millis()));
+        output.worker.replace();
+        // The reader owns the writers and starts the new one
+        output.worker.respawn.store(true, Ordering::SeqCst);
+        output.restarted(RestartReason::Wedged);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{create_splitting_threads, register_sink, register_source, Parser, Sink, Source};
+    use crate::{SIG_RUN, TIME_OUT};
+    use std::env::temp_dir;
+    use std::fs;
+    use std::io;
+
+    /// Records `0\n`, `1\n`... once the first read returned, which blocks
+    /// when `stuck`
+    struct Counter(u64, bool);
+    impl Source for Counter {
+        fn open(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+        fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+            if std::mem::take(&mut self.1) {
+                thread::sleep(Duration::from_secs(2));
+            }
+            thread::sleep(Duration::from_millis(20));
+            self.0 += 1;
+            Ok(Some(format!("{}\n", self.0).into_bytes()))
+        }
+    }
+
+    /// Keeps the records, blocking on the first one when `stuck`
+    struct Memory(Arc<Mutex<Vec<Vec<u8>>>>, bool);
+    impl Sink for Memory {
+        fn open(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+        fn write(&mut self, record: &[u8]) -> io::Result<()> {
+            if std::mem::take(&mut self.1) {
+                thread::sleep(Duration::from_secs(2));
+            }
+            self.0.lock().unwrap().push(record.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replace_wedged_workers() {
+        let stuck_reads = Arc::new(AtomicBool::new(true));
+        register_source("wedge", move |target| {
+            let stuck = target == "stuck" && stuck_reads.swap(false, Ordering::SeqCst);
+            Ok(Box::new(Counter(0, stuck)))
+        });
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink_written = Arc::clone(&written);
+        let stuck_writes = Arc::new(AtomicBool::new(true));
+        register_sink("wedge", move |_| {
+            let stuck = stuck_writes.swap(false, Ordering::SeqCst);
+            Ok(Box::new(Memory(Arc::clone(&sink_written), stuck)))
+        });
+
+        let file_name = temp_dir().join("p_split_watchdog_config");
+        let file_content = "
+[DEFAULT]
+root=/tmp
+watchdog=0.3
+[PIPES]
+cvAnalogsMapperExt=1,rt,source=wedge://stuck
+cvSpeedMapperExt=1,rt,source=wedge://fine
+[cvAnalogsMapperExt]
+cvAnalogsMapperExtFuelApp=1,wt,queue=64,sink=null://
+[cvSpeedMapperExt]
+cvSpeedMapperExtFuelApp=1,wt,queue=64,sink=wedge://memory
+";
+        register_sink("null", |_| Ok(Box::new(Memory(Arc::default(), false))));
+        fs::write(&file_name, file_content).expect("write");
+        let config = Parser::load_from_file(&file_name).expect("Should load configuration ");
+        let timeout = config.settings.watchdog.expect("watchdog");
+        assert_eq!(timeout, Duration::from_millis(300));
+
+        let signal = Arc::new(Mutex::new(SIG_RUN));
+        let readers = create_splitting_threads(&config.inputs, &signal);
+        let watchdog = spawn(
+            config.inputs.clone(),
+            Arc::clone(&signal),
+            readers,
+            timeout,
+            TIME_OUT,
+        );
+
+        // Both get a new worker well before the wedged ones return
+        thread::sleep(Duration::from_secs(1));
+        let stuck = &config.inputs[0];
+        assert_eq!(stuck.status.errors(), 1);
+        assert!(stuck.status.records() > 0);
+        let memory = &config.inputs[1].outputs[0];
+        assert_eq!(memory.status.errors(), 1);
+        assert!(!written.lock().unwrap().is_empty());
+
+        *signal.lock().unwrap() = SIG_EXIT;
+        for reader in watchdog.join().expect("watchdog") {
+            let _ = reader.join();
+        }
+    }
+}